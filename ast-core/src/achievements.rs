@@ -0,0 +1,470 @@
+use crate::{
+    audio::SoundEffect,
+    gamestate::Gamestate,
+    i18n::Localization,
+    menus::button,
+    stats::{LifetimeStats, RunStats},
+};
+use chrono::Local;
+use macroquad::prelude::{
+    BLACK, DARKGRAY, GOLD, GRAY, RED, WHITE, clear_background, draw_text, measure_text,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{read_to_string, write},
+};
+
+/// Definitions shipped with the game
+pub const ACHIEVEMENT_DEFINITIONS: &str = "./assets/achievements.json";
+/// Unlocks of the player
+pub const ACHIEVEMENTS_FILE: &str = "unlocked_achievements.json";
+/// Version written in the unlock file
+pub const ACHIEVEMENTS_VERSION: u32 = 1;
+
+/// What must happen to unlock an achievement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    /// A statistic of the current run, see [`RunStats::get_stat`]
+    RunStat { stat: String, at_least: f64 },
+    /// A lifetime total, see [`LifetimeStats::get_stat`]
+    LifetimeStat { stat: String, at_least: f64 },
+    /// Clear a wave without losing any shield
+    FlawlessWave,
+    /// Destroy this many asteroids with the missiles of a single homing salvo
+    Salvo { kills: u32 },
+    /// Clear every wave
+    Win,
+    /// Clear every wave with the maximum score
+    MaxScore,
+}
+
+/// Entry of the definitions file, the name and description are the catalog keys
+/// `achievement.<id>` and `achievement.<id>.description`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Achievement {
+    pub id: String,
+    pub condition: Condition,
+}
+
+impl Achievement {
+    pub fn name_key(&self) -> String {
+        format!("achievement.{}", self.id)
+    }
+
+    pub fn description_key(&self) -> String {
+        format!("achievement.{}.description", self.id)
+    }
+}
+
+/// What happened in the game, checked against the conditions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    /// A missile destroyed an asteroid, with the salvo of the missile for homing ones
+    Kill {
+        salvo: Option<u64>,
+    },
+    WaveCleared,
+    RunEnded {
+        won: bool,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UnlockFile {
+    version: u32,
+    /// Id and local date of the unlock
+    unlocked: BTreeMap<String, String>,
+}
+
+/// Definitions, unlocks and the progress of the current run
+#[derive(Debug, Default)]
+pub struct Achievements {
+    definitions: Vec<Achievement>,
+    unlocked: BTreeMap<String, String>,
+    /// Shield lost before the current wave
+    wave_damage: f32,
+    salvo_kills: HashMap<u64, u32>,
+}
+
+#[allow(unused)]
+impl Achievements {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the definitions, the unknown statistics are reported and their achievement ignored
+    pub fn load_definitions(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let definitions: Vec<Achievement> = serde_json::from_str(&read_to_string(path)?)?;
+        let stats = RunStats::new();
+        let lifetime = LifetimeStats::new();
+        self.definitions = definitions
+            .into_iter()
+            .filter(|achievement| {
+                let known = match &achievement.condition {
+                    Condition::RunStat { stat, .. } => stats.get_stat(stat).is_some(),
+                    Condition::LifetimeStat { stat, .. } => lifetime.get_stat(stat).is_some(),
+                    _ => true,
+                };
+                if !known {
+                    eprintln!(
+                        "[WARN] Unknown statistic in the achievement {:?}",
+                        achievement.id
+                    );
+                }
+                known
+            })
+            .collect();
+        Ok(())
+    }
+
+    pub fn load_unlocked(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file: UnlockFile = serde_json::from_str(&read_to_string(path)?)?;
+        if file.version != ACHIEVEMENTS_VERSION {
+            return Err(format!("unsupported version {}", file.version).into());
+        }
+        self.unlocked = file.unlocked;
+        Ok(())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = UnlockFile {
+            version: ACHIEVEMENTS_VERSION,
+            unlocked: self.unlocked.clone(),
+        };
+        write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+
+    /// Forget the progress of the previous run
+    pub fn start_run(&mut self) {
+        self.wave_damage = 0.0;
+        self.salvo_kills.clear();
+    }
+
+    /// Unlock the achievements whose condition is met, returns the new ones
+    pub fn handle(
+        &mut self,
+        event: GameEvent,
+        stats: &RunStats,
+        lifetime: &LifetimeStats,
+        score: [u128; 2],
+    ) -> Vec<Achievement> {
+        let salvo = match event {
+            GameEvent::Kill { salvo: Some(salvo) } => {
+                let kills = self.salvo_kills.entry(salvo).or_insert(0);
+                *kills += 1;
+                *kills
+            }
+            _ => 0,
+        };
+        let flawless = event == GameEvent::WaveCleared && stats.damage_taken <= self.wave_damage;
+        if event == GameEvent::WaveCleared {
+            self.wave_damage = stats.damage_taken;
+        }
+        let won = event == GameEvent::RunEnded { won: true };
+
+        let mut unlocked = Vec::new();
+        for achievement in &self.definitions {
+            if self.unlocked.contains_key(&achievement.id) {
+                continue;
+            }
+            let met = match &achievement.condition {
+                Condition::RunStat { stat, at_least } => {
+                    stats.get_stat(stat).is_some_and(|value| value >= *at_least)
+                }
+                Condition::LifetimeStat { stat, at_least } => lifetime
+                    .get_stat(stat)
+                    .is_some_and(|value| value >= *at_least),
+                Condition::FlawlessWave => flawless,
+                Condition::Salvo { kills } => salvo >= *kills,
+                Condition::Win => won,
+                Condition::MaxScore => won && score[0] >= score[1],
+            };
+            if met {
+                unlocked.push(achievement.clone());
+            }
+        }
+        let date = Local::now().format("%Y-%m-%d %H:%M").to_string();
+        for achievement in &unlocked {
+            ast_lib::log!("[INFO] Achievement unlocked: {}", achievement.id);
+            self.unlocked.insert(achievement.id.clone(), date.clone());
+        }
+        unlocked
+    }
+
+    pub fn get_definitions(&self) -> &Vec<Achievement> {
+        &self.definitions
+    }
+
+    /// Local date of the unlock
+    pub fn get_unlock_date(&self, id: &str) -> Option<&String> {
+        self.unlocked.get(id)
+    }
+
+    pub fn get_unlocked_count(&self) -> usize {
+        self.definitions
+            .iter()
+            .filter(|a| self.unlocked.contains_key(&a.id))
+            .count()
+    }
+}
+
+/// The "Achievements" screen, reachable from the start screen
+pub fn draw_gallery(gamestate: &mut Gamestate, screen_width: f32, screen_height: f32) {
+    clear_background(BLACK);
+    let locale: &Localization = &gamestate.locale;
+    let achievements = &gamestate.achievements;
+
+    let title = locale.format(
+        "achievements.title",
+        &[
+            ("unlocked", achievements.get_unlocked_count().to_string()),
+            ("total", achievements.get_definitions().len().to_string()),
+        ],
+    );
+    draw_text(
+        &title,
+        (screen_width - measure_text(&title, None, 80, 1.0).width) / 2.0,
+        100.0,
+        80.0,
+        WHITE,
+    );
+
+    let definitions = achievements.get_definitions();
+    let row_height = (screen_height * 0.65 / definitions.len().max(1) as f32).min(70.0);
+    for (row, achievement) in definitions.iter().enumerate() {
+        let y = screen_height * 0.18 + row as f32 * row_height;
+        let date = achievements.get_unlock_date(&achievement.id);
+        let (name_color, description_color) = match date {
+            Some(_) => (GOLD, WHITE),
+            None => (GRAY, DARKGRAY),
+        };
+        draw_text(
+            locale.tr(&achievement.name_key()),
+            screen_width * 0.1,
+            y + row_height * 0.45,
+            row_height * 0.45,
+            name_color,
+        );
+        draw_text(
+            locale.tr(&achievement.description_key()),
+            screen_width * 0.1,
+            y + row_height * 0.85,
+            row_height * 0.35,
+            description_color,
+        );
+        let status = match date {
+            Some(date) => locale.format("achievements.unlocked", &[("date", date.clone())]),
+            None => locale.tr("achievements.locked").to_string(),
+        };
+        draw_text(
+            &status,
+            screen_width * 0.7,
+            y + row_height * 0.6,
+            row_height * 0.4,
+            name_color,
+        );
+    }
+
+    if button(
+        screen_width / 2.0 - 0.2 * screen_width,
+        screen_height * 0.88,
+        0.4 * screen_width,
+        0.05 * screen_height,
+        gamestate.locale.tr("highscores.back"),
+        48.0,
+        RED,
+    ) {
+        gamestate.menu.pop();
+        gamestate.audio.play(SoundEffect::UiBlip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asteroid::Asteroid;
+
+    const DEFINITIONS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/achievements.json");
+
+    /// A run where `stat` reaches `value`, every statistic of the definitions has a case
+    fn run_with(stat: &str, value: f64) -> RunStats {
+        let mut stats = RunStats::new();
+        match stat {
+            "kills" | "cannon_kills" => {
+                for _ in 0..value as u32 {
+                    stats.record_kill(Asteroid::SCALE, false);
+                }
+            }
+            "time_alive" => stats.record_time(value),
+            "distance" => stats.record_flight(1.0, value as f32),
+            other => panic!("no run reaching {:?}, add it to the test", other),
+        }
+        assert_eq!(stats.get_stat(stat), Some(value));
+        stats
+    }
+
+    fn lifetime_with(stat: &str, value: f64) -> LifetimeStats {
+        let mut lifetime = LifetimeStats::new();
+        match stat {
+            "runs" => {
+                for _ in 0..value as u32 {
+                    lifetime.add_run(&RunStats::new());
+                }
+            }
+            other => lifetime.add_run(&run_with(other, value)),
+        }
+        assert_eq!(lifetime.get_stat(stat), Some(value));
+        lifetime
+    }
+
+    fn only(achievement: &Achievement) -> Achievements {
+        Achievements {
+            definitions: vec![achievement.clone()],
+            ..Default::default()
+        }
+    }
+
+    fn handle(achievements: &mut Achievements, event: GameEvent, stats: &RunStats) -> bool {
+        let score = [0, 0];
+        !achievements
+            .handle(event, stats, &LifetimeStats::new(), score)
+            .is_empty()
+    }
+
+    #[test]
+    fn every_shipped_achievement_can_be_unlocked() {
+        let mut shipped = Achievements::new();
+        shipped.load_definitions(DEFINITIONS).unwrap();
+        let count =
+            serde_json::from_str::<Vec<serde_json::Value>>(&read_to_string(DEFINITIONS).unwrap())
+                .unwrap()
+                .len();
+        // None was dropped for an unknown statistic
+        assert_eq!(shipped.get_definitions().len(), count);
+
+        let kill = GameEvent::Kill { salvo: None };
+        let none = LifetimeStats::new();
+        for achievement in shipped.get_definitions() {
+            let mut achievements = only(achievement);
+            let id = &achievement.id;
+            match &achievement.condition {
+                Condition::RunStat { stat, at_least } => {
+                    let below = run_with(stat, at_least - 1.0);
+                    assert!(!handle(&mut achievements, kill, &below), "{}", id);
+                    assert!(
+                        handle(&mut achievements, kill, &run_with(stat, *at_least)),
+                        "{}",
+                        id
+                    );
+                }
+                Condition::LifetimeStat { stat, at_least } => {
+                    let stats = RunStats::new();
+                    let below = lifetime_with(stat, at_least - 1.0);
+                    assert!(
+                        achievements.handle(kill, &stats, &below, [0, 0]).is_empty(),
+                        "{}",
+                        id
+                    );
+                    let reached = lifetime_with(stat, *at_least);
+                    assert!(
+                        !achievements
+                            .handle(kill, &stats, &reached, [0, 0])
+                            .is_empty(),
+                        "{}",
+                        id
+                    );
+                }
+                Condition::FlawlessWave => {
+                    let mut stats = RunStats::new();
+                    stats.record_damage(10.0);
+                    let cleared = GameEvent::WaveCleared;
+                    assert!(!handle(&mut achievements, cleared, &stats), "{}", id);
+                    // The damage of the previous waves does not count
+                    assert!(handle(&mut achievements, cleared, &stats), "{}", id);
+                }
+                Condition::Salvo { kills } => {
+                    let stats = RunStats::new();
+                    for _ in 1..*kills {
+                        let first = GameEvent::Kill { salvo: Some(1) };
+                        assert!(!handle(&mut achievements, first, &stats), "{}", id);
+                    }
+                    // Another salvo starts from zero
+                    let other = GameEvent::Kill { salvo: Some(2) };
+                    assert!(!handle(&mut achievements, other, &stats), "{}", id);
+                    let last = GameEvent::Kill { salvo: Some(1) };
+                    assert!(handle(&mut achievements, last, &stats), "{}", id);
+                }
+                Condition::Win => {
+                    let stats = RunStats::new();
+                    let lost = GameEvent::RunEnded { won: false };
+                    assert!(!handle(&mut achievements, lost, &stats), "{}", id);
+                    let won = GameEvent::RunEnded { won: true };
+                    assert!(handle(&mut achievements, won, &stats), "{}", id);
+                }
+                Condition::MaxScore => {
+                    let stats = RunStats::new();
+                    let won = GameEvent::RunEnded { won: true };
+                    let missed = achievements.handle(won, &stats, &none, [90, 100]);
+                    assert!(missed.is_empty(), "{}", id);
+                    let lost = GameEvent::RunEnded { won: false };
+                    let lost = achievements.handle(lost, &stats, &none, [100, 100]);
+                    assert!(lost.is_empty(), "{}", id);
+                    let max = achievements.handle(won, &stats, &none, [100, 100]);
+                    assert!(!max.is_empty(), "{}", id);
+                }
+            }
+            assert!(achievements.get_unlock_date(id).is_some(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn achievements_unlock_once_and_are_saved() {
+        let achievement = Achievement {
+            id: String::from("victory"),
+            condition: Condition::Win,
+        };
+        let mut achievements = only(&achievement);
+        let won = GameEvent::RunEnded { won: true };
+        assert!(handle(&mut achievements, won, &RunStats::new()));
+        assert!(!handle(&mut achievements, won, &RunStats::new()));
+        assert_eq!(achievements.get_unlocked_count(), 1);
+
+        let path = std::env::temp_dir().join(format!("achievements-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        achievements.save(path).unwrap();
+        let mut loaded = only(&achievement);
+        loaded.load_unlocked(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            loaded.get_unlock_date("victory"),
+            achievements.get_unlock_date("victory")
+        );
+    }
+
+    #[test]
+    fn unknown_statistics_are_dropped() {
+        let path = std::env::temp_dir().join(format!("definitions-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        write(
+            path,
+            r#"[
+                {"id": "typo", "condition": {"type": "run_stat", "stat": "kils", "at_least": 1}},
+                {"id": "victory", "condition": {"type": "win"}}
+            ]"#,
+        )
+        .unwrap();
+        let mut achievements = Achievements::new();
+        achievements.load_definitions(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let ids: Vec<&str> = achievements
+            .get_definitions()
+            .iter()
+            .map(|achievement| achievement.id.as_str())
+            .collect();
+        assert_eq!(ids, ["victory"]);
+    }
+}
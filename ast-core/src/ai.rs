@@ -0,0 +1,438 @@
+use crate::{
+    asteroid::Asteroid,
+    gamestate::{Gamestate, TICKS},
+    key_bindings::{Action, TickInput},
+    spaceship::Spaceship,
+};
+use ast_lib::CosmicEntity;
+use macroquad::prelude::{Vec2, vec2};
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use std::{f32::consts::PI, str::FromStr};
+
+/// How well the autopilot plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Ticks between two decisions, the actions are held meanwhile
+    pub fn get_reaction_ticks(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 20,
+            Difficulty::Normal => 8,
+            Difficulty::Hard => 2,
+        }
+    }
+
+    /// Largest random error added to the aim, in radians
+    pub fn get_aim_error(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.3,
+            Difficulty::Normal => 0.1,
+            Difficulty::Hard => 0.0,
+        }
+    }
+
+    /// Distance under which the asteroids are noticed
+    pub fn get_awareness(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 350.0,
+            Difficulty::Normal => 500.0,
+            Difficulty::Hard => 700.0,
+        }
+    }
+
+    /// Nearby targets needed before launching a homing salvo, never for the easy bot
+    pub fn get_salvo_targets(&self) -> Option<usize> {
+        match self {
+            Difficulty::Easy => None,
+            Difficulty::Normal => Some(4),
+            Difficulty::Hard => Some(2),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("unknown difficulty {:?}", s)),
+        }
+    }
+}
+
+/// Something the autopilot may shoot or dodge, in world coordinates
+#[derive(Debug, Clone)]
+struct Body {
+    position: Vec2,
+    velocity: Vec2,
+    size: f32,
+}
+
+impl Body {
+    fn from_asteroid(asteroid: &Asteroid) -> Self {
+        Body {
+            position: asteroid.get_position(),
+            velocity: vec2(
+                asteroid.get_direction().cos(),
+                asteroid.get_direction().sin(),
+            ) * asteroid.get_speed()
+                * asteroid.get_speed_multiplier(),
+            size: asteroid.get_size(),
+        }
+    }
+}
+
+/// Bot playing a ship with the same actions as a human, one [`TickInput`] per tick.
+///
+/// Every few ticks it looks at the nearby asteroids: it dodges the one about to hit the
+/// ship, otherwise it turns to the lead point of the best target and fires once aligned.
+/// In versus the other ships are the targets and the asteroids only obstacles.
+pub struct Autopilot {
+    player: usize,
+    difficulty: Difficulty,
+    /// Own generator, the simulation draws the same numbers with or without bots
+    rng: SmallRng,
+    /// Ticks before the next decision
+    cooldown: u32,
+    decision: TickInput,
+}
+
+#[allow(unused)]
+impl Autopilot {
+    /// Seconds ahead in which an asteroid on a collision course is dodged
+    pub const HORIZON: f32 = 1.2;
+    /// Speed the bot keeps when nothing threatens it
+    pub const CRUISE_SPEED: f32 = 120.0;
+
+    pub fn new(player: usize, difficulty: Difficulty) -> Self {
+        Autopilot {
+            player,
+            difficulty,
+            rng: SmallRng::seed_from_u64(player as u64),
+            cooldown: 0,
+            decision: TickInput::default(),
+        }
+    }
+
+    pub fn get_player(&self) -> usize {
+        self.player
+    }
+
+    pub fn get_difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Input of the next tick, decided again every [`Difficulty::get_reaction_ticks`] ticks
+    pub fn next_tick(&mut self, gamestate: &Gamestate) -> TickInput {
+        let Some(player) = gamestate.players.get(self.player) else {
+            return TickInput::default();
+        };
+        if !player.is_alive() {
+            self.decision = TickInput::default();
+            return TickInput::default();
+        }
+
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            // Only the held actions last until the next decision
+            return TickInput {
+                held: self.decision.held.clone(),
+                ..Default::default()
+            };
+        }
+        self.cooldown = self.difficulty.get_reaction_ticks() - 1;
+        self.decision = self.decide(gamestate, &player.spaceship);
+        self.decision.clone()
+    }
+
+    fn decide(&mut self, gamestate: &Gamestate, spaceship: &Spaceship) -> TickInput {
+        let mut input = TickInput::default();
+        let position = spaceship.get_position();
+        let velocity = ship_velocity(spaceship);
+        let awareness = self.difficulty.get_awareness();
+
+        let asteroids: Vec<Body> = gamestate
+            .asteroids
+            .iter()
+            .filter(|a| a.get_position().distance(position) - a.get_size() < awareness)
+            .map(Body::from_asteroid)
+            .collect();
+        let targets: Vec<Body> = if gamestate.versus.is_some() {
+            gamestate
+                .players
+                .iter()
+                .enumerate()
+                .filter(|(index, p)| *index != self.player && p.is_alive())
+                .map(|(_, p)| Body {
+                    position: p.spaceship.get_position(),
+                    velocity: ship_velocity(&p.spaceship),
+                    size: p.spaceship.get_size(),
+                })
+                .collect()
+        } else {
+            asteroids.clone()
+        };
+
+        // The asteroid that would hit the ship first
+        let threat = asteroids
+            .iter()
+            .filter_map(|a| {
+                let (time, distance) = closest_approach(position, velocity, a);
+                (time < Self::HORIZON && distance < a.size + spaceship.get_size() * 1.5)
+                    .then_some((time, a))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+
+        let heading = match threat {
+            Some((_, asteroid)) => {
+                // Dodge across the path of the asteroid, on the side the ship already is
+                let relative = asteroid.velocity - velocity;
+                let away = position - asteroid.position;
+                let across = vec2(-relative.y, relative.x);
+                let escape = if across.dot(away) >= 0.0 {
+                    across
+                } else {
+                    -across
+                };
+                input.held.push(Action::SpeedUp);
+                angle_of(escape)
+            }
+            None => {
+                // Brake back to the cruise speed
+                if spaceship.get_speed() > Self::CRUISE_SPEED {
+                    input.held.push(Action::SpeedDown);
+                } else if spaceship.get_speed() < -Self::CRUISE_SPEED {
+                    input.held.push(Action::SpeedUp);
+                }
+                // Towards the nearest target, ahead of it
+                let target = targets.iter().min_by(|a, b| {
+                    let da = a.position.distance(position) - a.size;
+                    let db = b.position.distance(position) - b.size;
+                    da.total_cmp(&db)
+                });
+                match target {
+                    Some(target) => {
+                        angle_of(lead_point(position, spaceship.get_max_speed(), target) - position)
+                    }
+                    None => spaceship.get_rotation(),
+                }
+            }
+        };
+        let error = self.difficulty.get_aim_error();
+        let heading = if error > 0.0 {
+            heading + self.rng.gen_range(-error..=error)
+        } else {
+            heading
+        };
+
+        // Turn the shortest way
+        let difference = normalize_angle(heading - spaceship.get_rotation());
+        let step = spaceship.get_turn_rate() * TICKS as f32;
+        if difference > step {
+            input.held.push(Action::MoveRight);
+        } else if difference < -step {
+            input.held.push(Action::MoveLeft);
+        }
+
+        // Fire when a target is in front
+        let aligned = targets.iter().any(|target| {
+            let to_target = target.position - position;
+            let spread = (target.size / to_target.length().max(1.0)).atan();
+            to_target.length() < self.difficulty.get_awareness()
+                && normalize_angle(angle_of(to_target) - spaceship.get_rotation()).abs()
+                    < spread.max(0.05)
+        });
+        if aligned {
+            input.held.push(Action::Fire);
+        }
+
+        // Homing salvo when surrounded
+        if let Some(needed) = self.difficulty.get_salvo_targets()
+            && targets.len() >= needed
+            && spaceship.get_missile_capacity() > 0
+            && spaceship.get_homming_cooldown() <= 0.0
+        {
+            input.pressed.push(Action::FireHoming);
+        }
+
+        // More homing missiles in a crowded field, fewer when it clears
+        if self.difficulty != Difficulty::Easy {
+            let wanted = (gamestate.asteroids.len() / 4).clamp(1, 8) as u8;
+            let capacity = spaceship.get_missile_capacity();
+            if capacity < wanted {
+                input.pressed.push(Action::IncreaseMissileCapacity);
+            } else if capacity > wanted {
+                input.pressed.push(Action::DecreaseMissileCapacity);
+            }
+        }
+
+        input
+    }
+}
+
+/// Velocity of a ship, it moves along its rotation
+fn ship_velocity(spaceship: &Spaceship) -> Vec2 {
+    vec2(
+        spaceship.get_rotation().cos(),
+        -spaceship.get_rotation().sin(),
+    ) * spaceship.get_speed()
+}
+
+/// Rotation of a ship facing `direction`
+fn angle_of(direction: Vec2) -> f32 {
+    (-direction.y).atan2(direction.x)
+}
+
+/// Angle between -PI and PI
+fn normalize_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+/// Seconds before `body` is the closest to a ship at `position` moving at `velocity`,
+/// and the distance between their centers then
+fn closest_approach(position: Vec2, velocity: Vec2, body: &Body) -> (f32, f32) {
+    let offset = body.position - position;
+    let relative = body.velocity - velocity;
+    let speed = relative.length_squared();
+    let time = if speed > f32::EPSILON {
+        (-offset.dot(relative) / speed).max(0.0)
+    } else {
+        0.0
+    };
+    (time, (offset + relative * time).length())
+}
+
+/// Where to shoot a missile at `missile_speed` so it meets `target`
+fn lead_point(position: Vec2, missile_speed: f32, target: &Body) -> Vec2 {
+    let mut aim = target.position;
+    for _ in 0..3 {
+        let time = aim.distance(position) / missile_speed.max(1.0);
+        aim = target.position + target.velocity * time;
+    }
+    aim
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_bindings::InputBuffer;
+    use ast_lib::seed_simulation_rng;
+
+    const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    fn field(seed: u64) -> Gamestate {
+        seed_simulation_rng(seed);
+        let mut gamestate = Gamestate::headless();
+        gamestate.update_simulation_speed();
+        gamestate
+    }
+
+    /// An asteroid `distance` in front of the first ship, moving at `speed` along `direction`
+    fn asteroid_ahead(gamestate: &mut Gamestate, distance: f32, speed: f32, direction: f32) {
+        let spaceship = &gamestate.players[0].spaceship;
+        let position = spaceship.position_in_front_with_rotation(distance, 0.0);
+        gamestate.asteroids = vec![Asteroid::new(
+            Some(position),
+            Some(speed),
+            Some(Asteroid::SCALE * 2.0),
+            None,
+            Some(direction),
+            Some(1.0),
+            None,
+            None,
+        )];
+    }
+
+    fn assert_valid(input: &TickInput, difficulty: Difficulty) {
+        for action in input.held.iter().chain(&input.pressed) {
+            assert!(InputBuffer::GAMEPLAY.contains(action), "{:?}", action);
+        }
+        for (i, action) in input.held.iter().enumerate() {
+            assert!(!input.held[i + 1..].contains(action), "{:?} twice", action);
+        }
+        assert!(!(input.is_held(Action::SpeedUp) && input.is_held(Action::SpeedDown)));
+        assert!(!(input.is_held(Action::MoveLeft) && input.is_held(Action::MoveRight)));
+        if difficulty == Difficulty::Easy {
+            assert!(input.pressed.is_empty());
+        }
+        assert!(input.scrolled.is_empty());
+    }
+
+    #[test]
+    fn difficulties_parse_in_any_case() {
+        assert_eq!("EASY".parse(), Ok(Difficulty::Easy));
+        assert_eq!("normal".parse(), Ok(Difficulty::Normal));
+        assert_eq!("Hard".parse(), Ok(Difficulty::Hard));
+        assert!("impossible".parse::<Difficulty>().is_err());
+    }
+
+    #[test]
+    fn every_difficulty_plays_valid_inputs() {
+        for difficulty in DIFFICULTIES {
+            let mut gamestate = field(3);
+            let mut bot = Autopilot::new(0, difficulty);
+            let reaction = difficulty.get_reaction_ticks() as usize;
+            for tick in 0..600 {
+                let input = bot.next_tick(&gamestate);
+                assert_valid(&input, difficulty);
+                // Presses only come with a decision
+                if tick % reaction != 0 {
+                    assert!(input.pressed.is_empty(), "{:?} at {}", difficulty, tick);
+                }
+                gamestate.tick(&[input]);
+                if !gamestate.is_any_alive() {
+                    break;
+                }
+            }
+            assert!(
+                gamestate.stats.get_fired() > 0,
+                "{:?} never fired",
+                difficulty
+            );
+        }
+    }
+
+    #[test]
+    fn bots_fire_at_what_is_ahead() {
+        for difficulty in DIFFICULTIES {
+            let mut gamestate = field(1);
+            asteroid_ahead(&mut gamestate, 250.0, 0.0, 0.0);
+            let input = Autopilot::new(0, difficulty).next_tick(&gamestate);
+            assert!(input.is_held(Action::Fire), "{:?}", difficulty);
+        }
+    }
+
+    #[test]
+    fn bots_dodge_what_comes_at_them() {
+        for difficulty in DIFFICULTIES {
+            let mut gamestate = field(1);
+            // Coming straight back at the ship
+            let rotation = gamestate.players[0].spaceship.get_rotation();
+            asteroid_ahead(&mut gamestate, 200.0, 300.0, PI - rotation);
+            let input = Autopilot::new(0, difficulty).next_tick(&gamestate);
+            assert!(input.is_held(Action::SpeedUp), "{:?}", difficulty);
+        }
+    }
+
+    #[test]
+    fn dead_or_missing_ships_do_nothing() {
+        let mut gamestate = field(1);
+        assert_eq!(
+            Autopilot::new(3, Difficulty::Hard).next_tick(&gamestate),
+            TickInput::default()
+        );
+        gamestate.players[0].spaceship.set_life(false);
+        assert_eq!(
+            Autopilot::new(0, Difficulty::Hard).next_tick(&gamestate),
+            TickInput::default()
+        );
+    }
+}
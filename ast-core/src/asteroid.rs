@@ -1,11 +1,12 @@
-use ast_lib::{CosmicEntity, NamedTexture, Change, generate_uid, select_weighted_texture, MISSING_TEXTURE, TEXTURE_SET};
+use ast_lib::{CosmicEntity, NamedTexture, Change, generate_uid, screen_size, select_variant, simulation_rng};
 use mac_der::Entity;
 use std::f32::consts::PI;
 use macroquad::prelude::{
-    draw_circle_lines, draw_line, draw_texture_ex, draw_text, measure_text, screen_dpi_scale, screen_height,
-    screen_width, vec2, DrawTextureParams, Vec2, BLUE, GREEN, RED, WHITE, YELLOW,
+    draw_text, measure_text, screen_dpi_scale, screen_height, screen_width, vec2,
+    DrawTextureParams, Vec2, BLUE, GREEN, RED, WHITE, YELLOW,
 };
-use ::rand::{thread_rng, Rng};
+use crate::camera::Camera;
+use ::rand::Rng;
 
 
 #[derive(PartialEq, Clone, Entity)]
@@ -26,11 +27,25 @@ impl Asteroid {
     /// Affects both the visual and the physics
     pub const SCALE: f32 = 30.0;
 
-    /// Default constructor using static TEXTURE_SET
+    /// Default constructor using the current asset pack
     pub fn new_default() -> Self {
         Self::new(None, None, None, None, None, None, None, None)
     }
 
+    /// Default constructor spawning near the edges of a world of size `bounds`
+    pub fn new_in_bounds(bounds: &Vec2) -> Self {
+        Self::new(
+            Some(Self::new_alea_pos(30.0, bounds)),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
     /// Main constructor
     pub fn new(
         position: Option<Vec2>,
@@ -42,11 +57,12 @@ impl Asteroid {
         turn_rate: Option<f32>,
         texture: Option<NamedTexture>,
     ) -> Self {
-        let mut rng = thread_rng();
+        let mut rng = simulation_rng();
         let new_properties = Self::new_properties();
 
         // Default values
-        let default_position = position.unwrap_or_else(|| Self::new_alea_pos(30.0));
+        let default_position = position
+            .unwrap_or_else(|| Self::new_alea_pos(30.0, &screen_size()));
         let default_speed = speed.unwrap_or(new_properties.2);
         let default_size = size.unwrap_or(rng.gen_range(2..=3) as f32 * Self::SCALE);
         let default_rotation = rotation.unwrap_or(Self::new_rotation());
@@ -56,9 +72,7 @@ impl Asteroid {
         let default_turn_rate = turn_rate.unwrap_or(rng.gen_range(0.5..1.5) * if rng.gen_bool(0.5) { 1.0 } else { -1.0 });
 
         // Texture selection:
-        let default_texture = texture
-            .or_else(|| select_weighted_texture(&TEXTURE_SET, "asteroid/", vec![85.0, 10.0, 5.0]))
-            .unwrap_or_else(|| MISSING_TEXTURE.clone());
+        let default_texture = texture.unwrap_or_else(|| select_variant("asteroid"));
 
         Self {
             id: generate_uid(),
@@ -81,6 +95,10 @@ impl Asteroid {
         &self.texture
     }
 
+    pub fn set_texture(&mut self, texture: NamedTexture) {
+        self.texture = texture;
+    }
+
     pub fn get_speed_multiplier(&self) -> f32 {
         self.speed_multiplier
     }
@@ -95,36 +113,36 @@ impl Asteroid {
     }
 
     // Moves the object based on its speed, applying inertia.
-    pub fn update(&mut self, delta_time: f64) {
+    pub fn update(&mut self, delta_time: f64, bounds: &Vec2) {
         let direction = vec2(self.direction.cos(), self.direction.sin());
         self.rotation += self.turn_rate * delta_time as f32;
         self.position += direction * self.speed * self.get_speed_multiplier() * delta_time as f32;
         // Move at the opposite edge
-        self.position = Self::bound_pos(self.position);
+        self.position = Self::bound_pos(self.position, bounds);
     }
 
-    /// Generates a random position near one of the screen edges.
-    fn new_alea_pos(offset: f32) -> Vec2 {
-        let mut rng = thread_rng();
+    /// Generates a random position near one of the world edges.
+    fn new_alea_pos(offset: f32, bounds: &Vec2) -> Vec2 {
+        let mut rng = simulation_rng();
         let nearpos: f32 = rng.gen_range(offset * 0.5..=offset);
         // 1 = top, 2 = right, 3 = bottom, 4 = left
         let nearside = rng.gen_range(1..=4);
         let xpos: f32 = match nearside {
-            2 => screen_width() - nearpos,
+            2 => bounds.x - nearpos,
             4 => nearpos,
-            _ => rng.gen_range(0.0..=screen_width()),
+            _ => rng.gen_range(0.0..=bounds.x),
         };
         let ypos: f32 = match nearside {
             1 => nearpos,
-            3 => screen_height() - nearpos,
-            _ => rng.gen_range(0.0..=screen_height()),
+            3 => bounds.y - nearpos,
+            _ => rng.gen_range(0.0..=bounds.y),
         };
         vec2(xpos, ypos)
     }
 
     /// Create properties based on each other and assign them to a tuple for the constructor
     fn new_properties() -> (f32, f32, f32) {
-        let mut rng = thread_rng();
+        let mut rng = simulation_rng();
         let size = rng.gen_range(1..=3) as f32 * Self::SCALE;
         let speed_multiplier = rng.gen_range(0.4..=1.5);
         let size_to_speed = match size {
@@ -141,13 +159,13 @@ impl Asteroid {
     }
 
     fn new_rotation() -> f32 {
-        let mut rng = thread_rng();
+        let mut rng = simulation_rng();
         rng.gen_range(1.0..=2.0 * PI)
     }
 
-    fn bound_pos(mut pos: Vec2) -> Vec2 {
-        pos.x = Self::bound_to(pos.x, screen_width());
-        pos.y = Self::bound_to(pos.y, screen_height());
+    fn bound_pos(mut pos: Vec2, bounds: &Vec2) -> Vec2 {
+        pos.x = Self::bound_to(pos.x, bounds.x);
+        pos.y = Self::bound_to(pos.y, bounds.y);
         pos
     }
 
@@ -163,7 +181,7 @@ impl Asteroid {
 
     // Create two smaller asteroids moving forward based on rotation
     pub fn split(&self, can_add: bool, to_add: u8, change_list: &mut Vec<Change<Asteroid>>) {
-        let mut rng = thread_rng();
+        let mut rng = simulation_rng();
         let new_size = self.get_size() - Self::SCALE;
 
         if new_size <= 0.0 {
@@ -226,7 +244,7 @@ impl Asteroid {
         award
     }
 
-    pub fn draw_trajectory(&self) {
+    pub fn draw_trajectory(&self, camera: &Camera) {
         // Define the arrow length and compute the direction where the asteroid is moving
         let arrow_length = 40.0;
         // Normalize to get direction
@@ -244,31 +262,23 @@ impl Asteroid {
         let end_direction = start + direction * arrow_length;
 
         // Draw the trajectory arrow
-        draw_line(start.x, start.y, end_direction.x, end_direction.y, 2.0, RED);
+        camera.draw_line(start, end_direction, 2.0, RED);
         // Draw the rotation direction of the texture arrow
-        draw_line(
-            start.x,
-            start.y,
-            end_rotation.x,
-            end_rotation.y,
-            2.0,
-            YELLOW,
-        );
+        camera.draw_line(start, end_rotation, 2.0, YELLOW);
     }
 
-    pub fn draw_self(&self, debug: bool) {
+    pub fn draw_self(&self, camera: &Camera, debug: bool) {
         let font_size = 20.0;
         let position = self.get_position();
         let draw_pos = position - self.size; // correct centering
 
-        draw_texture_ex(
+        camera.draw_texture(
             &self.texture.texture,
             // Center the texture to the asteroid's center
-            draw_pos.x,
-            draw_pos.y,
+            draw_pos,
+            Vec2::new(self.size, self.size) * 2.0,
             WHITE,
             DrawTextureParams {
-                dest_size: Some(Vec2::new(self.size, self.size) * 2.0),
                 rotation: -self.get_rotation(),
                 ..Default::default()
             },
@@ -323,41 +333,40 @@ impl Asteroid {
 
             let text_size = *debug_text_sizes.iter().max().unwrap() as f32;
 
-            // Draw besides the asteroid
-            let x_offset = if screen_width() - position.x >= text_size + Self::SCALE {
+            // Draw besides the asteroid, on the screen
+            let screen_position = camera.world_to_screen(position);
+            let x_offset = if screen_width() - screen_position.x >= text_size + Self::SCALE {
                 self.get_size() + 5.0
             } else {
                 -text_size - self.get_size()
             };
-            let y_offset = if screen_height() - position.y >= font_size * texts.len() as f32 {
+            let y_offset = if screen_height() - screen_position.y >= font_size * texts.len() as f32 {
                 20.0
             } else {
                 -font_size * texts.len() as f32
             };
 
             // Hitbox
-            draw_circle_lines(position.x, position.y, self.get_size(), 1.0, BLUE);
+            camera.draw_circle_lines(position, self.get_size(), 1.0, BLUE);
             // Center
-            draw_circle_lines(position.x, position.y, 3.0, 1.5, BLUE);
+            camera.draw_circle_lines(position, 3.0, 1.5, BLUE);
 
             for (index, field) in &mut texts.iter_mut().enumerate() {
                 draw_text(
                     field,
-                    position.x + x_offset,
-                    (position.y + y_offset) + index as f32 * 20.0,
+                    screen_position.x + x_offset,
+                    (screen_position.y + y_offset) + index as f32 * 20.0,
                     font_size,
                     GREEN,
                 );
             }
 
             // Trajectory + Rotation
-            self.draw_trajectory();
+            self.draw_trajectory(camera);
             // Comparison line
-            draw_line(
-                self.position.x,
-                self.position.y,
-                self.position.x,
-                self.position.y - 75.0,
+            camera.draw_line(
+                self.position,
+                self.position - vec2(0.0, 75.0),
                 1.0,
                 WHITE,
             );
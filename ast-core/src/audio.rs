@@ -0,0 +1,355 @@
+use crate::{
+    music::{Intensity, MusicMixer, Stem},
+    synth,
+};
+use ast_lib::current_time;
+use macroquad::audio::{PlaySoundParams, Sound, load_sound_from_bytes, play_sound, set_sound_volume};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::{Ref, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+/// Every sound the game can make
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    Fire,
+    HomingLaunch,
+    /// Size of the asteroid that was split, from 1 (small) to 3 (big)
+    AsteroidSplit(u8),
+    ShieldHit,
+    ShipDeath,
+    Thrust,
+    UiBlip,
+}
+
+impl SoundEffect {
+    pub const ALL: [SoundEffect; 9] = [
+        SoundEffect::Fire,
+        SoundEffect::HomingLaunch,
+        SoundEffect::AsteroidSplit(1),
+        SoundEffect::AsteroidSplit(2),
+        SoundEffect::AsteroidSplit(3),
+        SoundEffect::ShieldHit,
+        SoundEffect::ShipDeath,
+        SoundEffect::Thrust,
+        SoundEffect::UiBlip,
+    ];
+}
+
+/// Volume settings, every value is between 0.0 and 1.0
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Volume {
+    pub master: f32,
+    pub sfx: f32,
+    pub music: f32,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Volume {
+            master: 1.0,
+            sfx: 0.8,
+            music: 0.6,
+        }
+    }
+}
+
+/// Something able to output sounds
+pub trait AudioBackend {
+    /// Play an effect once at the given volume
+    fn play(&mut self, effect: SoundEffect, volume: f32);
+    /// Change the volume of one layer of the music
+    fn set_stem_volume(&mut self, stem: Stem, volume: f32);
+}
+
+/// Calls received by a `NullBackend`
+#[derive(Debug, Default)]
+pub struct NullRecord {
+    /// Last effects that would have been played, with their volume
+    pub played: Vec<(SoundEffect, f32)>,
+    pub stem_volumes: HashMap<Stem, f32>,
+}
+
+/// Backend that plays nothing, used for headless runs and tests.
+///
+/// It remembers the last effects that would have been played so the calls can be checked.
+/// Clones share the same record, so keep one to inspect a backend boxed into `Audio`.
+#[derive(Debug, Default, Clone)]
+pub struct NullBackend {
+    record: Rc<RefCell<NullRecord>>,
+}
+
+#[allow(unused)]
+impl NullBackend {
+    const HISTORY: usize = 64;
+
+    pub fn get_record(&self) -> Ref<'_, NullRecord> {
+        self.record.borrow()
+    }
+}
+
+impl AudioBackend for NullBackend {
+    fn play(&mut self, effect: SoundEffect, volume: f32) {
+        let mut record = self.record.borrow_mut();
+        if record.played.len() >= Self::HISTORY {
+            record.played.remove(0);
+        }
+        record.played.push((effect, volume));
+    }
+
+    fn set_stem_volume(&mut self, stem: Stem, volume: f32) {
+        self.record.borrow_mut().stem_volumes.insert(stem, volume);
+    }
+}
+
+/// Backend using macroquad's audio, needs a sound device
+pub struct MacroquadBackend {
+    sounds: HashMap<SoundEffect, Sound>,
+    stems: HashMap<Stem, Sound>,
+}
+
+impl MacroquadBackend {
+    /// Generate every effect and music stem with the synthesizer, no file needed.
+    ///
+    /// The stems start looping right away, silent until the mixer fades them in.
+    pub async fn synthesize() -> Self {
+        let mut sounds = HashMap::new();
+        for effect in SoundEffect::ALL {
+            let wav = synth::effect_buffer(effect).to_wav();
+            match load_sound_from_bytes(&wav).await {
+                Ok(sound) => {
+                    sounds.insert(effect, sound);
+                }
+                Err(e) => eprintln!("[WARN] Could not synthesize {:?}: {:?}", effect, e),
+            }
+        }
+
+        let mut stems = HashMap::new();
+        for stem in Stem::ALL {
+            let wav = synth::stem(stem).to_wav();
+            match load_sound_from_bytes(&wav).await {
+                Ok(sound) => {
+                    play_sound(
+                        &sound,
+                        PlaySoundParams {
+                            looped: true,
+                            volume: 0.0,
+                        },
+                    );
+                    stems.insert(stem, sound);
+                }
+                Err(e) => eprintln!("[WARN] Could not synthesize {:?}: {:?}", stem, e),
+            }
+        }
+
+        ast_lib::log!(
+            "[INFO] Synthesized {} sounds and {} music stems",
+            sounds.len(),
+            stems.len()
+        );
+        MacroquadBackend { sounds, stems }
+    }
+}
+
+impl AudioBackend for MacroquadBackend {
+    fn play(&mut self, effect: SoundEffect, volume: f32) {
+        if let Some(sound) = self.sounds.get(&effect) {
+            play_sound(
+                sound,
+                PlaySoundParams {
+                    looped: false,
+                    volume,
+                },
+            );
+        }
+    }
+
+    fn set_stem_volume(&mut self, stem: Stem, volume: f32) {
+        if let Some(sound) = self.stems.get(&stem) {
+            set_sound_volume(sound, volume);
+        }
+    }
+}
+
+/// Entry point of the game for everything sound related
+pub struct Audio {
+    backend: Box<dyn AudioBackend>,
+    volume: Volume,
+    last_played: HashMap<SoundEffect, f64>,
+    mixer: MusicMixer,
+}
+
+#[allow(unused)]
+impl Audio {
+    pub fn new(backend: Box<dyn AudioBackend>) -> Self {
+        Audio {
+            backend,
+            volume: Volume::default(),
+            last_played: HashMap::new(),
+            mixer: MusicMixer::new(),
+        }
+    }
+
+    /// Silent audio, does not touch the sound device
+    pub fn null() -> Self {
+        Self::new(Box::new(NullBackend::default()))
+    }
+
+    pub fn play(&mut self, effect: SoundEffect) {
+        let volume = self.volume.master * self.volume.sfx;
+        if volume > 0.0 {
+            self.backend.play(effect, volume);
+        }
+    }
+
+    /// Play an effect unless it was already played less than `interval` seconds ago,
+    /// used for sounds repeated while a key is held
+    pub fn play_throttled(&mut self, effect: SoundEffect, interval: f64) {
+        let now = current_time();
+        let last = self.last_played.get(&effect).copied().unwrap_or(f64::MIN);
+        if now - last >= interval {
+            self.last_played.insert(effect, now);
+            self.play(effect);
+        }
+    }
+
+    /// Fade the music stems toward the current intensity of the game
+    pub fn update_music(&mut self, intensity: &Intensity, delta_time: f32) {
+        self.mixer.update(intensity, delta_time);
+        let music = self.volume.master * self.volume.music;
+        for stem in Stem::ALL {
+            self.backend
+                .set_stem_volume(stem, self.mixer.get_gain(stem) * music);
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: Box<dyn AudioBackend>) {
+        self.backend = backend;
+    }
+
+    pub fn get_volume(&self) -> Volume {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume: Volume) {
+        self.volume = Volume {
+            master: volume.master.clamp(0.0, 1.0),
+            sfx: volume.sfx.clamp(0.0, 1.0),
+            music: volume.music.clamp(0.0, 1.0),
+        };
+    }
+}
+
+/// Next volume step when cycling a setting from the menus: 100%, 75%, ..., 0%, 100%
+pub fn next_volume_step(volume: f32) -> f32 {
+    if volume <= 0.0 {
+        1.0
+    } else {
+        ((volume * 4.0).ceil() - 1.0).max(0.0) / 4.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn null_audio(volume: Volume) -> (Audio, NullBackend) {
+        ast_lib::set_headless(true);
+        let backend = NullBackend::default();
+        let mut audio = Audio::new(Box::new(backend.clone()));
+        audio.set_volume(volume);
+        (audio, backend)
+    }
+
+    #[test]
+    fn effects_play_at_master_times_sfx() {
+        let (mut audio, backend) = null_audio(Volume {
+            master: 0.5,
+            sfx: 0.8,
+            music: 1.0,
+        });
+        audio.play(SoundEffect::Fire);
+        audio.play(SoundEffect::AsteroidSplit(3));
+
+        let record = backend.get_record();
+        assert_eq!(
+            record.played,
+            vec![
+                (SoundEffect::Fire, 0.4),
+                (SoundEffect::AsteroidSplit(3), 0.4)
+            ]
+        );
+    }
+
+    #[test]
+    fn muted_volumes_play_nothing() {
+        for volume in [
+            Volume {
+                master: 0.0,
+                ..Volume::default()
+            },
+            Volume {
+                sfx: 0.0,
+                ..Volume::default()
+            },
+        ] {
+            let (mut audio, backend) = null_audio(volume);
+            audio.play(SoundEffect::ShipDeath);
+            audio.play_throttled(SoundEffect::Thrust, 0.0);
+            assert!(backend.get_record().played.is_empty());
+        }
+    }
+
+    #[test]
+    fn throttled_effects_are_not_repeated_within_the_interval() {
+        // The clock stays at 0 when headless
+        let (mut audio, backend) = null_audio(Volume::default());
+        audio.play_throttled(SoundEffect::Thrust, 0.1);
+        audio.play_throttled(SoundEffect::Thrust, 0.1);
+        audio.play_throttled(SoundEffect::Fire, 0.1);
+        assert_eq!(backend.get_record().played.len(), 2);
+
+        audio.play_throttled(SoundEffect::Thrust, 0.0);
+        assert_eq!(backend.get_record().played.len(), 3);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let (mut audio, backend) = null_audio(Volume::default());
+        for _ in 0..NullBackend::HISTORY + 10 {
+            audio.play(SoundEffect::UiBlip);
+        }
+        assert_eq!(backend.get_record().played.len(), NullBackend::HISTORY);
+    }
+
+    #[test]
+    fn music_is_scaled_by_master_times_music() {
+        let (mut audio, backend) = null_audio(Volume {
+            master: 0.5,
+            sfx: 1.0,
+            music: 0.6,
+        });
+        let intensity = Intensity {
+            nearby_asteroids: 8,
+            shield: 0.0,
+            dying: false,
+            in_menu: false,
+        };
+        audio.update_music(&intensity, 0.5);
+
+        let record = backend.get_record();
+        for stem in Stem::ALL {
+            let expected = audio.mixer.get_gain(stem) * 0.3;
+            assert!(
+                (record.stem_volumes[&stem] - expected).abs() < 1e-6,
+                "{:?}",
+                stem
+            );
+        }
+        assert!(record.stem_volumes[&Stem::Pad] > 0.0);
+        assert_eq!(record.stem_volumes[&Stem::MenuPad], 0.0);
+    }
+}
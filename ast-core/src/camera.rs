@@ -0,0 +1,244 @@
+use ast_lib::screen_size;
+use macroquad::prelude::{
+    Color, DrawTextureParams, Texture2D, Vec2, draw_circle, draw_circle_lines, draw_line,
+    draw_rectangle_lines, draw_text, draw_texture_ex, draw_triangle, get_frame_time, screen_height,
+    screen_width, vec2,
+};
+
+/// How the camera frames the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// The whole world fits in the window, nothing moves
+    Fixed,
+    /// The camera follows the spaceship, a run started in this mode has a world bigger than the window
+    Follow,
+    /// Debug view, zoomed out so the whole world fits in the window
+    Overview,
+}
+
+/// Converts world coordinates to screen coordinates and owns the screen shake.
+///
+/// Every entity draws through the camera instead of calling macroquad directly,
+/// so that following, zooming and shaking apply to everything in the world.
+pub struct Camera {
+    mode: CameraMode,
+    position: Vec2,
+    zoom: f32,
+    world_size: Vec2,
+    world_scale: f32,
+    /// World size that no longer follows the window, shared by the peers of a network game
+    locked_world: Option<Vec2>,
+    trauma: f32,
+    trauma_decay: f32,
+    max_shake: f32,
+    shake_time: f32,
+    shake_offset: Vec2,
+}
+
+#[allow(unused)]
+impl Camera {
+    pub fn new() -> Self {
+        let screen = screen_size();
+        Camera {
+            mode: CameraMode::Fixed,
+            position: screen / 2.0,
+            zoom: 1.0,
+            world_size: screen,
+            world_scale: 2.0,
+            locked_world: None,
+            trauma: 0.0,
+            trauma_decay: 1.2,
+            max_shake: 25.0,
+            shake_time: 0.0,
+            shake_offset: Vec2::ZERO,
+        }
+    }
+
+    /// Go to the next camera mode, the overview is only available in debug.
+    /// Only the view changes, the world keeps its size until the next run
+    pub fn cycle_mode(&mut self, debug: bool) {
+        self.mode = match self.mode {
+            CameraMode::Fixed => CameraMode::Follow,
+            CameraMode::Follow if debug => CameraMode::Overview,
+            _ => CameraMode::Fixed,
+        };
+    }
+
+    /// Keep the world at `size` whatever the window and the mode, `None` to follow the window again
+    pub fn lock_world_size(&mut self, size: Option<Vec2>) {
+        self.locked_world = size;
+        if let Some(size) = size {
+            self.world_size = size;
+        }
+    }
+
+    /// Size the world of a new run from the window and the current mode, kept for the whole run
+    pub fn reset_world(&mut self) {
+        let screen = screen_size();
+        self.world_size = self.locked_world.unwrap_or(match self.mode {
+            CameraMode::Fixed => screen,
+            CameraMode::Follow | CameraMode::Overview => screen * self.world_scale,
+        });
+    }
+
+    /// Add trauma, the shake intensity grows with the square of the trauma
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    /// Recompute the framing and the shake for this frame
+    pub fn update(&mut self, target: Vec2, debug: bool) {
+        let screen = vec2(screen_width(), screen_height());
+
+        if self.mode == CameraMode::Overview && !debug {
+            self.mode = CameraMode::Fixed;
+        }
+
+        match self.mode {
+            CameraMode::Fixed => {
+                self.position = self.world_size / 2.0;
+                // A world bigger than the window is fitted in it
+                self.zoom = (screen.x / self.world_size.x).min(screen.y / self.world_size.y);
+            }
+            CameraMode::Follow => {
+                // Keep the view inside the world
+                let half = (screen / 2.0).min(self.world_size / 2.0);
+                self.position = target.clamp(half, self.world_size - half);
+                self.zoom = 1.0;
+            }
+            CameraMode::Overview => {
+                self.position = self.world_size / 2.0;
+                self.zoom = (screen.x / self.world_size.x).min(screen.y / self.world_size.y) * 0.9;
+            }
+        }
+
+        // Screen shake, decays in real time so it is not stretched by slow motion
+        let frame_time = get_frame_time();
+        self.trauma = (self.trauma - self.trauma_decay * frame_time).max(0.0);
+        self.shake_time += frame_time;
+        let shake = self.trauma * self.trauma * self.max_shake;
+        self.shake_offset = vec2(
+            (self.shake_time * 47.0).sin() + (self.shake_time * 29.0).cos() * 0.5,
+            (self.shake_time * 53.0).cos() + (self.shake_time * 31.0).sin() * 0.5,
+        ) / 1.5
+            * shake;
+    }
+
+    // === Conversions ===
+
+    pub fn world_to_screen(&self, point: Vec2) -> Vec2 {
+        (point - self.position) * self.zoom
+            + vec2(screen_width(), screen_height()) / 2.0
+            + self.shake_offset
+    }
+
+    pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+        (point - self.shake_offset - vec2(screen_width(), screen_height()) / 2.0) / self.zoom
+            + self.position
+    }
+
+    /// Scale a world length to a screen length
+    pub fn scale(&self, length: f32) -> f32 {
+        length * self.zoom
+    }
+
+    // === Drawing in world coordinates ===
+
+    pub fn draw_circle(&self, center: Vec2, radius: f32, color: Color) {
+        let center = self.world_to_screen(center);
+        draw_circle(center.x, center.y, self.scale(radius), color);
+    }
+
+    pub fn draw_circle_lines(&self, center: Vec2, radius: f32, thickness: f32, color: Color) {
+        let center = self.world_to_screen(center);
+        draw_circle_lines(
+            center.x,
+            center.y,
+            self.scale(radius),
+            self.scale(thickness),
+            color,
+        );
+    }
+
+    pub fn draw_line(&self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        let start = self.world_to_screen(start);
+        let end = self.world_to_screen(end);
+        draw_line(start.x, start.y, end.x, end.y, thickness, color);
+    }
+
+    pub fn draw_triangle(&self, v1: Vec2, v2: Vec2, v3: Vec2, color: Color) {
+        draw_triangle(
+            self.world_to_screen(v1),
+            self.world_to_screen(v2),
+            self.world_to_screen(v3),
+            color,
+        );
+    }
+
+    /// Draw a texture whose top left corner is at `position` with a size of `size`
+    pub fn draw_texture(
+        &self,
+        texture: &Texture2D,
+        position: Vec2,
+        size: Vec2,
+        color: Color,
+        params: DrawTextureParams,
+    ) {
+        let position = self.world_to_screen(position);
+        draw_texture_ex(
+            texture,
+            position.x,
+            position.y,
+            color,
+            DrawTextureParams {
+                dest_size: Some(size * self.zoom),
+                ..params
+            },
+        );
+    }
+
+    /// Text is anchored in the world but keeps its size so it stays readable
+    pub fn draw_text(&self, text: &str, position: Vec2, font_size: f32, color: Color) {
+        let position = self.world_to_screen(position);
+        draw_text(text, position.x, position.y, font_size, color);
+    }
+
+    /// Outline of the world, useful when it does not match the window
+    pub fn draw_world_bounds(&self, color: Color) {
+        let corner = self.world_to_screen(Vec2::ZERO);
+        draw_rectangle_lines(
+            corner.x,
+            corner.y,
+            self.world_size.x * self.zoom,
+            self.world_size.y * self.zoom,
+            2.0,
+            color,
+        );
+    }
+
+    // === Getters ===
+
+    pub fn get_mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    pub fn get_world_size(&self) -> Vec2 {
+        self.world_size
+    }
+
+    pub fn get_zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn get_trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    pub fn get_position(&self) -> Vec2 {
+        self.position
+    }
+
+    pub fn set_world_scale(&mut self, scale: f32) {
+        self.world_scale = scale.max(1.0);
+    }
+}
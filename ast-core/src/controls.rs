@@ -0,0 +1,245 @@
+use crate::{
+    audio::SoundEffect,
+    gamestate::Gamestate,
+    i18n::Localization,
+    key_bindings::{
+        Action, KEYBINDINGS_FILE, KeyBindings, KeyCombo, KeyInput, Modifier, default_keybindings,
+    },
+    menus::button,
+};
+use macroquad::prelude::{
+    BLUE, GOLD, GREEN, RED, WHITE, YELLOW, draw_text, get_time, screen_height, screen_width,
+};
+
+/// State of the "Controls" screen of the pause menu
+pub struct ControlsEditor {
+    /// Action waiting for a new binding
+    capturing: Option<Action>,
+    capture_start: f64,
+    /// Result of the last change, shown under the list
+    status: Option<String>,
+}
+
+#[allow(unused)]
+impl ControlsEditor {
+    /// Inputs received right after clicking "+" are ignored, it's the click itself
+    const CAPTURE_DELAY: f64 = 0.2;
+    /// Combos shown per action, the others are still active
+    const MAX_SHOWN: usize = 3;
+
+    pub fn new() -> Self {
+        ControlsEditor {
+            capturing: None,
+            capture_start: 0.0,
+            status: None,
+        }
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capturing.is_some()
+    }
+
+    pub fn start_capture(&mut self, action: Action) {
+        self.capturing = Some(action);
+        self.capture_start = get_time();
+    }
+
+    pub fn cancel_capture(&mut self) {
+        self.capturing = None;
+    }
+
+    /// Bind the first input pressed to the action being captured, with the modifiers held.
+    ///
+    /// A modifier is bound alone when it is released without pressing anything else,
+    /// Escape cancels.
+    fn update_capture(&mut self, locale: &Localization, keybindings: &mut KeyBindings) -> bool {
+        let Some(action) = self.capturing else {
+            return false;
+        };
+        if get_time() - self.capture_start < Self::CAPTURE_DELAY {
+            keybindings.clear_events();
+            return false;
+        }
+
+        let held = keybindings.get_held_keys();
+        let pressed = keybindings
+            .get_just_pressed()
+            .into_iter()
+            .find(|event| Modifier::from_key(event).is_none());
+        let combo = match pressed {
+            Some(event) => KeyCombo {
+                modifiers: Modifier::ALL
+                    .into_iter()
+                    .filter(|m| held.iter().any(|key| m.matches(key)))
+                    .collect(),
+                input: KeyInput::from_event(&event),
+            },
+            None => match keybindings
+                .get_just_released()
+                .into_iter()
+                .find(|event| Modifier::from_key(event).is_some())
+            {
+                Some(modifier) => KeyCombo {
+                    input: KeyInput::Key(modifier),
+                    modifiers: Vec::new(),
+                },
+                None => return false,
+            },
+        };
+        keybindings.clear_events();
+        self.capturing = None;
+
+        if combo.input == KeyInput::Key(String::from("Escape")) && combo.modifiers.is_empty() {
+            return false;
+        }
+        if !keybindings.get_combos(action).contains(&combo) {
+            keybindings.bind(action, combo);
+        }
+        self.save(locale, keybindings);
+        true
+    }
+
+    /// Write the bindings right away so they survive a crash
+    fn save(&mut self, locale: &Localization, keybindings: &KeyBindings) {
+        self.status = Some(match keybindings.save(KEYBINDINGS_FILE) {
+            Ok(()) => locale.tr("menu.controls.saved").to_string(),
+            Err(e) => locale.format("menu.controls.save_failed", &[("error", e.to_string())]),
+        });
+    }
+}
+
+/// Draw the list of actions with their bindings and apply the changes made by the player
+pub fn draw_controls(gamestate: &mut Gamestate, keybindings: &mut KeyBindings) {
+    let screen_width = screen_width();
+    let screen_height = screen_height();
+
+    if gamestate
+        .controls
+        .update_capture(&gamestate.locale, keybindings)
+    {
+        gamestate.audio.play(SoundEffect::UiBlip);
+    }
+
+    let hint = match gamestate.controls.capturing {
+        Some(action) => gamestate.locale.format(
+            "menu.controls.capture",
+            &[("action", gamestate.locale.tr(action.key()).to_string())],
+        ),
+        None => gamestate.locale.tr("menu.controls.hint").to_string(),
+    };
+    draw_text(
+        &hint,
+        screen_width * 0.1,
+        screen_height * 0.17,
+        32.0,
+        YELLOW,
+    );
+
+    let conflicts = keybindings.get_conflicts();
+    let row_height = screen_height * 0.6 / Action::ALL.len() as f32;
+    let font_size = row_height * 0.8;
+    let combo_width = screen_width * 0.14;
+
+    for (row, action) in Action::ALL.into_iter().enumerate() {
+        let y = screen_height * 0.2 + row_height * row as f32;
+        draw_text(
+            gamestate.locale.tr(action.key()),
+            screen_width * 0.1,
+            y + row_height * 0.75,
+            font_size,
+            if gamestate.controls.capturing == Some(action) {
+                YELLOW
+            } else {
+                WHITE
+            },
+        );
+
+        // Clicking a combo removes it
+        let combos = keybindings.get_combos(action).to_vec();
+        let mut removed = None;
+        for (index, combo) in combos.iter().take(ControlsEditor::MAX_SHOWN).enumerate() {
+            let conflicting = conflicts.iter().any(|(c, _)| c == combo);
+            if button(
+                screen_width * 0.4 + index as f32 * (combo_width + 8.0),
+                y + row_height * 0.1,
+                combo_width,
+                row_height * 0.8,
+                &combo.to_string(),
+                font_size,
+                if conflicting { RED } else { BLUE },
+            ) {
+                removed = Some(index);
+            }
+        }
+        if combos.len() > ControlsEditor::MAX_SHOWN {
+            draw_text(
+                &format!("+{}", combos.len() - ControlsEditor::MAX_SHOWN),
+                screen_width * 0.4 + ControlsEditor::MAX_SHOWN as f32 * (combo_width + 8.0),
+                y + row_height * 0.75,
+                font_size,
+                WHITE,
+            );
+        }
+        if button(
+            screen_width * 0.86,
+            y + row_height * 0.1,
+            screen_width * 0.04,
+            row_height * 0.8,
+            "+",
+            font_size,
+            GREEN,
+        ) && !gamestate.controls.is_capturing()
+        {
+            gamestate.controls.start_capture(action);
+            gamestate.audio.play(SoundEffect::UiBlip);
+        }
+
+        if let Some(index) = removed {
+            if !gamestate.controls.is_capturing() {
+                keybindings.unbind(action, index);
+                gamestate.controls.save(&gamestate.locale, keybindings);
+                gamestate.audio.play(SoundEffect::UiBlip);
+            }
+        }
+    }
+
+    // Conflicts and the result of the last change
+    let mut y = screen_height * 0.83;
+    for (combo, actions) in conflicts.iter().take(2) {
+        let names = actions
+            .iter()
+            .map(|a| gamestate.locale.tr(a.key()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        draw_text(
+            &gamestate.locale.format(
+                "menu.controls.conflict",
+                &[("combo", combo.to_string()), ("actions", names)],
+            ),
+            screen_width * 0.1,
+            y,
+            28.0,
+            RED,
+        );
+        y += 28.0;
+    }
+    if let Some(status) = &gamestate.controls.status {
+        draw_text(status, screen_width * 0.1, y, 28.0, GOLD);
+    }
+
+    if button(
+        screen_width / 2.0 - 0.2 * screen_width,
+        screen_height * 0.9,
+        0.4 * screen_width,
+        0.05 * screen_height,
+        gamestate.locale.tr("menu.controls.reset"),
+        48.0,
+        RED,
+    ) && !gamestate.controls.is_capturing()
+    {
+        // Only the keys, the input settings like the scroll sensitivity are kept
+        keybindings.bindings = default_keybindings().bindings;
+        gamestate.controls.save(&gamestate.locale, keybindings);
+        gamestate.audio.play(SoundEffect::UiBlip);
+    }
+}
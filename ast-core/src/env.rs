@@ -0,0 +1,300 @@
+use crate::{
+    gamestate::Gamestate, key_bindings::TickInput, spaceship::Spaceship, spectator::Snapshot,
+};
+use ast_lib::{CosmicEntity, HEADLESS_SCREEN, seed_simulation_rng, set_headless};
+use macroquad::prelude::{Vec2, vec2};
+use serde::Serialize;
+use std::f32::consts::PI;
+
+/// What the agent sees after a step
+#[derive(Debug, Serialize)]
+pub struct Observation {
+    /// Distance to the closest asteroid along each ray, from 0 when touching the ship
+    /// to 1 when nothing is within [`Env::RAY_RANGE`]. The first ray points where the
+    /// ship faces, the next ones turn counterclockwise on screen
+    pub rays: Vec<f32>,
+    /// Every entity on the field, like the spectator stream
+    pub entities: Snapshot,
+}
+
+/// Reinforcement learning environment on the real game rules, without a window.
+///
+/// The agent drives the first ship, alone in a world of [`HEADLESS_SCREEN`]. A step runs
+/// `frame_skip` ticks with the same action, the pressed actions only on the first one.
+/// Nothing is written to disk: no statistics, achievements or high scores.
+pub struct Env {
+    gamestate: Gamestate,
+    ray_count: usize,
+    frame_skip: u32,
+}
+
+#[allow(unused)]
+impl Env {
+    /// Longest distance seen by the rays
+    pub const RAY_RANGE: f32 = 800.0;
+    pub const DEFAULT_RAYS: usize = 16;
+    /// Reward lost with each life
+    pub const LIFE_PENALTY: f32 = 100.0;
+
+    /// Switches macroquad's stand-ins on, no window may be used afterwards
+    pub fn new() -> Self {
+        set_headless(true);
+        let mut gamestate = Gamestate::new();
+        gamestate.save_progress = false;
+        gamestate.camera.lock_world_size(Some(HEADLESS_SCREEN));
+        Env {
+            gamestate,
+            ray_count: Self::DEFAULT_RAYS,
+            frame_skip: 1,
+        }
+    }
+
+    pub fn get_gamestate(&self) -> &Gamestate {
+        &self.gamestate
+    }
+
+    /// Settings like the number of waves, applied from the next reset
+    pub fn get_gamestate_mut(&mut self) -> &mut Gamestate {
+        &mut self.gamestate
+    }
+
+    pub fn get_ray_count(&self) -> usize {
+        self.ray_count
+    }
+
+    pub fn set_ray_count(&mut self, ray_count: usize) {
+        self.ray_count = ray_count.max(1);
+    }
+
+    pub fn get_frame_skip(&self) -> u32 {
+        self.frame_skip
+    }
+
+    pub fn set_frame_skip(&mut self, frame_skip: u32) {
+        self.frame_skip = frame_skip.max(1);
+    }
+
+    /// Start a new run, two runs with the same seed and actions play out the same
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        seed_simulation_rng(seed);
+        let gamestate = &mut self.gamestate;
+        gamestate.loop_number = 0;
+        gamestate.discard_cooldown = 0.0;
+        gamestate.menu.clear();
+        gamestate.reset();
+        gamestate.update_simulation_speed();
+        self.observe()
+    }
+
+    /// Play `action` and return what follows, the reward is the score made minus
+    /// [`Env::LIFE_PENALTY`] per life lost. Once done, only [`Env::reset`] goes on
+    pub fn step(&mut self, action: &TickInput) -> (Observation, f32, bool) {
+        let (score, lives) = self.get_progress();
+        for tick in 0..self.frame_skip {
+            if self.is_done() {
+                break;
+            }
+            let input = if tick == 0 {
+                action.clone()
+            } else {
+                TickInput {
+                    held: action.held.clone(),
+                    ..Default::default()
+                }
+            };
+            self.gamestate.update_simulation_speed();
+            self.gamestate.tick(&[input]);
+        }
+
+        let (new_score, new_lives) = self.get_progress();
+        let reward = new_score.saturating_sub(score) as f32
+            - lives.saturating_sub(new_lives) as f32 * Self::LIFE_PENALTY;
+        (self.observe(), reward, self.is_done())
+    }
+
+    /// The ship is destroyed or the run ended
+    pub fn is_done(&self) -> bool {
+        let gamestate = &self.gamestate;
+        !gamestate.is_any_alive() || gamestate.over || gamestate.win || !gamestate.menu.is_empty()
+    }
+
+    pub fn observe(&self) -> Observation {
+        Observation {
+            rays: self.cast_rays(),
+            entities: Snapshot::new(&self.gamestate),
+        }
+    }
+
+    fn get_progress(&self) -> (u128, u8) {
+        let player = &self.gamestate.players[0];
+        (player.score, player.lives)
+    }
+
+    fn cast_rays(&self) -> Vec<f32> {
+        let spaceship = &self.gamestate.players[0].spaceship;
+        (0..self.ray_count)
+            .map(|index| {
+                let angle =
+                    spaceship.get_rotation() + index as f32 * 2.0 * PI / self.ray_count as f32;
+                // Same convention as the ship's movement, the y axis points down
+                let direction = vec2(angle.cos(), -angle.sin());
+                self.cast_ray(spaceship, direction) / Self::RAY_RANGE
+            })
+            .collect()
+    }
+
+    /// Distance along `direction` to the edge of the closest asteroid, at most the range
+    fn cast_ray(&self, spaceship: &Spaceship, direction: Vec2) -> f32 {
+        let origin = spaceship.get_position();
+        self.gamestate
+            .asteroids
+            .iter()
+            .filter_map(|asteroid| {
+                let offset = asteroid.get_position() - origin;
+                let along = offset.dot(direction);
+                let across = offset.length_squared() - along * along;
+                let radius = asteroid.get_size();
+                if across > radius * radius {
+                    return None;
+                }
+                let half_chord = (radius * radius - across).sqrt();
+                // Inside the asteroid counts as touching, behind the ship is not seen
+                (along + half_chord >= 0.0).then_some((along - half_chord).max(0.0))
+            })
+            .fold(Self::RAY_RANGE, f32::min)
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_bindings::Action;
+
+    /// Thrust, turn and fire in a fixed pattern
+    fn action(step: usize) -> TickInput {
+        let mut held = vec![Action::Fire];
+        match step % 30 {
+            0..10 => held.push(Action::SpeedUp),
+            10..20 => held.push(Action::MoveLeft),
+            _ => held.push(Action::MoveRight),
+        }
+        let pressed = if step % 45 == 0 {
+            vec![Action::FireHoming]
+        } else {
+            Vec::new()
+        };
+        TickInput {
+            held,
+            pressed,
+            ..Default::default()
+        }
+    }
+
+    /// State hash, rays, reward and end of each step
+    fn trajectory(env: &mut Env, seed: u64, steps: usize) -> Vec<(u64, Vec<f32>, f32, bool)> {
+        let first = env.reset(seed);
+        let mut trajectory = vec![(env.get_gamestate().get_state_hash(), first.rays, 0.0, false)];
+        for step in 0..steps {
+            let (observation, reward, done) = env.step(&action(step));
+            trajectory.push((
+                env.get_gamestate().get_state_hash(),
+                observation.rays,
+                reward,
+                done,
+            ));
+            if done {
+                break;
+            }
+        }
+        trajectory
+    }
+
+    #[test]
+    fn same_seed_and_actions_same_trajectory() {
+        let mut env = Env::new();
+        let first = trajectory(&mut env, 42, 300);
+        // The same environment again, then a fresh one
+        assert_eq!(trajectory(&mut env, 42, 300), first);
+        assert_eq!(trajectory(&mut Env::new(), 42, 300), first);
+        assert_ne!(trajectory(&mut env, 43, 300), first);
+    }
+
+    #[test]
+    fn observations_fit_the_settings() {
+        let mut env = Env::new();
+        env.set_ray_count(0);
+        assert_eq!(env.get_ray_count(), 1);
+        env.set_ray_count(8);
+        let observation = env.reset(7);
+        assert_eq!(observation.rays.len(), 8);
+        assert!(observation.rays.iter().all(|ray| (0.0..=1.0).contains(ray)));
+        assert_eq!(observation.entities.ships.len(), 1);
+        assert_eq!(
+            observation.entities.asteroids.len() as u32,
+            Gamestate::WAVE_SIZE
+        );
+        assert_eq!(env.get_gamestate().camera.get_world_size(), HEADLESS_SCREEN);
+    }
+
+    #[test]
+    fn rays_see_the_closest_asteroid_edge() {
+        let mut env = Env::new();
+        env.set_ray_count(4);
+        env.reset(7);
+        let gamestate = env.get_gamestate_mut();
+        let spaceship = &gamestate.players[0].spaceship;
+        let ahead = spaceship.position_in_front_with_rotation(300.0, 0.0);
+        gamestate.asteroids = vec![crate::asteroid::Asteroid::new(
+            Some(ahead),
+            Some(0.0),
+            Some(60.0),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )];
+
+        let rays = env.observe().rays;
+        assert!(
+            (rays[0] - 240.0 / Env::RAY_RANGE).abs() < 1e-3,
+            "{:?}",
+            rays
+        );
+        assert_eq!(&rays[1..], [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn frame_skip_runs_several_ticks_per_step() {
+        let mut env = Env::new();
+        env.set_frame_skip(4);
+        env.reset(7);
+        env.step(&TickInput::default());
+        assert_eq!(env.get_gamestate().loop_number, 4);
+    }
+
+    #[test]
+    fn kills_are_rewarded_and_losing_the_ship_ends_the_episode() {
+        let mut env = Env::new();
+        env.reset(7);
+        let mut rewarded = false;
+        let mut done = false;
+        for step in 0..5000 {
+            let (_, reward, finished) = env.step(&action(step));
+            rewarded |= reward > 0.0;
+            if finished {
+                done = true;
+                break;
+            }
+        }
+        assert!(rewarded);
+        assert!(done);
+        assert!(env.is_done());
+    }
+}
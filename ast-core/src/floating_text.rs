@@ -1,6 +1,7 @@
 use mac_der::Entity;
 use ast_lib::generate_uid;
-use macroquad::prelude::{draw_text, Color, Vec2};
+use macroquad::prelude::{Color, Vec2};
+use crate::camera::Camera;
 
 #[derive(PartialEq, Clone, Entity)]
 pub struct LifetimedText {
@@ -36,11 +37,10 @@ impl LifetimedText {
         };
     }
 
-    pub fn display(&self) {
-        draw_text(
+    pub fn display(&self, camera: &Camera) {
+        camera.draw_text(
             &self.text.to_string(),
-            self.position.x,
-            self.position.y,
+            self.position,
             self.size,
             self.color,
         );
@@ -1,10 +1,10 @@
-use crate::{asteroid::Asteroid, missile::Missile, spaceship::Spaceship, floating_text::LifetimedText};
-use ast_lib::{CosmicEntity, Change, apply_changes, TEXTURE_SET};
-use std::path::PathBuf;
+use crate::{achievements::{Achievements, GameEvent, ACHIEVEMENTS_FILE}, asteroid::Asteroid, audio::{Audio, SoundEffect}, camera::{Camera, CameraMode}, controls::ControlsEditor, highscores::{HighScoreTable, NameEntry}, i18n::Localization, key_bindings::{self, InputContext, TickInput}, minimap::Minimap, music::{Intensity, MusicMixer}, missile::Missile, player::{MAX_PLAYERS, Player}, spaceship::Spaceship, stats::{LifetimeStats, RunStats, STATS_FILE}, versus::{self, Versus}, floating_text::LifetimedText};
+use ast_lib::{CosmicEntity, Change, apply_changes, generate_uid, get_sprite, simulation_rng, ASSET_PACK};
 use macroquad::prelude::{
-    draw_texture_ex, screen_height, screen_width, DrawTextureParams, Texture2D, Vec2, WHITE
+    draw_texture_ex, screen_height, screen_width, vec2, DrawTextureParams, Vec2, GOLD, GRAY, GREEN, MAGENTA, WHITE
 };
 use rand::{thread_rng, Rng};
+use std::hash::{DefaultHasher, Hash, Hasher};
 
 pub const TICKS: f64 = 1.0 / 60.0;
 
@@ -15,17 +15,45 @@ pub struct Gamestate {
     pub fps: u32,
     pub fps_cooldown: f64,
     pub debug: bool,
+    /// Network game in lockstep: the simulation only depends on what both peers share,
+    /// so the debug mode, the time controls and the local menus leave it alone
+    pub lockstep: bool,
     pub loop_number: u128,
+    /// Simulation time of the last random discard of the game over
+    pub discard_cooldown: f64,
     pub input: Vec<String>,
+    pub camera: Camera,
+    pub minimap: Minimap,
+    pub controls: ControlsEditor,
+    pub audio: Audio,
+    pub locale: Localization,
+    pub highscores: HighScoreTable,
+    /// Run waiting for the player's name before entering the high scores
+    pub name_entry: Option<NameEntry>,
+    pub stats: RunStats,
+    pub lifetime_stats: LifetimeStats,
+    pub achievements: Achievements,
+    /// Events waiting to be checked against the achievements
+    pub events: Vec<GameEvent>,
+    /// Write the statistics and achievements to disk and ask for a high score name, off for headless runs
+    pub save_progress: bool,
 
     pub asteroids: Vec<Asteroid>,
     pub asteroids_children: u8,
     pub missiles: Vec<Missile>,
-    pub spaceship: Spaceship,
+    /// Ships sharing the field, the first player also drives the camera when alone
+    pub players: Vec<Player>,
+    /// Competitive rounds instead of the waves, when set
+    pub versus: Option<Versus>,
     pub asteroid_limit: u8,
     pub number_of_asteroids: u32,
     pub score: [u128; 2],
     pub multipliers: Vec<u8>,
+    pub wave: u32,
+    /// Waves to clear before winning, a single field unless `--waves` asks for more
+    pub max_waves: u32,
+    /// Lives of each ship at the start of a run, the ship respawns until they run out
+    pub starting_lives: u8,
 
     pub asteroid_changes: Vec<Change<Asteroid>>,
     pub missile_changes: Vec<Change<Missile>>,
@@ -40,6 +68,11 @@ pub struct Gamestate {
 
 // The multipliers contains the size of the asteroid as the index-1
 impl Gamestate {
+    /// A single life like the original game, unless `--lives` asks for more
+    pub const STARTING_LIVES: u8 = 1;
+    /// Asteroids in the first wave, each wave adds two more
+    pub const WAVE_SIZE: u32 = 20;
+
     pub fn new() -> Gamestate {
         Gamestate {
             delta_time: 0.0,
@@ -48,17 +81,35 @@ impl Gamestate {
             fps: 0,
             fps_cooldown: 0.0,
             debug: false,
+            lockstep: false,
             loop_number: 0,
+            discard_cooldown: 0.0,
             input: Vec::new(),
+            camera: Camera::new(),
+            minimap: Minimap::new(),
+            controls: ControlsEditor::new(),
+            audio: Audio::null(),
+            locale: Localization::empty(),
+            highscores: HighScoreTable::new(),
+            name_entry: None,
+            stats: RunStats::new(),
+            lifetime_stats: LifetimeStats::new(),
+            achievements: Achievements::new(),
+            events: Vec::new(),
+            save_progress: true,
 
             asteroids: Vec::new(),
             asteroids_children: 2,
             missiles: Vec::new(),
-            spaceship: Spaceship::new(),
+            players: vec![Player::new(0)],
+            versus: None,
             asteroid_limit: 26,
             number_of_asteroids: 0,
             score: [0, 0],
             multipliers: vec![3, 2, 1],
+            wave: 1,
+            max_waves: 1,
+            starting_lives: Self::STARTING_LIVES,
 
             asteroid_changes: Vec::new(),
             missile_changes: Vec::new(),
@@ -72,35 +123,62 @@ impl Gamestate {
         }
     }
 
+    /// Game ready to play without a window, that never touches the player's files
+    #[cfg(test)]
+    pub(crate) fn headless() -> Gamestate {
+        ast_lib::set_headless(true);
+        let mut gamestate = Gamestate::new();
+        gamestate.save_progress = false;
+        gamestate.reset();
+        gamestate
+    }
+
     /// Reset the gamestate to a playable environment
     pub fn reset(&mut self) {
         self.win = false;
         self.over = false;
         self.asteroids.clear();
         self.missiles.clear();
-        self.spaceship = Spaceship::new();
+        self.camera.reset_world();
+        self.set_player_count(self.players.len());
+        for index in 0..self.players.len() {
+            let position = self.get_spawn_point(index);
+            self.players[index].spaceship.set_position(position);
+        }
         self.texts = Vec::new();
         self.menu.pop();
-        for _ in 1..=20 {
-            self.asteroids.push(Asteroid::new_default());
+        self.wave = 1;
+        self.score = [0, 0];
+        self.stats = RunStats::new();
+        self.achievements.start_run();
+        self.events.clear();
+        if self.versus.is_some() {
+            self.versus = Some(Versus::new());
+            versus::start_round(self);
+        } else {
+            self.spawn_wave();
+        }
+    }
+
+    /// Fill the field with the asteroids of the current wave and add them to the max score
+    pub fn spawn_wave(&mut self) {
+        let world = self.camera.get_world_size();
+        for _ in 0..Self::WAVE_SIZE + 2 * (self.wave - 1) {
+            self.asteroids.push(Asteroid::new_in_bounds(&world));
         }
         self.number_of_asteroids = self.asteroids.len() as u32;
         let mults = &self.multipliers;
-        self.score = [
-            0,
-            self.get_max_score(100, &mults, self.asteroids_children, self.debug)[3],
-        ];
+        self.score[1] += self.get_max_score(100, &mults, self.asteroids_children, self.debug)[3];
     }
 
-    /// Get a texture by PathBuf key. Falls back to "missing.png" if not found.
-    pub fn get_texture(&self, file: &PathBuf) -> &Texture2D {
-        if let Some(texture) = TEXTURE_SET.get(file) {
-            texture
-        } else {
-            eprintln!("[WARN] Texture {:?} not found, using default.", file);
-            TEXTURE_SET
-                .get(&PathBuf::from("missing.png"))
-                .expect("Default texture missing!")
+    /// Give the asteroids the textures of the current asset pack, keeping their variant when it still exists
+    pub fn refresh_textures(&mut self) {
+        let pack = ASSET_PACK.read().unwrap();
+        for asteroid in &mut self.asteroids {
+            let texture = pack
+                .find_variant("asteroid", &asteroid.get_texture().name)
+                .unwrap_or_else(|| pack.select_variant("asteroid"));
+            asteroid.set_texture(texture);
         }
     }
 
@@ -111,76 +189,386 @@ impl Gamestate {
         }
     }
 
-    pub fn update_spaceship(&mut self) {
-        self.spaceship.update(self.delta_time);
+    pub fn update_spaceships(&mut self) {
+        let world = self.camera.get_world_size();
+        for player in &mut self.players {
+            player.spaceship.update(TICKS, &world);
+            if player.is_alive() && self.menu.is_empty() {
+                self.stats
+                    .record_flight(TICKS, player.spaceship.get_speed());
+            }
+        }
+        if self.is_any_alive() && self.menu.is_empty() {
+            self.stats.record_time(TICKS);
+        }
     }
 
     pub fn update_missiles(&mut self) {
+        let world = self.camera.get_world_size();
         for missile in &mut self.missiles {
-            missile.update(&self.asteroids, self.delta_time);
+            if self.versus.is_some() {
+                // Homing missiles chase the other ships
+                let opponents: Vec<Spaceship> = self
+                    .players
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, player)| *index != missile.get_owner() && player.is_alive())
+                    .map(|(_, player)| player.spaceship)
+                    .collect();
+                missile.update(&opponents, TICKS, &world);
+            } else {
+                missile.update(&self.asteroids, TICKS, &world);
+            }
         }
     }
 
     pub fn update_asteroids(&mut self) {
+        let world = self.camera.get_world_size();
         for asteroid in &mut self.asteroids {
-            asteroid.update(self.delta_time);
+            asteroid.update(TICKS, &world);
         }
     }
 
     pub fn update_scores(&mut self) {
         // Floating texts
         for text in &mut self.texts {
-            text.update(self.delta_time);
+            text.update(TICKS);
         }
     }
 
     pub fn update_simulation_speed(&mut self) {
-        if self.menu.is_empty() {
+        // Only the end of the run pauses a network game, the pause menu is local
+        let paused = if self.lockstep {
+            self.menu.first().is_some_and(|menu| menu == "Start")
+        } else {
+            !self.menu.is_empty()
+        };
+        if !paused {
             self.simulation_speed = 1.0;
         } else if !self.debug {
             self.simulation_speed = 0.0;
         }
 
         // Pause state when there is a menu
-        if !self.menu.is_empty() && !(self.get_last_menu_item() == "Start" && self.debug) {
+        if paused && !(self.get_last_menu_item() == "Start" && self.debug) {
             self.simulation_speed = 0.0;
         }
         // Slow motion when Game over
-        if !self.spaceship.get_life() && self.simulation_speed == 1.0 && !self.debug {
+        if !self.is_any_alive() && self.simulation_speed == 1.0 && !self.debug {
             self.simulation_speed = 0.05;
         }
     }
 
     pub fn update_ending(&mut self) {
-        // Ending Conditions
-        if !self.spaceship.get_life() && self.simulation_speed > 0.0 && !self.debug {
+        if self.versus.is_some() {
+            versus::update_round(self);
+            return;
+        }
+
+        // Ending Conditions, every player is dead or the field is cleared
+        if !self.is_any_alive() && self.simulation_speed > 0.0 && !self.debug {
             self.simulation_speed = 0.1;
             if self.number_of_asteroids <= 0 {
                 self.over = true;
                 if self.menu.is_empty() {
                     self.menu.push(String::from("Start"));
+                    self.finish_run();
                 }
             }
         }
 
-        if self.spaceship.get_life() && self.simulation_speed > 0.0 && self.number_of_asteroids <= 0
-        {
+        if self.is_any_alive() && self.simulation_speed > 0.0 && self.number_of_asteroids <= 0 {
+            // Next wave
+            if self.wave < self.max_waves && !self.debug {
+                self.events.push(GameEvent::WaveCleared);
+                self.wave += 1;
+                self.spawn_wave();
+                return;
+            }
             self.win = true;
             if self.menu.is_empty() && !self.debug {
+                self.events.push(GameEvent::WaveCleared);
                 self.menu.push(String::from("Start"));
+                self.finish_run();
+            }
+        }
+    }
+
+    /// Save the statistics of the run, check the achievements and ask for the player's name when it enters the high scores
+    pub fn finish_run(&mut self) {
+        self.lifetime_stats.add_run(&self.stats);
+        if self.save_progress
+            && let Err(e) = self.lifetime_stats.save(STATS_FILE)
+        {
+            eprintln!("[ERROR] Couldn't save the statistics: {}", e);
+        }
+        self.events.push(GameEvent::RunEnded { won: self.win });
+        self.process_events();
+        if self.save_progress && self.highscores.qualifies(self.score[0]) {
+            self.name_entry = Some(NameEntry::new(self.score[0], self.score[1], self.wave));
+        }
+    }
+
+    /// Check the pending events against the achievements and announce the unlocks
+    pub fn process_events(&mut self) {
+        let mut unlocked = Vec::new();
+        for event in self.events.drain(..) {
+            unlocked.extend(self.achievements.handle(
+                event,
+                &self.stats,
+                &self.lifetime_stats,
+                self.score,
+            ));
+        }
+        if unlocked.is_empty() {
+            return;
+        }
+
+        for (index, achievement) in unlocked.iter().enumerate() {
+            let text = self.locale.format(
+                "achievements.toast",
+                &[("name", self.locale.tr(&achievement.name_key()).to_string())],
+            );
+            self.text_changes.push(Change::Add(LifetimedText::new(
+                3.0,
+                self.get_focus() - vec2(0.0, 80.0 + 40.0 * index as f32),
+                0.0,
+                text,
+                36.0,
+                GOLD,
+                -20.0,
+            )));
+        }
+        self.audio.play(SoundEffect::UiBlip);
+        if self.save_progress
+            && let Err(e) = self.achievements.save(ACHIEVEMENTS_FILE)
+        {
+            eprintln!("[ERROR] Couldn't save the achievements: {}", e);
+        }
+    }
+
+    /// How intense the game is, for the music
+    pub fn get_music_intensity(&self) -> Intensity {
+        let alive: Vec<&Player> = self.players.iter().filter(|p| p.is_alive()).collect();
+        let nearby_asteroids = self
+            .asteroids
+            .iter()
+            .filter(|a| {
+                alive.iter().any(|p| {
+                    a.get_position().distance(p.spaceship.get_position()) - a.get_size()
+                        < MusicMixer::NEARBY_RADIUS
+                })
+            })
+            .count() as u32;
+
+        Intensity {
+            nearby_asteroids,
+            // The most damaged ship sets the tension
+            shield: alive
+                .iter()
+                .map(|p| p.spaceship.get_shield())
+                .fold(None, |lowest: Option<f32>, shield| {
+                    Some(lowest.map_or(shield, |l| l.min(shield)))
+                })
+                .unwrap_or(0.0),
+            // Same condition as the slow motion of `update_simulation_speed`
+            dying: !self.is_any_alive() && !self.debug,
+            in_menu: !self.menu.is_empty(),
+        }
+    }
+
+    pub fn update_music(&mut self) {
+        let intensity = self.get_music_intensity();
+        self.audio
+            .update_music(&intensity, macroquad::prelude::get_frame_time());
+    }
+
+    pub fn update_camera(&mut self) {
+        let focus = self.get_focus();
+        self.camera.update(focus, self.debug);
+    }
+
+    /// Runs one fixed tick of `TICKS` seconds with the input of each player, in player order
+    pub fn tick(&mut self, tick_inputs: &[TickInput]) {
+        // The frame may have changed the speed since the last tick, the peer's did not
+        if self.lockstep {
+            self.update_simulation_speed();
+        }
+        self.loop_number += 1;
+
+        for (index, tick_input) in tick_inputs.iter().enumerate() {
+            key_bindings::apply_tick_input(self, index, tick_input);
+        }
+
+        let world = self.camera.get_world_size();
+        self.discard_out_of_bounds_missiles(&world);
+
+        apply_changes(&mut self.missiles, &mut self.missile_changes);
+
+        self.resolve_collisions();
+
+        if self.versus.is_some() {
+            versus::resolve_missile_hits(self);
+        }
+        self.discard_texts();
+
+        // End Game gimmick
+        let time = self.get_simulation_time();
+        let mut cooldown = self.discard_cooldown;
+        self.discard_asteroids_random(time, &mut cooldown, 50);
+        self.discard_cooldown = cooldown;
+
+        self.step();
+    }
+
+    /// Collisions of the asteroids with the ships and the missiles, resolved in order
+    pub fn resolve_collisions(&mut self) {
+        let mut rng = thread_rng();
+        for asteroid in &mut self.asteroids {
+            if asteroid.get_size() == 0.0 {
+                self
+                    .asteroid_changes
+                    .push(Change::Remove(asteroid.get_id()));
+            }
+            // Check the collision between the SPACESHIPS and ASTEROIDS
+            for player in &mut self.players {
+                let spaceship = &mut player.spaceship;
+                if !spaceship.get_life()
+                    || spaceship.get_invulnerability() > 0.0
+                    || !asteroid.collides_with(spaceship)
+                {
+                    continue;
+                }
+
+                // Asteroids are obstacles in versus, they stay in one piece
+                if self.versus.is_none() {
+                    self
+                        .asteroid_changes
+                        .push(Change::Remove(asteroid.get_id()));
+                    asteroid.split(
+                        (self.number_of_asteroids + self.asteroids_children as u32)
+                            < self.asteroid_limit.into(),
+                        self.asteroids_children,
+                        &mut self.asteroid_changes,
+                    );
+                }
+
+                let damage =
+                    5.0 / 3.0 * (asteroid.get_size() / Asteroid::SCALE + 1.0).powf(2.0);
+                spaceship.modify_shield(-damage);
+                self.stats.record_damage(damage);
+
+                spaceship.set_invulnerability(0.4);
+                self.camera.add_trauma(0.5);
+                self.audio.play(SoundEffect::ShieldHit);
+                spaceship.set_speed(spaceship.get_speed() * 0.25);
+                spaceship.add_rotation(simulation_rng().gen_range(1.0..std::f32::consts::PI));
+
+                if spaceship.get_shield() <= 0.0 {
+                    player.lives = player.lives.saturating_sub(1);
+                    self.stats.record_life_lost();
+                    self.audio.play(SoundEffect::ShipDeath);
+                    if player.lives > 0 {
+                        // Back to the center of the world with a fresh shield
+                        player
+                            .spaceship
+                            .respawn(self.camera.get_world_size() / 2.0);
+                        self.camera.add_trauma(0.8);
+                    } else {
+                        player.spaceship.set_life(false);
+                        self.camera.add_trauma(1.0);
+                    }
+                }
+                // A single ship is hit per asteroid and tick
+                break;
+            }
+
+            // Missile collisions
+            for missile in &self.missiles {
+                let collision = asteroid.collides_with(missile);
+                if collision {
+                    self
+                        .missile_changes
+                        .push(Change::Remove(missile.get_id()));
+                    let already_removed = self
+                        .asteroid_changes
+                        .iter()
+                        .any(|c| matches!(c, Change::Remove(a) if *a == asteroid.get_id()));
+
+                    if !already_removed && self.versus.is_none() {
+                        self.audio.play(SoundEffect::AsteroidSplit(
+                            (asteroid.get_size() / Asteroid::SCALE).round() as u8,
+                        ));
+                        asteroid.split(
+                            (self.number_of_asteroids
+                                + self.asteroids_children as u32)
+                                < self.asteroid_limit.into(),
+                            self.asteroids_children,
+                            &mut self.asteroid_changes,
+                        );
+
+                        let score = asteroid
+                            .grant_score(&mut self.score[0], &self.multipliers);
+                        if let Some(owner) = self.players.get_mut(missile.get_owner()) {
+                            owner.score += score;
+                        }
+                        self
+                            .stats
+                            .record_kill(asteroid.get_size(), missile.is_homing());
+                        self.events.push(GameEvent::Kill {
+                            salvo: missile.get_salvo(),
+                        });
+
+                        self.text_changes.push(Change::Add(LifetimedText::new(
+                            match score {
+                                100 => 1.0,
+                                200 => 2.0,
+                                300 => 2.5,
+                                _ => 1.0,
+                            },
+                            missile.get_position()
+                                + vec2(
+                                    rng.gen_range(-50.0..=50.0), // Random X offset
+                                    rng.gen_range(-100.0..=100.0),
+                                ), // Random Y offset,
+                            0.0,
+                            score.to_string(),
+                            match score {
+                                100 => 30.0,
+                                200 => 35.0,
+                                300 => 45.0,
+                                _ => 30.0,
+                            },
+                            match score {
+                                100 => GREEN,
+                                200 => GOLD,
+                                300 => MAGENTA,
+                                _ => WHITE,
+                            },
+                            -30.0,
+                        )));
+                    }
+                }
             }
         }
     }
 
-    /// Makes the updates of the simulation so things moves and interact
+    /// Makes the updates of every frame, the simulation itself moves in [`Gamestate::step`]
     pub fn update_all(&mut self) {
-        // Update every element
         self.update_fps();
-        self.update_spaceship();
+        self.update_camera();
+        self.update_simulation_speed();
+        self.update_music();
+    }
+
+    /// Moves the simulation by one tick of `TICKS` seconds, so two games fed the same
+    /// inputs from the same seed stay identical
+    pub fn step(&mut self) {
+        // Update every element
+        self.update_spaceships();
         self.update_missiles();
         self.update_asteroids();
         self.update_scores();
-        self.update_simulation_speed();
 
         // Remove destroyed objects
         apply_changes(&mut self.asteroids, &mut self.asteroid_changes);
@@ -190,6 +578,7 @@ impl Gamestate {
         self.number_of_asteroids = self.asteroids.len() as u32;
 
         self.update_ending();
+        self.process_events();
     }
 
     // Main functions
@@ -206,14 +595,62 @@ impl Gamestate {
             }
     }
 
-    /// Time should be the current time, cooldown is the stored variable
+    /// Hash of everything the simulation depends on, peers in lockstep compare it to detect
+    /// a desync. The ids and textures are left out, they are local to each game
+    pub fn get_state_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.loop_number.hash(&mut hasher);
+        self.wave.hash(&mut hasher);
+        self.score.hash(&mut hasher);
+
+        let mut floats: Vec<f32> = Vec::new();
+        for asteroid in &self.asteroids {
+            let position = asteroid.get_position();
+            floats.extend([
+                position.x,
+                position.y,
+                asteroid.get_rotation(),
+                asteroid.get_speed(),
+                asteroid.get_size(),
+                asteroid.get_direction(),
+            ]);
+        }
+        for missile in &self.missiles {
+            let position = missile.get_position();
+            floats.extend([position.x, position.y, missile.get_rotation(), missile.get_speed()]);
+            missile.get_owner().hash(&mut hasher);
+        }
+        for player in &self.players {
+            let spaceship = &player.spaceship;
+            let position = spaceship.get_position();
+            floats.extend([
+                position.x,
+                position.y,
+                spaceship.get_rotation(),
+                spaceship.get_speed(),
+                spaceship.get_shield(),
+            ]);
+            (spaceship.get_life(), player.score, player.lives).hash(&mut hasher);
+        }
+        for value in floats {
+            value.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Seconds simulated since the start, counted in ticks
+    pub fn get_simulation_time(&self) -> f64 {
+        self.loop_number as f64 * TICKS
+    }
+
+    /// Time should be the simulation time, cooldown is the stored variable
     pub fn discard_asteroids_random(&mut self, time: f64, cooldown: &mut f64, chance: u16) {
         // Remove asteroids when the ship is destroyed
-            if !self.spaceship.get_life()
+            if !self.is_any_alive()
                 && time - *cooldown >= 0.5
                 && self.simulation_speed > 0.0
             {
-                let mut rng = thread_rng();
+                let mut rng = simulation_rng();
                 for asteroid in &mut self.asteroids {
                     if rng.gen_range(0..=100) <= chance {
                         asteroid.split(
@@ -241,36 +678,56 @@ impl Gamestate {
     pub fn draw_all(&mut self) {
         // Background
         if !self.debug {
-            draw_texture_ex(
-                self.get_texture(&PathBuf::from("background2.png")),
-                0.0,
-                0.0,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(Vec2::new(screen_width(), screen_height())),
-                    ..Default::default()
-                },
-            );
+            let background = &get_sprite("background").texture;
+            if self.camera.get_mode() == CameraMode::Fixed {
+                draw_texture_ex(
+                    background,
+                    0.0,
+                    0.0,
+                    WHITE,
+                    DrawTextureParams {
+                        dest_size: Some(Vec2::new(screen_width(), screen_height())),
+                        ..Default::default()
+                    },
+                );
+            } else {
+                // Stretched over the whole world so it scrolls with the camera
+                self.camera.draw_texture(
+                    background,
+                    Vec2::ZERO,
+                    self.camera.get_world_size(),
+                    WHITE,
+                    DrawTextureParams::default(),
+                );
+            }
+        }
+
+        if self.camera.get_mode() != CameraMode::Fixed {
+            self.camera.draw_world_bounds(GRAY);
         }
 
         // Draw asteroids
         for asteroid in &self.asteroids {
-            asteroid.draw_self(self.debug);
+            asteroid.draw_self(&self.camera, self.debug);
         }
 
-        // Draw spaceship
-        if self.spaceship.get_life() {
-            self.spaceship.draw(25.0, self.delta_time, self.debug);
+        // Draw spaceships
+        for player in &mut self.players {
+            if player.is_alive() {
+                player
+                    .spaceship
+                    .draw(&self.camera, 25.0, self.delta_time, self.debug);
+            }
         }
 
         // Draw missiles
         for missile in &self.missiles {
-            missile.draw(self.debug);
+            missile.draw(&self.camera, self.debug);
         }
 
         // Draw the score obtained
         for text_bubble in &self.texts {
-            text_bubble.display();
+            text_bubble.display(&self.camera);
         }
     }
 
@@ -313,9 +770,12 @@ impl Gamestate {
         for (index, &multiplier) in multipliers.iter().enumerate().take(3) {
             let computed_score = result[index] * multiplier as u128 * base_score;
             if print {
-                println!(
+                ast_lib::log!(
                     "{}x{}x{}={}",
-                    base_score, multiplier, result[index], computed_score
+                    base_score,
+                    multiplier,
+                    result[index],
+                    computed_score
                 );
             }
             total_score += computed_score;
@@ -327,39 +787,109 @@ impl Gamestate {
 
     // === Helper Functions ===
 
+    /// Binding layers of the current screen, from the bottom one to the top one
+    pub fn get_input_contexts(&self) -> Vec<InputContext> {
+        let mut contexts = vec![InputContext::Gameplay];
+        // The start screen is hidden in debug mode, the simulation runs behind it
+        if !self.menu.is_empty() && !(self.get_last_menu_item() == "Start" && self.debug) {
+            contexts.push(InputContext::Menu);
+        }
+        if self.debug {
+            contexts.push(InputContext::Debug);
+        }
+        if self.controls.is_capturing() || self.name_entry.is_some() {
+            contexts.push(InputContext::TextEntry);
+        }
+        contexts
+    }
+
+    /// Replace the players by `count` new ones, from 1 to `MAX_PLAYERS`
+    pub fn set_player_count(&mut self, count: usize) {
+        self.players = (0..count.clamp(1, MAX_PLAYERS))
+            .map(|index| {
+                let mut player = Player::new(index);
+                player.lives = self.starting_lives.max(1);
+                player
+            })
+            .collect();
+    }
+
+    pub fn is_any_alive(&self) -> bool {
+        self.players.iter().any(|p| p.is_alive())
+    }
+
+    /// Where a player appears, side by side around the center of the world,
+    /// spread across it in versus
+    pub fn get_spawn_point(&self, index: usize) -> Vec2 {
+        let world = self.camera.get_world_size();
+        if self.versus.is_some() {
+            let fraction = (index + 1) as f32 / (self.players.len() + 1) as f32;
+            return vec2(world.x * fraction, world.y / 2.0);
+        }
+        let offset = index as f32 - (self.players.len() - 1) as f32 / 2.0;
+        world / 2.0 + vec2(offset * 80.0, 0.0)
+    }
+
+    /// Middle of the living ships, the camera follows it
+    pub fn get_focus(&self) -> Vec2 {
+        let alive: Vec<Vec2> = self
+            .players
+            .iter()
+            .filter(|p| p.is_alive())
+            .map(|p| p.spaceship.get_position())
+            .collect();
+        if alive.is_empty() {
+            return self.players[0].spaceship.get_position();
+        }
+        alive.iter().copied().sum::<Vec2>() / alive.len() as f32
+    }
+
     /// Will return the last current menu
     pub fn get_last_menu_item(&self) -> &str {
         self.menu.last().map(|s| s.as_str()).unwrap_or("")
     }
 
-    /// Will summon a missile from the spaceship
-    pub fn summon_missile(&mut self, is_homing: bool) {
+    /// Will summon a missile from the spaceship of a player
+    pub fn summon_missile(&mut self, player: usize, is_homing: bool) {
+        let spaceship = &self.players[player].spaceship;
         if is_homing {
-            let capacity = self.spaceship.get_missile_capacity() as usize;
-            let positions = self.spaceship.generate_positions_angles(
+            let capacity = spaceship.get_missile_capacity() as usize;
+            let positions = spaceship.generate_positions_angles(
                 std::f32::consts::PI / 2.0 + 0.2,
                 3.0 * std::f32::consts::PI / 2.0,
                 std::f32::consts::PI / 2.0,
                 3.0 * std::f32::consts::PI / 2.0 - 0.2,
             );
 
+            let salvo = generate_uid();
             for idx in 0..capacity {
-                self.missiles.push(Missile::new(
+                let mut missile = Missile::new(
                     positions[idx].0,
                     200.0,
                     positions[idx].1,
                     is_homing,
                     Vec2::from_array([-100.0; 2]),
-                ));
+                );
+                missile.set_salvo(salvo);
+                missile.set_owner(player);
+                self.missiles.push(missile);
+            }
+            if capacity > 0 {
+                self.audio.play(SoundEffect::HomingLaunch);
             }
+            self.stats.record_fire(capacity as u32, true);
         } else {
-            self.missiles.push(Missile::new(
-                self.spaceship.get_position(),
-                self.spaceship.get_max_speed(),
-                self.spaceship.get_rotation(),
+            let mut missile = Missile::new(
+                spaceship.get_position(),
+                spaceship.get_max_speed(),
+                spaceship.get_rotation(),
                 is_homing,
                 Vec2::from_array([-100.0; 2]),
-            ));
+            );
+            missile.set_owner(player);
+            self.missiles.push(missile);
+            self.audio.play(SoundEffect::Fire);
+            self.stats.record_fire(1, false);
         }
     }
 
@@ -371,7 +901,9 @@ impl Gamestate {
     }
 
     pub fn create_debug_asteroid(&mut self) {
-        let asteroid_position = self.spaceship.position_in_front_with_rotation(500.0, 0.0);
+        let asteroid_position = self.players[0]
+            .spaceship
+            .position_in_front_with_rotation(500.0, 0.0);
 
         let asteroid = Asteroid::new(
             Some(asteroid_position),
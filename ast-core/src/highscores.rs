@@ -0,0 +1,357 @@
+use crate::{audio::SoundEffect, gamestate::Gamestate, key_bindings::KeyBindings, menus::button};
+use chrono::Local;
+use macroquad::prelude::{
+    BLACK, GOLD, GRAY, KeyCode, RED, WHITE, YELLOW, clear_background, draw_text, get_char_pressed,
+    get_time, is_key_pressed, measure_text,
+};
+use serde::{Deserialize, Serialize};
+use std::fs::{read_to_string, write};
+
+/// Default location of the high-score table
+pub const HIGHSCORES_FILE: &str = "highscores.json";
+/// Version written in the high-score file
+pub const HIGHSCORES_VERSION: u32 = 1;
+
+/// One finished run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u128,
+    /// Best score possible for the waves played, see [`Gamestate::get_max_score`]
+    pub max_score: u128,
+    pub wave: u32,
+    /// Local date of the end of the run, "YYYY-MM-DD HH:MM"
+    pub date: String,
+}
+
+impl HighScore {
+    /// Percentage of the maximum score obtained
+    pub fn get_completion(&self) -> f64 {
+        if self.max_score == 0 {
+            return 0.0;
+        }
+        self.score as f64 / self.max_score as f64 * 100.0
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HighScoresFile {
+    version: u32,
+    entries: Vec<HighScore>,
+}
+
+/// Best runs, sorted from the highest score
+#[derive(Debug, Default)]
+pub struct HighScoreTable {
+    entries: Vec<HighScore>,
+}
+
+#[allow(unused)]
+impl HighScoreTable {
+    /// Runs kept in the table
+    pub const MAX_ENTRIES: usize = 10;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file: HighScoresFile = serde_json::from_str(&read_to_string(path)?)?;
+        if file.version != HIGHSCORES_VERSION {
+            return Err(format!("unsupported version {}", file.version).into());
+        }
+        let mut table = HighScoreTable {
+            entries: file.entries,
+        };
+        table.entries.sort_by(|a, b| b.score.cmp(&a.score));
+        table.entries.truncate(Self::MAX_ENTRIES);
+        Ok(table)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = HighScoresFile {
+            version: HIGHSCORES_VERSION,
+            entries: self.entries.clone(),
+        };
+        write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+
+    /// True when a run with this score enters the table
+    pub fn qualifies(&self, score: u128) -> bool {
+        score > 0
+            && (self.entries.len() < Self::MAX_ENTRIES
+                || self.entries.last().is_some_and(|last| score > last.score))
+    }
+
+    /// Insert a run after the ones with the same score, returns its rank from 0
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let rank = self
+            .entries
+            .iter()
+            .position(|e| e.score < entry.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(Self::MAX_ENTRIES);
+        Some(rank)
+    }
+
+    pub fn get_entries(&self) -> &Vec<HighScore> {
+        &self.entries
+    }
+}
+
+/// Name typed by the player for a run that entered the table
+pub struct NameEntry {
+    pub entry: HighScore,
+}
+
+impl NameEntry {
+    pub const MAX_LENGTH: usize = 12;
+
+    pub fn new(score: u128, max_score: u128, wave: u32) -> Self {
+        NameEntry {
+            entry: HighScore {
+                name: String::new(),
+                score,
+                max_score,
+                wave,
+                date: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            },
+        }
+    }
+}
+
+/// Read the typed characters, returns true once the name is confirmed or skipped
+fn update_name_entry(gamestate: &mut Gamestate) -> bool {
+    let Some(editing) = &mut gamestate.name_entry else {
+        return false;
+    };
+    while let Some(c) = get_char_pressed() {
+        if !c.is_control() && editing.entry.name.chars().count() < NameEntry::MAX_LENGTH {
+            editing.entry.name.push(c);
+        }
+    }
+    if is_key_pressed(KeyCode::Backspace) {
+        editing.entry.name.pop();
+    }
+
+    if is_key_pressed(KeyCode::Escape) {
+        gamestate.name_entry = None;
+        return true;
+    }
+    if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+        let mut entry = gamestate.name_entry.take().unwrap().entry;
+        entry.name = entry.name.trim().to_string();
+        if entry.name.is_empty() {
+            entry.name = gamestate.locale.tr("highscores.anonymous").to_string();
+        }
+        gamestate.highscores.insert(entry);
+        if let Err(e) = gamestate.highscores.save(HIGHSCORES_FILE) {
+            eprintln!("[ERROR] Couldn't save the high scores: {}", e);
+        }
+        return true;
+    }
+    false
+}
+
+/// Prompt shown on the start screen after a run that entered the table
+pub fn draw_name_entry(
+    gamestate: &mut Gamestate,
+    keybindings: &KeyBindings,
+    screen_width: f32,
+    screen_height: f32,
+) {
+    if gamestate.name_entry.is_none() {
+        return;
+    }
+    // The keys typed must not start a new run
+    keybindings.clear_events();
+    if update_name_entry(gamestate) {
+        gamestate.audio.play(SoundEffect::UiBlip);
+        return;
+    }
+
+    let prompt = gamestate.locale.tr("highscores.enter_name");
+    draw_text(
+        prompt,
+        (screen_width - measure_text(prompt, None, 30, 1.0).width) / 2.0,
+        screen_height / 2.0 + 120.0,
+        30.0,
+        YELLOW,
+    );
+    let editing = gamestate.name_entry.as_ref().unwrap();
+    let cursor = if get_time() % 1.0 < 0.5 { "_" } else { " " };
+    let name = format!("{}{}", editing.entry.name, cursor);
+    draw_text(
+        &name,
+        (screen_width - measure_text(&name, None, 40, 1.0).width) / 2.0,
+        screen_height / 2.0 + 170.0,
+        40.0,
+        WHITE,
+    );
+}
+
+/// The "HighScores" screen, reachable from the start screen
+pub fn draw_highscores(gamestate: &mut Gamestate, screen_width: f32, screen_height: f32) {
+    clear_background(BLACK);
+
+    let title = gamestate.locale.tr("highscores.title");
+    draw_text(
+        title,
+        (screen_width - measure_text(title, None, 80, 1.0).width) / 2.0,
+        100.0,
+        80.0,
+        WHITE,
+    );
+
+    let columns = [0.1, 0.17, 0.42, 0.6, 0.7, 0.8];
+    let headers = [
+        "#",
+        gamestate.locale.tr("highscores.name"),
+        gamestate.locale.tr("highscores.score"),
+        gamestate.locale.tr("highscores.wave"),
+        gamestate.locale.tr("highscores.completion"),
+        gamestate.locale.tr("highscores.date"),
+    ];
+    for (x, header) in columns.iter().zip(headers) {
+        draw_text(header, screen_width * x, screen_height * 0.22, 32.0, GRAY);
+    }
+
+    let entries = gamestate.highscores.get_entries();
+    if entries.is_empty() {
+        let empty = gamestate.locale.tr("highscores.empty");
+        draw_text(
+            empty,
+            (screen_width - measure_text(empty, None, 32, 1.0).width) / 2.0,
+            screen_height * 0.4,
+            32.0,
+            GRAY,
+        );
+    }
+    for (rank, entry) in entries.iter().enumerate() {
+        let y = screen_height * 0.27 + rank as f32 * screen_height * 0.055;
+        let values = [
+            (rank + 1).to_string(),
+            entry.name.clone(),
+            entry.score.to_string(),
+            entry.wave.to_string(),
+            format!("{:.0}%", entry.get_completion()),
+            entry.date.clone(),
+        ];
+        for (x, value) in columns.iter().zip(values) {
+            draw_text(
+                &value,
+                screen_width * x,
+                y,
+                32.0,
+                if rank == 0 { GOLD } else { WHITE },
+            );
+        }
+    }
+
+    if button(
+        screen_width / 2.0 - 0.2 * screen_width,
+        screen_height * 0.88,
+        0.4 * screen_width,
+        0.05 * screen_height,
+        gamestate.locale.tr("highscores.back"),
+        48.0,
+        RED,
+    ) {
+        gamestate.menu.pop();
+        gamestate.audio.play(SoundEffect::UiBlip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, score: u128) -> HighScore {
+        HighScore {
+            name: name.to_string(),
+            score,
+            max_score: 1000,
+            wave: 3,
+            date: "2024-01-01 12:00".to_string(),
+        }
+    }
+
+    fn names(table: &HighScoreTable) -> Vec<&str> {
+        table
+            .get_entries()
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn runs_are_ranked_by_score() {
+        let mut table = HighScoreTable::new();
+        assert_eq!(table.insert(run("b", 200)), Some(0));
+        assert_eq!(table.insert(run("c", 100)), Some(1));
+        assert_eq!(table.insert(run("a", 300)), Some(0));
+        assert_eq!(table.insert(run("b2", 200)), Some(2));
+        // Ties go after the runs already in the table
+        assert_eq!(names(&table), vec!["a", "b", "b2", "c"]);
+    }
+
+    #[test]
+    fn zero_scores_never_qualify() {
+        let mut table = HighScoreTable::new();
+        assert!(!table.qualifies(0));
+        assert_eq!(table.insert(run("nobody", 0)), None);
+        assert!(table.get_entries().is_empty());
+    }
+
+    #[test]
+    fn full_tables_keep_the_best_runs() {
+        let mut table = HighScoreTable::new();
+        for score in 1..=HighScoreTable::MAX_ENTRIES as u128 {
+            table.insert(run(&score.to_string(), score * 10));
+        }
+        assert_eq!(table.get_entries().len(), HighScoreTable::MAX_ENTRIES);
+
+        // Equal to the last one is not enough
+        assert!(!table.qualifies(10));
+        assert_eq!(table.insert(run("tie", 10)), None);
+
+        assert_eq!(table.insert(run("new", 55)), Some(5));
+        assert_eq!(table.get_entries().len(), HighScoreTable::MAX_ENTRIES);
+        assert_eq!(table.get_entries().last().unwrap().score, 20);
+        assert!(names(&table).contains(&"new"));
+    }
+
+    #[test]
+    fn tables_survive_a_save() {
+        let mut table = HighScoreTable::new();
+        table.insert(run("a", 300));
+        table.insert(run("b", 100));
+        let path = std::env::temp_dir().join(format!("highscores-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        table.save(path).unwrap();
+        let loaded = HighScoreTable::load(path).unwrap();
+        assert_eq!(loaded.get_entries(), table.get_entries());
+
+        // Edited files are sorted and truncated again
+        let entries: Vec<HighScore> = (1..=15).map(|score| run("x", score)).collect();
+        let file = HighScoresFile {
+            version: HIGHSCORES_VERSION,
+            entries,
+        };
+        write(path, serde_json::to_string(&file).unwrap()).unwrap();
+        let loaded = HighScoreTable::load(path).unwrap();
+        assert_eq!(loaded.get_entries().len(), HighScoreTable::MAX_ENTRIES);
+        assert_eq!(loaded.get_entries()[0].score, 15);
+        assert_eq!(loaded.get_entries().last().unwrap().score, 6);
+
+        write(path, r#"{"version": 99, "entries": []}"#).unwrap();
+        assert!(HighScoreTable::load(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
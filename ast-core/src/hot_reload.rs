@@ -0,0 +1,300 @@
+use crate::{
+    gamestate::Gamestate,
+    i18n::Localization,
+    key_bindings::{KeyBindings, LoadReport},
+};
+use ast_lib::{AssetPack, set_asset_pack};
+use macroquad::prelude::{
+    Color, RED, WHITE, draw_rectangle, draw_text, get_time, measure_text, screen_height,
+    screen_width,
+};
+use std::{
+    fs::{metadata, read_dir},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Files the game reloads while running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watched {
+    KeyBindings,
+    Textures,
+    Catalogs,
+}
+
+impl Watched {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Watched::KeyBindings => "Keybindings",
+            Watched::Textures => "Textures",
+            Watched::Catalogs => "Catalogs",
+        }
+    }
+}
+
+/// Last modification time of a file, or of the most recently modified file inside a directory
+fn last_modified(path: &Path) -> Option<SystemTime> {
+    let meta = metadata(path).ok()?;
+    let mut latest = meta.modified().ok();
+    if meta.is_dir() {
+        for entry in read_dir(path).ok()?.filter_map(Result::ok) {
+            latest = latest.max(last_modified(&entry.path()));
+        }
+    }
+    latest
+}
+
+/// Polls the watched files between frames and reloads the ones that changed.
+///
+/// Errors stay on screen until the file is fixed, the previous content keeps being used meanwhile.
+pub struct HotReload {
+    watched: Vec<(Watched, PathBuf, Option<SystemTime>)>,
+    errors: Vec<(Watched, String)>,
+    last_check: f64,
+}
+
+#[allow(unused)]
+impl HotReload {
+    /// Seconds between two checks of the files
+    pub const INTERVAL: f64 = 0.5;
+
+    pub fn new(keybindings: &str, textures: &str, catalogs: &str) -> Self {
+        let watched = [
+            (Watched::KeyBindings, keybindings),
+            (Watched::Textures, textures),
+            (Watched::Catalogs, catalogs),
+        ]
+        .into_iter()
+        .map(|(kind, path)| {
+            let path = PathBuf::from(path);
+            let modified = last_modified(&path);
+            (kind, path, modified)
+        })
+        .collect();
+
+        HotReload {
+            watched,
+            errors: Vec::new(),
+            last_check: 0.0,
+        }
+    }
+
+    /// Show an error that happened outside of a reload, at startup for example
+    pub fn report(&mut self, kind: Watched, error: String) {
+        eprintln!("[ERROR] {}: {}", kind.name(), error);
+        self.errors.push((kind, error));
+    }
+
+    /// Show the entries of a keybindings file that were skipped
+    pub fn report_bindings(&mut self, report: &LoadReport) {
+        for invalid in &report.invalid {
+            self.report(Watched::KeyBindings, invalid.clone());
+        }
+        if !report.merged.is_empty() {
+            ast_lib::log!("[INFO] Default bindings added for {:?}", report.merged);
+        }
+    }
+
+    /// Watched files modified since the last call, their errors are dropped as they get reloaded
+    fn take_changed(&mut self) -> Vec<(Watched, PathBuf)> {
+        let mut changed = Vec::new();
+        for (kind, path, modified) in &mut self.watched {
+            let current = last_modified(path);
+            if current != *modified {
+                *modified = current;
+                changed.push((*kind, path.clone()));
+            }
+        }
+        self.errors
+            .retain(|(kind, _)| !changed.iter().any(|(k, _)| k == kind));
+        changed
+    }
+
+    /// Reload every watched file that changed since the last check
+    pub async fn poll(&mut self, gamestate: &mut Gamestate, keybindings: &mut KeyBindings) {
+        if get_time() - self.last_check < Self::INTERVAL {
+            return;
+        }
+        self.last_check = get_time();
+
+        for (kind, path) in self.take_changed() {
+            ast_lib::log!("[INFO] {:?} changed, reloading", path);
+            match kind {
+                Watched::KeyBindings => match KeyBindings::load(&path.to_string_lossy()) {
+                    Ok((bindings, report)) => {
+                        keybindings.replace_bindings(bindings);
+                        self.report_bindings(&report);
+                    }
+                    Err(e) => self.report(kind, e.to_string()),
+                },
+                Watched::Textures => match AssetPack::load(&path).await {
+                    Ok(pack) => {
+                        set_asset_pack(pack);
+                        gamestate.refresh_textures();
+                    }
+                    Err(errors) => {
+                        for error in errors {
+                            self.report(kind, error.to_string());
+                        }
+                    }
+                },
+                Watched::Catalogs => {
+                    for error in gamestate.locale.reload(&path.to_string_lossy()) {
+                        self.report(kind, error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draw the pending errors at the top of the screen
+    pub fn draw_errors(&self, locale: &Localization) {
+        if self.errors.is_empty() {
+            return;
+        }
+
+        let font_size = (screen_height() / 40.0).max(14.0);
+        let lines: Vec<String> = self
+            .errors
+            .iter()
+            .map(|(kind, error)| format!("{}: {}", kind.name(), error))
+            .collect();
+        let width = lines
+            .iter()
+            .map(|line| measure_text(line, None, font_size as u16, 1.0).width)
+            .fold(0.0, f32::max)
+            .min(screen_width() - 20.0);
+        let height = font_size * (lines.len() + 1) as f32;
+
+        draw_rectangle(
+            10.0,
+            10.0,
+            width + 20.0,
+            height + 10.0,
+            Color::from_rgba(0, 0, 0, 200),
+        );
+        draw_text(
+            locale.tr("reload.failed"),
+            20.0,
+            10.0 + font_size,
+            font_size,
+            RED,
+        );
+        for (index, line) in lines.iter().enumerate() {
+            draw_text(
+                line,
+                20.0,
+                10.0 + font_size * (index + 2) as f32,
+                font_size,
+                WHITE,
+            );
+        }
+    }
+
+    pub fn get_errors(&self) -> &Vec<(Watched, String)> {
+        &self.errors
+    }
+
+    /// Whether a watched file failed to load and still has to be fixed
+    pub fn has_errors(&self, kind: Watched) -> bool {
+        self.errors.iter().any(|(k, _)| *k == kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs::{self, File},
+        time::Duration,
+    };
+
+    /// Temporary copy of the three watched paths, catalogs being a directory
+    fn watched_dir(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("reload-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("lang")).unwrap();
+        fs::write(root.join("keybindings.json"), "{}").unwrap();
+        fs::write(root.join("lang/en.json"), "{}").unwrap();
+        root
+    }
+
+    fn hot_reload(root: &Path) -> HotReload {
+        let path = |file: &str| root.join(file).to_string_lossy().to_string();
+        HotReload::new(&path("keybindings.json"), &path("pack"), &path("lang"))
+    }
+
+    /// Set the modification time of a file in the future, two quick writes can land on the
+    /// same timestamp and a directory is at least as recent as its last created file
+    fn touch(path: &Path, seconds: u64) {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(4_000_000_000 + seconds);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn directories_report_their_latest_file() {
+        let root = watched_dir("latest");
+        touch(&root.join("lang/en.json"), 10);
+        fs::write(root.join("lang/fr.json"), "{}").unwrap();
+        touch(&root.join("lang/fr.json"), 20);
+
+        assert_eq!(
+            last_modified(&root.join("lang")),
+            last_modified(&root.join("lang/fr.json"))
+        );
+        assert_eq!(last_modified(&root.join("missing")), None);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn only_modified_files_are_reloaded() {
+        let root = watched_dir("changed");
+        let mut reload = hot_reload(&root);
+        assert_eq!(reload.take_changed(), vec![]);
+
+        touch(&root.join("keybindings.json"), 1);
+        touch(&root.join("lang/en.json"), 1);
+        let kinds: Vec<Watched> = reload.take_changed().into_iter().map(|(k, _)| k).collect();
+        assert_eq!(kinds, vec![Watched::KeyBindings, Watched::Catalogs]);
+        assert_eq!(reload.take_changed(), vec![]);
+
+        // A pack appearing counts as a change too
+        fs::create_dir(root.join("pack")).unwrap();
+        let kinds: Vec<Watched> = reload.take_changed().into_iter().map(|(k, _)| k).collect();
+        assert_eq!(kinds, vec![Watched::Textures]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn errors_stay_until_their_file_changes() {
+        let root = watched_dir("errors");
+        let mut reload = hot_reload(&root);
+        reload.report(Watched::KeyBindings, "broken".to_string());
+        reload.report_bindings(&LoadReport {
+            invalid: vec!["unknown key".to_string()],
+            ..LoadReport::default()
+        });
+        reload.report(Watched::Catalogs, "missing key".to_string());
+        assert!(reload.has_errors(Watched::KeyBindings));
+        assert!(!reload.has_errors(Watched::Textures));
+        assert_eq!(reload.get_errors().len(), 3);
+
+        // Nothing changed, the errors are still relevant
+        reload.take_changed();
+        assert_eq!(reload.get_errors().len(), 3);
+
+        touch(&root.join("keybindings.json"), 1);
+        reload.take_changed();
+        assert!(!reload.has_errors(Watched::KeyBindings));
+        assert_eq!(
+            reload.get_errors(),
+            &vec![(Watched::Catalogs, "missing key".to_string())]
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{gamestate::Gamestate, player::Player, spaceship::Spaceship, versus};
+use macroquad::prelude::{
+    Color, DARKGRAY, GOLD, GREEN, MAGENTA, RED, Vec2, WHITE, YELLOW, draw_arc, draw_circle,
+    draw_circle_lines, draw_rectangle, draw_rectangle_lines, draw_text, draw_triangle,
+    measure_text, screen_height, screen_width, vec2,
+};
+
+/// Pips drawn for the homing capacity before switching to a counter
+const MAX_PIPS: u8 = 12;
+
+/// Size unit of the HUD, everything is proportional to the screen height
+fn unit() -> f32 {
+    screen_height() / 1000.0
+}
+
+fn shield_color(shield: f32) -> Color {
+    if shield > 66.0 {
+        GREEN
+    } else if shield > 33.0 {
+        YELLOW
+    } else {
+        RED
+    }
+}
+
+/// Circle filling up as a cooldown comes back, `remaining` goes from 1.0 (just used) to 0.0 (ready)
+fn draw_cooldown(center: Vec2, radius: f32, remaining: f32, label: &str, color: Color) {
+    let remaining = remaining.clamp(0.0, 1.0);
+    draw_circle(center.x, center.y, radius, Color::from_rgba(0, 0, 0, 160));
+    draw_circle_lines(center.x, center.y, radius, 2.0, DARKGRAY);
+    if remaining <= 0.0 {
+        draw_circle_lines(center.x, center.y, radius, 3.0, color);
+    } else {
+        // Starts at the top and goes clockwise
+        draw_arc(
+            center.x,
+            center.y,
+            32,
+            radius - 3.0,
+            -90.0,
+            6.0,
+            360.0 * (1.0 - remaining),
+            color,
+        );
+    }
+
+    let font_size = radius * 0.8;
+    let width = measure_text(label, None, font_size as u16, 1.0).width;
+    draw_text(
+        label,
+        center.x - width / 2.0,
+        center.y + font_size / 3.0,
+        font_size,
+        WHITE,
+    );
+}
+
+fn draw_shield_bar(gamestate: &Gamestate, player: &Player, origin: Vec2, size: Vec2) {
+    let shield = player.spaceship.get_shield();
+    let ratio = (shield / Spaceship::MAX_SHIELD).clamp(0.0, 1.0);
+
+    draw_rectangle(
+        origin.x,
+        origin.y,
+        size.x,
+        size.y,
+        Color::from_rgba(0, 0, 0, 160),
+    );
+    draw_rectangle(
+        origin.x,
+        origin.y,
+        size.x * ratio,
+        size.y,
+        shield_color(shield),
+    );
+    draw_rectangle_lines(origin.x, origin.y, size.x, size.y, 2.0, WHITE);
+
+    let label = gamestate
+        .locale
+        .format("hud.shield", &[("value", format!("{:.0}", ratio * 100.0))]);
+    draw_text(
+        &label,
+        origin.x + 8.0 * unit(),
+        origin.y + size.y * 0.75,
+        size.y,
+        WHITE,
+    );
+}
+
+fn draw_lives(player: &Player, origin: Vec2) {
+    let size = 14.0 * unit();
+    for index in 0..player.lives {
+        let center = origin + vec2(index as f32 * size * 2.5 + size, 0.0);
+        // Small spaceships pointing up
+        draw_triangle(
+            center + vec2(0.0, -size),
+            center + vec2(-size * 0.7, size),
+            center + vec2(size * 0.7, size),
+            player.color,
+        );
+    }
+}
+
+fn draw_capacity_pips(player: &Player, origin: Vec2) {
+    let capacity = player.spaceship.get_missile_capacity();
+    let radius = 6.0 * unit();
+    for index in 0..capacity.min(MAX_PIPS) {
+        draw_circle(
+            origin.x + index as f32 * radius * 3.0 + radius,
+            origin.y,
+            radius,
+            MAGENTA,
+        );
+    }
+    if capacity > MAX_PIPS {
+        draw_text(
+            &format!("+{}", capacity - MAX_PIPS),
+            origin.x + MAX_PIPS as f32 * radius * 3.0,
+            origin.y + radius,
+            radius * 4.0,
+            MAGENTA,
+        );
+    }
+}
+
+fn draw_wave(gamestate: &Gamestate) {
+    let unit = unit();
+    // A single field has no wave to show
+    if gamestate.max_waves > 1 {
+        let font_size = 40.0 * unit;
+        let wave = gamestate.locale.format(
+            "hud.wave",
+            &[
+                ("wave", gamestate.wave.to_string()),
+                ("max", gamestate.max_waves.to_string()),
+            ],
+        );
+        let width = measure_text(&wave, None, font_size as u16, 1.0).width;
+        draw_text(
+            &wave,
+            (screen_width() - width) / 2.0,
+            60.0 * unit,
+            font_size,
+            WHITE,
+        );
+    }
+
+    // Score multiplier of each asteroid size, smallest first
+    let multipliers = gamestate
+        .multipliers
+        .iter()
+        .zip(["hud.size_small", "hud.size_medium", "hud.size_large"])
+        .map(|(multiplier, size)| {
+            gamestate.locale.format(
+                "hud.multiplier",
+                &[
+                    ("size", gamestate.locale.tr(size).to_string()),
+                    ("multiplier", multiplier.to_string()),
+                ],
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("  ");
+    let font_size = 28.0 * unit;
+    let width = measure_text(&multipliers, None, font_size as u16, 1.0).width;
+    draw_text(
+        &multipliers,
+        (screen_width() - width) / 2.0,
+        if gamestate.max_waves > 1 { 95.0 } else { 60.0 } * unit,
+        font_size,
+        GOLD,
+    );
+}
+
+/// Shield, lives, cooldowns and homing capacity of a player
+fn draw_player(
+    gamestate: &Gamestate,
+    player: &Player,
+    bar_origin: Vec2,
+    bar_size: Vec2,
+    cooldown_center: Vec2,
+    radius: f32,
+) {
+    let unit = unit();
+    draw_shield_bar(gamestate, player, bar_origin, bar_size);
+    // Without respawns the shield is the only life
+    if gamestate.starting_lives > 1 {
+        draw_lives(player, bar_origin - vec2(0.0, 30.0 * unit));
+    }
+
+    draw_cooldown(
+        cooldown_center - vec2(radius * 1.4, 0.0),
+        radius,
+        (player.spaceship.get_firing_cooldown() / Spaceship::FIRE_COOLDOWN) as f32,
+        gamestate.locale.tr("hud.fire"),
+        GREEN,
+    );
+    draw_cooldown(
+        cooldown_center + vec2(radius * 1.4, 0.0),
+        radius,
+        (player.spaceship.get_homming_cooldown() / Spaceship::HOMING_COOLDOWN) as f32,
+        gamestate.locale.tr("hud.homing"),
+        MAGENTA,
+    );
+    draw_capacity_pips(player, cooldown_center + vec2(radius * 2.8, radius * 0.5));
+}
+
+/// Gameplay overlay: shield, lives, cooldowns, homing capacity, wave and multipliers
+pub fn draw_hud(gamestate: &Gamestate) {
+    let unit = unit();
+    let margin = 20.0 * unit;
+
+    if let [player] = gamestate.players.as_slice() {
+        // Bottom left: lives above the shield bar, bottom center: cooldowns and the homing capacity
+        let bar_size = vec2(screen_width() * 0.2, 30.0 * unit);
+        let bar_origin = vec2(margin, screen_height() - margin - bar_size.y);
+        let radius = 32.0 * unit;
+        let center = vec2(screen_width() / 2.0, screen_height() - margin - radius);
+        draw_player(gamestate, player, bar_origin, bar_size, center, radius);
+    } else {
+        // One panel per player along the bottom, in the colour of the ship
+        let panel_width = (screen_width() - margin) / gamestate.players.len() as f32;
+        let radius = 22.0 * unit;
+        for (index, player) in gamestate.players.iter().enumerate() {
+            let x = margin + index as f32 * panel_width;
+            let bar_size = vec2(panel_width * 0.45, 26.0 * unit);
+            let bar_origin = vec2(x, screen_height() - margin - bar_size.y);
+            let center = vec2(
+                x + bar_size.x + radius * 2.6,
+                screen_height() - margin - radius,
+            );
+            draw_player(gamestate, player, bar_origin, bar_size, center, radius);
+
+            let label = gamestate.locale.format(
+                "hud.player",
+                &[
+                    ("player", (index + 1).to_string()),
+                    ("score", player.score.to_string()),
+                ],
+            );
+            draw_text(
+                &label,
+                x,
+                bar_origin.y - 55.0 * unit,
+                28.0 * unit,
+                player.color,
+            );
+        }
+    }
+
+    // Top center
+    match &gamestate.versus {
+        Some(versus) => versus::draw_scoreboard(gamestate, versus),
+        None => draw_wave(gamestate),
+    }
+}
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::read_to_string,
+};
+
+/// Languages the game can be displayed in, saved by their code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Language {
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "fr")]
+    French,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::French];
+
+    /// Reference language, every other catalog is checked against it
+    pub const REFERENCE: Language = Language::English;
+
+    /// Name of the catalog file, without the extension
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::French => "fr",
+        }
+    }
+
+    /// Name of the language written in that language
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::French => "Français",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Language::English => Language::French,
+            Language::French => Language::English,
+        }
+    }
+}
+
+/// String catalogs of every language and the language currently in use.
+///
+/// Texts are looked up by key, `{name}` placeholders are replaced by [`Localization::format`].
+pub struct Localization {
+    language: Language,
+    catalogs: HashMap<Language, HashMap<String, String>>,
+}
+
+#[allow(unused)]
+impl Localization {
+    /// No catalog, every lookup returns its key
+    pub fn empty() -> Self {
+        Localization {
+            language: Language::REFERENCE,
+            catalogs: HashMap::new(),
+        }
+    }
+
+    /// Load `<directory>/<code>.json` for every language and report the missing keys
+    pub fn load(directory: &str) -> Self {
+        let mut localization = Self::empty();
+        for error in localization.reload(directory) {
+            eprintln!("[WARN] {}", error);
+        }
+        for (language, key) in localization.missing_keys() {
+            eprintln!(
+                "[WARN] Catalog {:?} does not define {:?}",
+                language.code(),
+                key
+            );
+        }
+        localization
+    }
+
+    /// Read the catalogs again, a catalog that can't be read keeps its previous content.
+    ///
+    /// Returns the catalogs that couldn't be read, the current language is kept.
+    pub fn reload(&mut self, directory: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        for language in Language::ALL {
+            let path = format!("{}/{}.json", directory, language.code());
+            let catalog = read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| {
+                    serde_json::from_str::<HashMap<String, String>>(&json).map_err(|e| e.to_string())
+                });
+            match catalog {
+                Ok(catalog) => {
+                    ast_lib::log!("[INFO] Loaded catalog: {:?}", path);
+                    self.catalogs.insert(language, catalog);
+                }
+                Err(e) => errors.push(format!("Couldn't load the catalog {:?}: {}", path, e)),
+            }
+        }
+        errors
+    }
+
+    /// Every key of the reference catalog that another catalog does not define,
+    /// and every key used by another catalog that the reference does not define
+    pub fn missing_keys(&self) -> Vec<(Language, String)> {
+        let keys = |language: Language| -> BTreeSet<&String> {
+            self.catalogs
+                .get(&language)
+                .map(|catalog| catalog.keys().collect())
+                .unwrap_or_default()
+        };
+        let reference = keys(Language::REFERENCE);
+
+        let mut missing = Vec::new();
+        for language in Language::ALL {
+            if language == Language::REFERENCE {
+                continue;
+            }
+            let other = keys(language);
+            for key in reference.difference(&other) {
+                missing.push((language, key.to_string()));
+            }
+            for key in other.difference(&reference) {
+                missing.push((Language::REFERENCE, key.to_string()));
+            }
+        }
+        missing
+    }
+
+    /// Text of `key` in the current language, falls back to the reference
+    /// language and then to the key itself
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        [self.language, Language::REFERENCE]
+            .iter()
+            .find_map(|language| self.catalogs.get(language)?.get(key))
+            .map(|text| text.as_str())
+            .unwrap_or(key)
+    }
+
+    /// Text of `key` with its `{name}` placeholders replaced
+    pub fn format(&self, key: &str, args: &[(&str, String)]) -> String {
+        let mut text = self.tr(key).to_string();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+
+    pub fn get_language(&self) -> Language {
+        self.language
+    }
+
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_dir;
+
+    const CATALOGS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/lang");
+
+    fn load() -> Localization {
+        let mut localization = Localization::empty();
+        let errors = localization.reload(CATALOGS);
+        assert!(errors.is_empty(), "{:?}", errors);
+        localization
+    }
+
+    #[test]
+    fn every_catalog_file_is_a_language() {
+        for entry in read_dir(CATALOGS).unwrap() {
+            let path = entry.unwrap().path();
+            let code = path.file_stem().unwrap().to_string_lossy().to_string();
+            assert!(
+                Language::ALL.iter().any(|language| language.code() == code),
+                "{:?} has no language",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn catalogs_define_every_key() {
+        assert_eq!(load().missing_keys(), Vec::new());
+    }
+
+    #[test]
+    fn catalogs_use_the_same_placeholders() {
+        let localization = load();
+        let placeholders = |text: &str| -> BTreeSet<String> {
+            text.split('{')
+                .skip(1)
+                .filter_map(|part| part.split_once('}').map(|(name, _)| name.to_string()))
+                .collect()
+        };
+        let reference = &localization.catalogs[&Language::REFERENCE];
+        for language in Language::ALL {
+            for (key, text) in &localization.catalogs[&language] {
+                assert_eq!(
+                    placeholders(text),
+                    placeholders(&reference[key]),
+                    "{:?} in {:?}",
+                    key,
+                    language
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lookups_fall_back_to_the_reference_then_the_key() {
+        let mut localization = Localization::empty();
+        localization.catalogs.insert(
+            Language::English,
+            HashMap::from([
+                ("greeting".to_string(), "Hello {name}".to_string()),
+                ("only_english".to_string(), "English".to_string()),
+            ]),
+        );
+        localization.catalogs.insert(
+            Language::French,
+            HashMap::from([("greeting".to_string(), "Bonjour {name}".to_string())]),
+        );
+        localization.set_language(Language::French);
+
+        assert_eq!(
+            localization.format("greeting", &[("name", "Ada".to_string())]),
+            "Bonjour Ada"
+        );
+        assert_eq!(localization.tr("only_english"), "English");
+        assert_eq!(localization.tr("unknown"), "unknown");
+    }
+}
@@ -0,0 +1,444 @@
+use crate::key_bindings::ScrollState;
+use macroquad::prelude::{
+    KeyCode, MouseButton, get_keys_pressed, get_keys_released, is_mouse_button_pressed,
+    is_mouse_button_released, mouse_wheel,
+};
+use rdev::{EventType, listen};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::read_to_string,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::spawn,
+};
+
+/// Keys and buttons currently down, and those that changed since the last frame
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InputState {
+    pub(crate) pressed: Vec<String>,
+    pub(crate) just_pressed: Vec<String>,
+    pub(crate) just_released: Vec<String>,
+}
+
+/// Raw input, keys and buttons use the names of `rdev` ("KeyW", "ShiftLeft", "Left"...)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Press(String),
+    Release(String),
+    /// Vertical wheel movement, positive when scrolling up
+    Scroll(f64),
+}
+
+/// Shared input state of a [`KeyBindings`](crate::key_bindings::KeyBindings), backends push their events into it
+#[derive(Debug, Clone)]
+pub struct InputSink {
+    pub(crate) input_state: Arc<Mutex<InputState>>,
+    pub(crate) scroll_state: Arc<Mutex<ScrollState>>,
+    pub(crate) scroll_accumulator: Arc<Mutex<f64>>,
+    pub(crate) scroll_sensitivity: Arc<Mutex<f64>>,
+}
+
+impl InputSink {
+    pub fn push(&self, event: InputEvent) {
+        let mut input = self.input_state.lock().unwrap();
+
+        match event {
+            InputEvent::Press(name) => {
+                if !input.pressed.contains(&name) {
+                    input.pressed.push(name.clone());
+                    input.just_pressed.push(name);
+                }
+            }
+            InputEvent::Release(name) => {
+                input.pressed.retain(|x| x != &name);
+                input.just_released.push(name);
+            }
+            InputEvent::Scroll(delta_y) => {
+                let mut scroll_state = self.scroll_state.lock().unwrap();
+                let mut acc = self.scroll_accumulator.lock().unwrap();
+
+                // accumulate positive and negative values
+                *acc += delta_y * *self.scroll_sensitivity.lock().unwrap();
+
+                if *acc > 0.0 {
+                    *scroll_state = ScrollState::Up;
+                    let change = *acc as i32;
+                    if change != 0 {
+                        input.just_pressed.push(format!("ScrollUp:{change}"));
+                        *acc -= change as f64; // keep remainder
+                    }
+                } else if *acc < 0.0 {
+                    *scroll_state = ScrollState::Down;
+                    let change = (-*acc) as i32;
+                    if change != 0 {
+                        input.just_pressed.push(format!("ScrollDown:{change}"));
+                        *acc += change as f64; // keep remainder
+                    }
+                } else {
+                    *scroll_state = ScrollState::Idle;
+                }
+            }
+        }
+    }
+}
+
+/// Source of the keyboard and mouse events
+pub trait InputBackend {
+    /// Start delivering events to `sink`
+    fn start(&mut self, sink: InputSink);
+
+    /// Called once per frame before the input is read, event driven backends do nothing
+    fn poll(&mut self) {}
+}
+
+// === MACROQUAD ===
+
+/// Name `rdev` gives to a key, so bindings work with every backend
+fn key_name(key: KeyCode) -> String {
+    let name = match key {
+        KeyCode::Space => "Space",
+        KeyCode::Apostrophe => "Quote",
+        KeyCode::Comma => "Comma",
+        KeyCode::Minus => "Minus",
+        KeyCode::Period => "Dot",
+        KeyCode::Slash => "Slash",
+        KeyCode::Key0 => "Num0",
+        KeyCode::Key1 => "Num1",
+        KeyCode::Key2 => "Num2",
+        KeyCode::Key3 => "Num3",
+        KeyCode::Key4 => "Num4",
+        KeyCode::Key5 => "Num5",
+        KeyCode::Key6 => "Num6",
+        KeyCode::Key7 => "Num7",
+        KeyCode::Key8 => "Num8",
+        KeyCode::Key9 => "Num9",
+        KeyCode::Semicolon => "SemiColon",
+        KeyCode::Equal => "Equal",
+        KeyCode::LeftBracket => "LeftBracket",
+        KeyCode::Backslash => "BackSlash",
+        KeyCode::RightBracket => "RightBracket",
+        KeyCode::GraveAccent => "BackQuote",
+        KeyCode::World1 | KeyCode::World2 => "IntlBackslash",
+        KeyCode::Escape => "Escape",
+        KeyCode::Enter => "Return",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Insert => "Insert",
+        KeyCode::Delete => "Delete",
+        KeyCode::Right => "RightArrow",
+        KeyCode::Left => "LeftArrow",
+        KeyCode::Down => "DownArrow",
+        KeyCode::Up => "UpArrow",
+        KeyCode::PageUp => "PageUp",
+        KeyCode::PageDown => "PageDown",
+        KeyCode::Home => "Home",
+        KeyCode::End => "End",
+        KeyCode::CapsLock => "CapsLock",
+        KeyCode::ScrollLock => "ScrollLock",
+        KeyCode::NumLock => "NumLock",
+        KeyCode::PrintScreen => "PrintScreen",
+        KeyCode::Pause => "Pause",
+        KeyCode::KpDecimal => "KpDelete",
+        KeyCode::KpDivide => "KpDivide",
+        KeyCode::KpMultiply => "KpMultiply",
+        KeyCode::KpSubtract => "KpMinus",
+        KeyCode::KpAdd => "KpPlus",
+        KeyCode::KpEnter => "KpReturn",
+        KeyCode::LeftShift => "ShiftLeft",
+        KeyCode::LeftControl => "ControlLeft",
+        KeyCode::LeftAlt => "Alt",
+        KeyCode::LeftSuper => "MetaLeft",
+        KeyCode::RightShift => "ShiftRight",
+        KeyCode::RightControl => "ControlRight",
+        KeyCode::RightAlt => "AltGr",
+        KeyCode::RightSuper => "MetaRight",
+        // Letters are "KeyA".."KeyZ", F1.. and Kp0.. have the same name
+        other => {
+            let name = format!("{:?}", other);
+            return if name.len() == 1 {
+                format!("Key{}", name)
+            } else {
+                name
+            };
+        }
+    };
+    name.to_string()
+}
+
+/// Reads the input of the game window only, keys typed in other windows are ignored
+pub struct MacroquadBackend {
+    sink: Option<InputSink>,
+}
+
+impl MacroquadBackend {
+    const BUTTONS: [(MouseButton, &'static str); 3] = [
+        (MouseButton::Left, "Left"),
+        (MouseButton::Right, "Right"),
+        (MouseButton::Middle, "Middle"),
+    ];
+
+    pub fn new() -> Self {
+        MacroquadBackend { sink: None }
+    }
+}
+
+impl InputBackend for MacroquadBackend {
+    fn start(&mut self, sink: InputSink) {
+        self.sink = Some(sink);
+    }
+
+    fn poll(&mut self) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        for key in get_keys_pressed() {
+            sink.push(InputEvent::Press(key_name(key)));
+        }
+        for key in get_keys_released() {
+            sink.push(InputEvent::Release(key_name(key)));
+        }
+        for (button, name) in Self::BUTTONS {
+            if is_mouse_button_pressed(button) {
+                sink.push(InputEvent::Press(name.to_string()));
+            }
+            if is_mouse_button_released(button) {
+                sink.push(InputEvent::Release(name.to_string()));
+            }
+        }
+        // One step per notch, whatever the platform reports
+        let wheel = mouse_wheel().1;
+        if wheel != 0.0 {
+            sink.push(InputEvent::Scroll(wheel.signum() as f64));
+        }
+    }
+}
+
+// === RDEV ===
+
+/// System wide hook, works when the window is not focused.
+///
+/// Needs permissions on some systems and does not work on Wayland,
+/// the window input is used instead when the hook fails.
+pub struct RdevBackend {
+    failed: Arc<AtomicBool>,
+    fallback: MacroquadBackend,
+}
+
+impl RdevBackend {
+    pub fn new() -> Self {
+        RdevBackend {
+            failed: Arc::new(AtomicBool::new(false)),
+            fallback: MacroquadBackend::new(),
+        }
+    }
+}
+
+impl InputBackend for RdevBackend {
+    fn start(&mut self, sink: InputSink) {
+        self.fallback.start(sink.clone());
+        let failed = Arc::clone(&self.failed);
+
+        // Spawn a separate thread for the global listener
+        spawn(move || {
+            let result = listen(move |event| match event.event_type {
+                EventType::KeyPress(k) => sink.push(InputEvent::Press(format!("{:?}", k))),
+                EventType::KeyRelease(k) => sink.push(InputEvent::Release(format!("{:?}", k))),
+                EventType::ButtonPress(b) => sink.push(InputEvent::Press(format!("{:?}", b))),
+                EventType::ButtonRelease(b) => sink.push(InputEvent::Release(format!("{:?}", b))),
+                EventType::Wheel { delta_y, .. } => sink.push(InputEvent::Scroll(delta_y as f64)),
+                _ => {}
+            });
+            if let Err(e) = result {
+                eprintln!(
+                    "[ERROR] The global input hook failed ({:?}), using the window input",
+                    e
+                );
+                failed.store(true, Ordering::Relaxed);
+            }
+        });
+    }
+
+    fn poll(&mut self) {
+        if self.failed.load(Ordering::Relaxed) {
+            self.fallback.poll();
+        }
+    }
+}
+
+// === SCRIPTED ===
+
+/// Plays a list of events at given frames, for tests and replays
+#[derive(Debug, Default)]
+pub struct ScriptedBackend {
+    /// Frame at which each event is sent, sorted
+    script: Vec<(u64, InputEvent)>,
+    frame: u64,
+    sink: Option<InputSink>,
+}
+
+#[allow(unused)]
+impl ScriptedBackend {
+    pub fn new(mut script: Vec<(u64, InputEvent)>) -> Self {
+        script.sort_by_key(|(frame, _)| *frame);
+        ScriptedBackend {
+            script,
+            frame: 0,
+            sink: None,
+        }
+    }
+
+    /// Read a script from a JSON file: `[[frame, {"Press": "KeyW"}], ...]`
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let script: Vec<(u64, InputEvent)> = serde_json::from_str(&read_to_string(path)?)?;
+        Ok(Self::new(script))
+    }
+
+    /// True once every event has been sent
+    pub fn is_finished(&self) -> bool {
+        self.script.is_empty()
+    }
+
+    pub fn get_frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+impl InputBackend for ScriptedBackend {
+    fn start(&mut self, sink: InputSink) {
+        self.sink = Some(sink);
+    }
+
+    fn poll(&mut self) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        let due = self
+            .script
+            .iter()
+            .take_while(|(frame, _)| *frame <= self.frame)
+            .count();
+        for (_, event) in self.script.drain(..due) {
+            sink.push(event);
+        }
+        self.frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_bindings::{Action, KeyBindings, KeyCombo, KeyInput, Modifier};
+    use rdev::Key;
+
+    fn press(frame: u64, key: &str) -> (u64, InputEvent) {
+        (frame, InputEvent::Press(key.to_string()))
+    }
+
+    fn release(frame: u64, key: &str) -> (u64, InputEvent) {
+        (frame, InputEvent::Release(key.to_string()))
+    }
+
+    fn actions(
+        keybindings: &KeyBindings,
+        test: impl Fn(&KeyBindings, Action) -> bool,
+    ) -> Vec<Action> {
+        Action::ALL
+            .into_iter()
+            .filter(|action| test(keybindings, *action))
+            .collect()
+    }
+
+    #[test]
+    fn scripted_events_drive_the_bindings_frame_by_frame() {
+        let mut keybindings = KeyBindings::new();
+        keybindings.bind_key(Action::SpeedUp, Key::KeyW);
+        keybindings.bind_key(Action::SpeedDown, Key::KeyS);
+        keybindings.bind(
+            Action::Stop,
+            KeyCombo {
+                input: KeyInput::from_key(Key::KeyS),
+                modifiers: vec![Modifier::Control],
+            },
+        );
+        keybindings.bind_scroll(Action::IncreaseMissileCapacity, "ScrollUp");
+
+        let mut backend = ScriptedBackend::new(vec![
+            press(0, "KeyW"),
+            release(2, "KeyW"),
+            press(3, "ControlRight"),
+            press(4, "KeyS"),
+            release(5, "KeyS"),
+            release(5, "ControlRight"),
+            // Shift is not part of any combo, S alone is matched
+            press(6, "ShiftLeft"),
+            press(7, "KeyS"),
+            (8, InputEvent::Scroll(1.0)),
+        ]);
+        keybindings.start_backend(&mut backend);
+
+        use Action::*;
+        // (pressed, held, released) after each frame
+        let expected: [(&[Action], &[Action], &[Action]); 9] = [
+            (&[SpeedUp], &[SpeedUp], &[]),
+            (&[], &[SpeedUp], &[]),
+            (&[], &[], &[SpeedUp]),
+            (&[], &[], &[]),
+            // The more specific combo wins over S alone
+            (&[Stop], &[Stop], &[]),
+            (&[], &[], &[Stop]),
+            (&[], &[], &[]),
+            (&[SpeedDown], &[SpeedDown], &[]),
+            // A scroll notch is a press of its direction
+            (&[IncreaseMissileCapacity], &[SpeedDown], &[]),
+        ];
+        for (frame, (pressed, held, released)) in expected.into_iter().enumerate() {
+            assert_eq!(backend.get_frame(), frame as u64);
+            backend.poll();
+            assert_eq!(
+                actions(&keybindings, KeyBindings::is_action_pressed),
+                pressed,
+                "pressed at frame {}",
+                frame
+            );
+            assert_eq!(
+                actions(&keybindings, KeyBindings::is_action_held),
+                held,
+                "held at frame {}",
+                frame
+            );
+            assert_eq!(
+                actions(&keybindings, KeyBindings::is_action_released),
+                released,
+                "released at frame {}",
+                frame
+            );
+            let scrolled = if frame == 8 { 1 } else { 0 };
+            assert_eq!(keybindings.is_scrolled(IncreaseMissileCapacity), scrolled);
+            keybindings.clear_events();
+        }
+        assert!(backend.is_finished());
+    }
+
+    #[test]
+    fn scripts_load_from_json() {
+        let path = std::env::temp_dir().join(format!("script-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"[[3, {"Release": "KeyW"}], [1, {"Press": "KeyW"}], [5, {"Scroll": -2.0}]]"#,
+        )
+        .unwrap();
+        let backend = ScriptedBackend::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            backend.script,
+            vec![
+                (1, InputEvent::Press("KeyW".to_string())),
+                (3, InputEvent::Release("KeyW".to_string())),
+                (5, InputEvent::Scroll(-2.0)),
+            ]
+        );
+    }
+}
@@ -1,16 +1,70 @@
-use crate::gamestate::Gamestate;
+use crate::{
+    audio::SoundEffect,
+    gamestate::{Gamestate, TICKS},
+    input::{InputBackend, InputSink, InputState},
+    settings,
+    spaceship::Spaceship,
+};
 use ast_lib::CosmicEntity;
 use std::{
     collections::{BTreeMap, HashMap},
     fs::{read_to_string, write},
     sync::{Arc, Mutex},
-    thread::spawn,
 };
-use rdev::{listen, Button, Event, EventType, Key};
+use rdev::{Button, Key};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 // === DEFINITIONS ===
 
+/// File the bindings are loaded from and saved to
+pub const KEYBINDINGS_FILE: &str = "keybindings.json";
+
+/// Version of the keybindings file, files without a version are version 0
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Names `rdev` gives to the keys, the window backend uses the same ones
+pub const KEY_NAMES: [&str; 104] = [
+    "Alt", "AltGr", "Backspace", "CapsLock", "ControlLeft", "ControlRight", "Delete", "DownArrow",
+    "End", "Escape", "F1", "F10", "F11", "F12", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9",
+    "Home", "LeftArrow", "MetaLeft", "MetaRight", "PageDown", "PageUp", "Return", "RightArrow",
+    "ShiftLeft", "ShiftRight", "Space", "Tab", "UpArrow", "PrintScreen", "ScrollLock", "Pause",
+    "NumLock", "BackQuote", "Num1", "Num2", "Num3", "Num4", "Num5", "Num6", "Num7", "Num8",
+    "Num9", "Num0", "Minus", "Equal", "KeyQ", "KeyW", "KeyE", "KeyR", "KeyT", "KeyY", "KeyU",
+    "KeyI", "KeyO", "KeyP", "LeftBracket", "RightBracket", "KeyA", "KeyS", "KeyD", "KeyF", "KeyG",
+    "KeyH", "KeyJ", "KeyK", "KeyL", "SemiColon", "Quote", "BackSlash", "IntlBackslash", "KeyZ",
+    "KeyX", "KeyC", "KeyV", "KeyB", "KeyN", "KeyM", "Comma", "Dot", "Slash", "Insert", "KpReturn",
+    "KpMinus", "KpPlus", "KpMultiply", "KpDivide", "Kp0", "Kp1", "Kp2", "Kp3", "Kp4", "Kp5", "Kp6",
+    "Kp7", "Kp8", "Kp9", "KpDelete",
+];
+
+/// Content of the keybindings file, bindings are checked one by one
+#[derive(Deserialize)]
+struct KeyBindingsFile {
+    #[serde(default)]
+    settings: InputSettings,
+    #[serde(default)]
+    bindings: serde_json::Map<String, Value>,
+}
+
+#[derive(Serialize)]
+struct KeyBindingsFileRef<'a> {
+    version: u32,
+    settings: &'a InputSettings,
+    bindings: BTreeMap<&'a Action, &'a Vec<KeyCombo>>,
+}
+
+/// What had to be fixed while loading a keybindings file
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    /// Schema version of the file
+    pub version: u32,
+    /// Entries that were skipped
+    pub invalid: Vec<String>,
+    /// Actions missing from the file that got their default bindings
+    pub merged: Vec<Action>,
+}
+
 /// Scroll state used internally
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ScrollState {
@@ -42,6 +96,17 @@ impl KeyInput {
         KeyInput::Mouse(format!("{:?}", b))
     }
 
+    /// Input matching an event recorded by the listener ("KeyW", "Left", "ScrollUp:1"...)
+    pub fn from_event(event: &str) -> Self {
+        if let Some((direction, _)) = event.split_once(':') {
+            KeyInput::Scroll(direction.to_string())
+        } else if ["Left", "Right", "Middle"].contains(&event) || event.starts_with("Unknown(") {
+            KeyInput::Mouse(event.to_string())
+        } else {
+            KeyInput::Key(event.to_string())
+        }
+    }
+
     pub fn as_string(&self) -> &str {
         match self {
             KeyInput::Key(k) | KeyInput::Mouse(k) | KeyInput::Scroll(k) => k,
@@ -57,6 +122,30 @@ pub enum Modifier {
     Meta,
 }
 
+impl Modifier {
+    pub const ALL: [Modifier; 4] = [
+        Modifier::Control,
+        Modifier::Shift,
+        Modifier::Alt,
+        Modifier::Meta,
+    ];
+
+    /// True for the keys of both sides of the keyboard ("ControlLeft" and "ControlRight" are Control)
+    pub fn matches(&self, key: &str) -> bool {
+        match self {
+            Modifier::Control => key == "ControlLeft" || key == "ControlRight",
+            Modifier::Shift => key == "ShiftLeft" || key == "ShiftRight",
+            Modifier::Alt => key == "Alt" || key == "AltGr",
+            Modifier::Meta => key == "MetaLeft" || key == "MetaRight",
+        }
+    }
+
+    /// Modifier a key belongs to, if any
+    pub fn from_key(key: &str) -> Option<Modifier> {
+        Self::ALL.into_iter().find(|m| m.matches(key))
+    }
+}
+
 impl std::fmt::Display for Modifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
@@ -76,27 +165,70 @@ pub struct KeyCombo {
     pub modifiers: Vec<Modifier>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl KeyCombo {
+    /// Check the names against the ones the input backends report
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.input.as_string();
+        let unknown_code = name
+            .strip_prefix("Unknown(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .is_some_and(|code| code.parse::<u32>().is_ok());
+        let valid = match &self.input {
+            KeyInput::Key(_) => KEY_NAMES.contains(&name) || unknown_code,
+            KeyInput::Mouse(_) => ["Left", "Right", "Middle"].contains(&name) || unknown_code,
+            KeyInput::Scroll(_) => ["ScrollUp", "ScrollDown"].contains(&name),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("unknown input {:?}", self.input))
+        }
+    }
+}
+
+impl std::fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{}+", modifier)?;
+        }
+        match &self.input {
+            KeyInput::Key(k) => write!(f, "{}", k.strip_prefix("Key").unwrap_or(k)),
+            KeyInput::Mouse(b) => write!(f, "Mouse {}", b),
+            KeyInput::Scroll(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Input options saved along with the bindings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    pub scroll_sensitivity: f64,
+    /// Input backend used when none is given on the command line ("window" or "global")
+    pub backend: String,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        InputSettings {
+            scroll_sensitivity: 1.0,
+            backend: String::from("window"),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct KeyBindings {
     pub bindings: HashMap<Action, Vec<KeyCombo>>,
+    settings: InputSettings,
 
-    #[serde(skip)]
-    listener_handle: Option<std::thread::JoinHandle<()>>,
-    #[serde(skip)]
     input_state: Arc<Mutex<InputState>>,
-    #[serde(skip)]
     scroll_state: Arc<Mutex<ScrollState>>,
-    #[serde(skip)]
     scroll_accumulator: Arc<Mutex<f64>>,
-    #[serde(skip)]
-    scroll_sensitivity: f64,
-}
-
-#[derive(Debug, Clone, Default)]
-struct InputState {
-    pressed: Vec<String>,
-    just_pressed: Vec<String>,
-    just_released: Vec<String>,
+    /// Shared with the backends so a reload applies right away
+    scroll_sensitivity: Arc<Mutex<f64>>,
+    /// Active binding layers, the last one is on top
+    contexts: Vec<InputContext>,
 }
 
 #[allow(unused)]
@@ -105,11 +237,12 @@ impl KeyBindings {
     pub fn new() -> Self {
         Self {
             bindings: HashMap::new(),
-            listener_handle: None,
+            settings: InputSettings::default(),
             input_state: Arc::new(Mutex::new(InputState::default())),
             scroll_state: Arc::new(Mutex::new(ScrollState::Idle)),
             scroll_accumulator: Arc::new(Mutex::new(0.0)),
-            scroll_sensitivity: 1.0,
+            scroll_sensitivity: Arc::new(Mutex::new(1.0)),
+            contexts: Vec::new(),
         }
     }
 
@@ -151,6 +284,64 @@ impl KeyBindings {
         );
     }
 
+    /// Remove the combo at `index` from the bindings of an action
+    pub fn unbind(&mut self, action: Action, index: usize) {
+        if let Some(combos) = self.bindings.get_mut(&action) {
+            if index < combos.len() {
+                combos.remove(index);
+            }
+        }
+    }
+
+    /// Combos bound to an action, in the order they were added
+    pub fn get_combos(&self, action: Action) -> &[KeyCombo] {
+        self.bindings.get(&action).map(|c| c.as_slice()).unwrap_or(&[])
+    }
+
+    /// Every action a combo is bound to
+    pub fn get_actions(&self, combo: &KeyCombo) -> Vec<Action> {
+        let mut actions: Vec<Action> = self
+            .bindings
+            .iter()
+            .filter(|(_, combos)| combos.contains(combo))
+            .map(|(action, _)| *action)
+            .collect();
+        actions.sort();
+        actions
+    }
+
+    /// Combos bound to several actions of the same context, sorted by the first action using them
+    pub fn get_conflicts(&self) -> Vec<(KeyCombo, Vec<Action>)> {
+        let mut conflicts: Vec<(KeyCombo, Vec<Action>)> = Vec::new();
+        for action in Action::ALL {
+            for combo in self.get_combos(action) {
+                let actions: Vec<Action> = self
+                    .get_actions(combo)
+                    .into_iter()
+                    .filter(|other| {
+                        self.get_actions(combo).iter().any(|a| {
+                            a != other && a.contexts().iter().any(|c| other.contexts().contains(c))
+                        })
+                    })
+                    .collect();
+                if !actions.is_empty() && !conflicts.iter().any(|(c, _)| c == combo) {
+                    conflicts.push((combo.clone(), actions));
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Replace the binding layers, from the bottom one to the top one
+    pub fn set_contexts(&mut self, contexts: Vec<InputContext>) {
+        self.contexts = contexts;
+    }
+
+    /// Topmost binding layer
+    pub fn get_context(&self) -> Option<InputContext> {
+        self.contexts.last().copied()
+    }
+
     /// Call this at the end of your main loop to clear transient states
     pub fn clear_events(&self) {
         let mut input = self.input_state.lock().unwrap();
@@ -159,101 +350,163 @@ impl KeyBindings {
         *self.scroll_state.lock().unwrap() = ScrollState::Idle;
     }
 
-    /// Start listening to global keyboard and mouse events
-    pub fn start_listening(&self) {
-        let input_state_clone = Arc::clone(&self.input_state);
-        let scroll_state_clone = Arc::clone(&self.scroll_state);
-        let scroll_accumulator_clone = Arc::clone(&self.scroll_accumulator); // <-- clone the Arc
-        let sensitivity = self.scroll_sensitivity;
-
-        // Spawn a separate thread for the global listener
-        spawn(move || {
-            listen(move |event: Event| {
-                let mut input = input_state_clone.lock().unwrap();
-
-                match event.event_type {
-                    EventType::KeyPress(k) => {
-                        let k_str = format!("{:?}", k);
-                        if !input.pressed.contains(&k_str) {
-                            input.pressed.push(k_str.clone());
-                            input.just_pressed.push(k_str);
-                        }
-                    }
-                    EventType::KeyRelease(k) => {
-                        let k_str = format!("{:?}", k);
-                        input.pressed.retain(|x| x != &k_str);
-                        input.just_released.push(k_str);
-                    }
-                    EventType::ButtonPress(b) => {
-                        let b_str = format!("{:?}", b);
-                        if !input.pressed.contains(&b_str) {
-                            input.pressed.push(b_str.clone());
-                            input.just_pressed.push(b_str);
-                        }
-                    }
-                    EventType::ButtonRelease(b) => {
-                        let b_str = format!("{:?}", b);
-                        input.pressed.retain(|x| x != &b_str);
-                        input.just_released.push(b_str);
-                    }
-                    EventType::Wheel {
-                        delta_x: _,
-                        delta_y,
-                    } => {
-                        let mut scroll_state = scroll_state_clone.lock().unwrap();
-                        let mut acc = scroll_accumulator_clone.lock().unwrap();
-                        let delta_f = delta_y as f64 * sensitivity;
-
-                        *acc += delta_f; // accumulate positive and negative values
-
-                        if *acc > 0.0 {
-                            *scroll_state = ScrollState::Up;
-                            let change = *acc as i32;
-                            if change != 0 {
-                                input.just_pressed.push(format!("ScrollUp:{change}"));
-                                *acc -= change as f64; // keep remainder
-                            }
-                        } else if *acc < 0.0 {
-                            *scroll_state = ScrollState::Down;
-                            let change = (-*acc) as i32;
-                            if change != 0 {
-                                input.just_pressed.push(format!("ScrollDown:{change}"));
-                                *acc += change as f64; // keep remainder
-                            }
-                        } else {
-                            *scroll_state = ScrollState::Idle;
-                        }
-                    }
+    /// Start delivering the events of `backend` to these bindings
+    pub fn start_backend(&self, backend: &mut dyn InputBackend) {
+        backend.start(self.sink());
+    }
 
-                    _ => {}
-                }
-            })
-            .unwrap();
-        });
+    /// Handle to the input state, for backends
+    pub fn sink(&self) -> InputSink {
+        InputSink {
+            input_state: Arc::clone(&self.input_state),
+            scroll_state: Arc::clone(&self.scroll_state),
+            scroll_accumulator: Arc::clone(&self.scroll_accumulator),
+            scroll_sensitivity: Arc::clone(&self.scroll_sensitivity),
+        }
     }
 
-    /// Save bindings to a JSON file in a predictable (sorted) manner
+    /// Save bindings and settings to a JSON file in a predictable (sorted) manner
     pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
         // Convert into a BTreeMap for sorted keys
-        let sorted: BTreeMap<_, _> = self.bindings.iter().collect();
-        let json = serde_json::to_string_pretty(&sorted)?;
+        let file = KeyBindingsFileRef {
+            version: SCHEMA_VERSION,
+            settings: &self.settings,
+            bindings: self.bindings.iter().collect(),
+        };
+        let json = serde_json::to_string_pretty(&file)?;
         write(path, json)?;
         Ok(())
     }
 
-    /// Load bindings from a JSON file
-    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut bindings: KeyBindings = serde_json::from_str(&read_to_string(path)?)?;
-        bindings.input_state = Arc::new(Mutex::new(InputState::default()));
-        bindings.scroll_state = Arc::new(Mutex::new(ScrollState::Idle));
-        bindings.scroll_accumulator = Arc::new(Mutex::new(0.0));
-        bindings.listener_handle = None;
-        Ok(bindings)
+    /// Load bindings from a JSON file, see [`KeyBindings::from_json`]
+    pub fn load(path: &str) -> Result<(Self, LoadReport), Box<dyn std::error::Error>> {
+        Self::from_json(serde_json::from_str(&read_to_string(path)?)?)
+    }
+
+    /// Load the profile of an extra player, missing actions come from `defaults`
+    pub fn load_with_defaults(
+        path: &str,
+        defaults: &KeyBindings,
+    ) -> Result<(Self, LoadReport), Box<dyn std::error::Error>> {
+        Self::from_json_with_defaults(serde_json::from_str(&read_to_string(path)?)?, defaults)
+    }
+
+    /// Read bindings of any known schema version.
+    ///
+    /// Invalid entries are skipped and listed in the report, actions missing from the file
+    /// get their default bindings. Only an unreadable file or an unknown version is an error.
+    pub fn from_json(json: Value) -> Result<(Self, LoadReport), Box<dyn std::error::Error>> {
+        Self::from_json_with_defaults(json, &default_keybindings())
+    }
+
+    /// Like [`KeyBindings::from_json`], with the bindings merged for the missing actions
+    pub fn from_json_with_defaults(
+        json: Value,
+        defaults: &KeyBindings,
+    ) -> Result<(Self, LoadReport), Box<dyn std::error::Error>> {
+        let mut report = LoadReport::default();
+        let (settings, entries) = match json.get("version") {
+            // Version 0 was a bare map of actions
+            None => match json {
+                Value::Object(entries) => (InputSettings::default(), entries),
+                _ => return Err("The keybindings file must be a JSON object".into()),
+            },
+            Some(version) if version.as_u64() == Some(SCHEMA_VERSION as u64) => {
+                let file: KeyBindingsFile = serde_json::from_value(json)?;
+                report.version = SCHEMA_VERSION;
+                (file.settings, file.bindings)
+            }
+            Some(version) => {
+                return Err(format!(
+                    "Unsupported keybindings version {} (this game reads up to {})",
+                    version, SCHEMA_VERSION
+                )
+                .into());
+            }
+        };
+
+        let mut bindings = KeyBindings::new();
+        bindings.set_settings(settings);
+
+        let mut in_file = Vec::new();
+        for (name, combos) in entries {
+            let Ok(action) = serde_json::from_value::<Action>(Value::String(name.clone())) else {
+                report.invalid.push(format!("Unknown action {:?}", name));
+                continue;
+            };
+            let combos = match combos {
+                Value::Array(combos) => combos,
+                other => {
+                    report.invalid.push(format!("{:?}: expected a list, got {}", action, other));
+                    continue;
+                }
+            };
+
+            let mut valid = 0;
+            let total = combos.len();
+            for combo in combos {
+                match serde_json::from_value::<KeyCombo>(combo)
+                    .map_err(|e| e.to_string())
+                    .and_then(|combo| combo.validate().map(|_| combo))
+                {
+                    Ok(combo) => {
+                        bindings.bind(action, combo);
+                        valid += 1;
+                    }
+                    Err(e) => report.invalid.push(format!("{:?}: {}", action, e)),
+                }
+            }
+            // An action whose every binding is invalid gets the defaults back
+            if valid > 0 || total == 0 {
+                bindings.bindings.entry(action).or_default();
+                in_file.push(action);
+            }
+        }
+
+        for action in Action::ALL {
+            if in_file.contains(&action) {
+                continue;
+            }
+            let combos = defaults.get_combos(action);
+            if !combos.is_empty() {
+                bindings.bindings.insert(action, combos.to_vec());
+                report.merged.push(action);
+            }
+        }
+
+        Ok((bindings, report))
+    }
+
+    /// Read the input of `other`, so several profiles share a single backend
+    pub fn with_input_of(mut self, other: &KeyBindings) -> Self {
+        self.input_state = Arc::clone(&other.input_state);
+        self.scroll_state = Arc::clone(&other.scroll_state);
+        self.scroll_accumulator = Arc::clone(&other.scroll_accumulator);
+        self.scroll_sensitivity = Arc::clone(&other.scroll_sensitivity);
+        self
+    }
+
+    /// Take the bindings and settings of `other` while keeping the listener and the input state,
+    /// so a reloaded file applies without restarting the global listener
+    pub fn replace_bindings(&mut self, other: KeyBindings) {
+        self.bindings = other.bindings;
+        self.set_settings(other.settings);
     }
 
     // === Setter | Getters ===
     pub fn set_scroll_sensitivity(&mut self, sensitivity: f64) {
-        self.scroll_sensitivity = sensitivity.max(0.1);
+        self.settings.scroll_sensitivity = sensitivity.max(0.1);
+        *self.scroll_sensitivity.lock().unwrap() = self.settings.scroll_sensitivity;
+    }
+
+    pub fn set_settings(&mut self, settings: InputSettings) {
+        let sensitivity = settings.scroll_sensitivity;
+        self.settings = settings;
+        self.set_scroll_sensitivity(sensitivity);
+    }
+
+    pub fn get_settings(&self) -> &InputSettings {
+        &self.settings
     }
 
     /// Get a clone of the currently pressed keys
@@ -270,19 +523,36 @@ impl KeyBindings {
         pressed
     }
 
+    /// Inputs pressed since the last `clear_events`, in the order they happened
+    pub fn get_just_pressed(&self) -> Vec<String> {
+        self.input_state.lock().unwrap().just_pressed.clone()
+    }
+
+    /// Inputs released since the last `clear_events`
+    pub fn get_just_released(&self) -> Vec<String> {
+        self.input_state.lock().unwrap().just_released.clone()
+    }
+
     pub fn is_action_held(&self, action: Action) -> bool {
         let input = self.input_state.lock().unwrap();
-        self.is_combo_active(&input.pressed, action)
+        self.is_combo_active(&input.pressed, &input.pressed, action)
     }
 
     pub fn is_action_pressed(&self, action: Action) -> bool {
         let input = self.input_state.lock().unwrap();
-        self.is_combo_active(&input.just_pressed, action)
+        self.is_combo_active(&input.just_pressed, &input.pressed, action)
     }
 
     pub fn is_action_released(&self, action: Action) -> bool {
         let input = self.input_state.lock().unwrap();
-        self.is_combo_active(&input.just_released, action)
+        // Modifiers released on the same frame still count
+        let held: Vec<String> = input
+            .pressed
+            .iter()
+            .chain(&input.just_released)
+            .cloned()
+            .collect();
+        self.is_combo_active(&input.just_released, &held, action)
     }
 
     /// Returns the total scroll magnitude for an action in the current frame
@@ -293,6 +563,11 @@ impl KeyBindings {
         // Look up all combos bound to this action
         if let Some(combos) = self.bindings.get(&action) {
             for combo in combos {
+                if !self.is_combo_selected(combo, &input.just_pressed, &input.pressed)
+                    || !self.is_reachable(action, combo, &input.just_pressed, &input.pressed)
+                {
+                    continue;
+                }
                 if let KeyInput::Scroll(direction) = &combo.input {
                     // Sum up all scroll deltas in just_pressed
                     for event in &input.just_pressed {
@@ -311,63 +586,134 @@ impl KeyBindings {
 
     /// Helper to check if an action's key combinations are active.
     ///
+    /// `set` holds the events being tested (held, just pressed or just released keys)
+    /// and `held` the keys currently down, used for the modifiers.
+    ///
     /// A combination is considered **active** if:
-    /// - All of its modifiers are currently in the pressed set.
-    /// - Its main key / mouse button / scroll event is also in the pressed set.
+    /// - Its main key / mouse button / scroll event is in `set`.
+    /// - All of its modifiers are held, on either side of the keyboard.
+    /// - No other combination on the same main input with more modifiers is active:
+    ///   the most specific combination wins, so `Ctrl + S` does not also fire `S`.
     ///
     /// # Example
-    /// Binding `Ctrl + S` to a `SaveAction`:
-    ///
-    /// ```
-    /// use std::collections::HashMap;
-    /// use crate::key_bindings::{KeyBindings, KeyCombo, KeyInput};
-    ///
-    /// // Create a keybinding instance
-    /// let mut kb = KeyBindings::new();
-    ///
-    /// // Bind Ctrl+S to "SaveAction"
-    /// kb.bind(
-    ///     "SaveAction",
-    ///     KeyCombo {
-    ///         input: KeyInput::Key("KeyS".into()),
-    ///         modifiers: vec!["ControlLeft".into()],
-    ///     },
-    /// );
-    ///
-    /// // Simulate currently pressed keys
-    /// let pressed = vec!["ControlLeft".to_string(), "KeyS".to_string()];
-    ///
-    /// // Call the helper directly
-    /// let active = kb.is_combo_active(&pressed, "SaveAction");
-    /// assert!(active); // true, because Ctrl+S is pressed
-    /// ```
+    /// With `S` bound to `SpeedDown` and `Ctrl + S` bound to another action,
+    /// holding `ControlRight` and `KeyS` only activates the `Ctrl + S` action,
+    /// while holding `ShiftLeft` and `KeyS` still activates `SpeedDown`.
     ///
     /// In normal usage you don’t call this function directly;
     /// instead you use higher-level helpers like:
     ///
-    /// ```
-    /// if keybindings.is_action_pressed("SaveAction") {
-    ///     println!("Save triggered!");
+    /// ```ignore
+    /// if keybindings.is_action_pressed(Action::Fire) {
+    ///     println!("Fire!");
     /// }
     /// ```
     ///
-    fn is_combo_active(&self, set: &[String], action: Action) -> bool {
-        if let Some(combos) = self.bindings.get(&action) {
-            for combo in combos {
-                let all_modifiers_pressed =
-                    combo.modifiers.iter().all(|m| set.contains(&m.to_string()));
-                let main_pressed = set.contains(&String::from(combo.input.as_string()));
-                if all_modifiers_pressed && main_pressed {
-                    return true;
-                }
+    fn is_combo_active(&self, set: &[String], held: &[String], action: Action) -> bool {
+        self.get_combos(action).iter().any(|combo| {
+            self.is_combo_selected(combo, set, held) && self.is_reachable(action, combo, set, held)
+        })
+    }
+
+    /// Walk the layers from the top: the first layer binding the input consumes it,
+    /// and an opaque layer stops everything below it
+    fn is_reachable(&self, action: Action, combo: &KeyCombo, set: &[String], held: &[String]) -> bool {
+        if self.contexts.is_empty() {
+            return true;
+        }
+        for context in self.contexts.iter().rev() {
+            if action.contexts().contains(context) {
+                return true;
+            }
+            if context.is_opaque() {
+                return false;
+            }
+            let consumed = Action::ALL
+                .into_iter()
+                .filter(|other| other.contexts().contains(context))
+                .flat_map(|other| self.get_combos(other))
+                .any(|other| other.input == combo.input && self.is_combo_selected(other, set, held));
+            if consumed {
+                return false;
             }
         }
         false
     }
+
+    /// True if a layer of the action is active and no opaque layer covers it
+    fn is_in_active_layers(&self, action: Action) -> bool {
+        if self.contexts.is_empty() {
+            return true;
+        }
+        for context in self.contexts.iter().rev() {
+            if action.contexts().contains(context) {
+                return true;
+            }
+            if context.is_opaque() {
+                return false;
+            }
+        }
+        false
+    }
+
+    /// True if the combo matches and no more specific combo of the active layers shadows it
+    fn is_combo_selected(&self, combo: &KeyCombo, set: &[String], held: &[String]) -> bool {
+        Self::combo_matches(combo, set, held)
+            && !self
+                .bindings
+                .iter()
+                .filter(|(action, _)| self.is_in_active_layers(**action))
+                .flat_map(|(_, combos)| combos)
+                .any(|other| {
+                    other.input == combo.input
+                        && other.modifiers.len() > combo.modifiers.len()
+                        && combo.modifiers.iter().all(|m| other.modifiers.contains(m))
+                        && Self::combo_matches(other, set, held)
+                })
+    }
+
+    fn combo_matches(combo: &KeyCombo, set: &[String], held: &[String]) -> bool {
+        let main = combo.input.as_string();
+        let main_active = match &combo.input {
+            KeyInput::Scroll(direction) => set
+                .iter()
+                .any(|event| event.starts_with(&format!("{}:", direction))),
+            _ => set.iter().any(|event| event == main),
+        };
+        // The main key does not count as its own modifier ("ShiftLeft" alone is not Shift + ShiftLeft)
+        main_active
+            && combo
+                .modifiers
+                .iter()
+                .all(|m| held.iter().any(|key| key != main && m.matches(key)))
+    }
 }
 
 // === END DEFINITION ===
 
+/// Binding layer, the same key can trigger different actions in different layers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InputContext {
+    /// Flying the ship
+    Gameplay,
+    /// Start screen and pause menus
+    Menu,
+    /// Debug tools, on top of the others when the debug mode is on
+    Debug,
+    /// A widget reads the raw keys, like the controls editor waiting for a binding
+    TextEntry,
+}
+
+impl InputContext {
+    /// Opaque layers consume every input, the layers below get nothing
+    pub fn is_opaque(&self) -> bool {
+        match self {
+            InputContext::Gameplay | InputContext::Menu | InputContext::TextEntry => true,
+            InputContext::Debug => false,
+        }
+    }
+}
+
 /// Game actions
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, PartialOrd, Ord)]
 pub enum Action {
@@ -384,6 +730,8 @@ pub enum Action {
     FireHoming,
 
     ToggleDebug,
+    CycleCamera,
+    ToggleMinimap,
     Escape,
     Confirm,
 
@@ -395,6 +743,78 @@ pub enum Action {
     ScrollDown,
 }
 
+impl Action {
+    pub const ALL: [Action; 19] = [
+        Action::SpeedUp,
+        Action::SpeedDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Stop,
+        Action::IncreaseMissileCapacity,
+        Action::DecreaseMissileCapacity,
+        Action::Fire,
+        Action::FireHoming,
+        Action::ToggleDebug,
+        Action::CycleCamera,
+        Action::ToggleMinimap,
+        Action::Escape,
+        Action::Confirm,
+        Action::Pause,
+        Action::Accelerate,
+        Action::SlowDown,
+        Action::ScrollUp,
+        Action::ScrollDown,
+    ];
+
+    /// Layers the action can be triggered in
+    pub fn contexts(&self) -> &'static [InputContext] {
+        use InputContext::*;
+        match self {
+            Action::SpeedUp
+            | Action::SpeedDown
+            | Action::MoveLeft
+            | Action::MoveRight
+            | Action::Stop
+            | Action::IncreaseMissileCapacity
+            | Action::DecreaseMissileCapacity
+            | Action::Fire
+            | Action::FireHoming
+            | Action::ToggleMinimap => &[Gameplay],
+            Action::Escape => &[Gameplay, Menu],
+            Action::Confirm | Action::ScrollUp | Action::ScrollDown => &[Menu],
+            Action::ToggleDebug => &[Gameplay, Menu, Debug],
+            Action::CycleCamera | Action::Pause | Action::Accelerate | Action::SlowDown => {
+                &[Gameplay, Debug]
+            }
+        }
+    }
+
+    /// Key of the name in the localization catalogs
+    pub fn key(&self) -> &'static str {
+        match self {
+            Action::SpeedUp => "action.speed_up",
+            Action::SpeedDown => "action.speed_down",
+            Action::MoveLeft => "action.move_left",
+            Action::MoveRight => "action.move_right",
+            Action::Stop => "action.stop",
+            Action::IncreaseMissileCapacity => "action.increase_missile_capacity",
+            Action::DecreaseMissileCapacity => "action.decrease_missile_capacity",
+            Action::Fire => "action.fire",
+            Action::FireHoming => "action.fire_homing",
+            Action::ToggleDebug => "action.toggle_debug",
+            Action::CycleCamera => "action.cycle_camera",
+            Action::ToggleMinimap => "action.toggle_minimap",
+            Action::Escape => "action.escape",
+            Action::Confirm => "action.confirm",
+            Action::Pause => "action.pause",
+            Action::Accelerate => "action.accelerate",
+            Action::SlowDown => "action.slow_down",
+            Action::ScrollUp => "action.scroll_up",
+            Action::ScrollDown => "action.scroll_down",
+        }
+    }
+}
+
 pub fn default_keybindings() -> KeyBindings {
     let mut kb = KeyBindings::new();
 
@@ -420,6 +840,8 @@ pub fn default_keybindings() -> KeyBindings {
 
     // ==== Misc ====
     kb.bind_key(Action::ToggleDebug, Key::F3);
+    kb.bind_key(Action::CycleCamera, Key::F4);
+    kb.bind_key(Action::ToggleMinimap, Key::KeyM);
     kb.bind_key(Action::Confirm, Key::Return);
     kb.bind_key(Action::Escape, Key::Escape);
 
@@ -430,95 +852,210 @@ pub fn default_keybindings() -> KeyBindings {
     kb
 }
 
-pub fn handle_input(gamestate: &mut Gamestate, keybindings: &KeyBindings) {
-    let turn_rate = gamestate.spaceship.get_turn_rate();
+/// Actions sampled for one fixed tick of the simulation, sent to the peer in a network game
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TickInput {
+    /// Actions held when the frame was sampled
+    pub held: Vec<Action>,
+    /// Actions pressed since the previous tick, once per press
+    pub pressed: Vec<Action>,
+    /// Scroll steps since the previous tick
+    pub scrolled: Vec<(Action, u8)>,
+}
 
-    // Toggle debug
-    if keybindings.is_action_pressed(Action::ToggleDebug) {
-        gamestate.debug = !gamestate.debug;
+impl TickInput {
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
     }
 
-    // Start menu handling
-    if gamestate.get_last_menu_item() == "Start" {
-        if keybindings.is_action_pressed(Action::Confirm) {
-            gamestate.reset();
-        }
-        if keybindings.is_action_pressed(Action::Escape) {
-            keybindings.clear_events();
-            gamestate.exit = true;
-        }
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
     }
 
-    // Pause menu
-    if keybindings.is_action_pressed(Action::Escape) {
-        if gamestate.menu.is_empty() {
-            gamestate.menu.push(String::from("Main"));
-        } else {
-            gamestate.menu.pop();
+    /// Held, or tapped and released between two samples
+    pub fn is_active(&self, action: Action) -> bool {
+        self.is_held(action) || self.is_pressed(action)
+    }
+
+    /// Presses and scroll steps since the previous tick
+    pub fn get_count(&self, action: Action) -> u32 {
+        self.pressed.iter().filter(|a| **a == action).count() as u32
+            + self
+                .scrolled
+                .iter()
+                .filter(|(a, _)| *a == action)
+                .map(|(_, amount)| *amount as u32)
+                .sum::<u32>()
+    }
+}
+
+/// Gameplay input sampled every frame and consumed by the fixed-timestep loop.
+///
+/// Held actions apply to every tick, presses are delivered to a single tick
+/// and wait for the next one when a frame runs no tick at all.
+#[derive(Debug, Default)]
+pub struct InputBuffer {
+    pending: TickInput,
+}
+
+impl InputBuffer {
+    /// Actions read by the simulation ticks
+    pub const GAMEPLAY: [Action; 9] = [
+        Action::SpeedUp,
+        Action::SpeedDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Stop,
+        Action::Fire,
+        Action::FireHoming,
+        Action::IncreaseMissileCapacity,
+        Action::DecreaseMissileCapacity,
+    ];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the state of the gameplay actions, call it once per frame before the ticks
+    pub fn sample(&mut self, keybindings: &KeyBindings) {
+        self.pending.held = Self::GAMEPLAY
+            .into_iter()
+            .filter(|action| keybindings.is_action_held(*action))
+            .collect();
+        for action in Self::GAMEPLAY {
+            if keybindings.is_action_pressed(action) {
+                self.pending.pressed.push(action);
+            }
+            let amount = keybindings.is_scrolled(action);
+            if amount != 0 {
+                self.pending.scrolled.push((action, amount));
+            }
         }
     }
 
+    /// Input of the next tick, the presses are consumed
+    pub fn next_tick(&mut self) -> TickInput {
+        let tick = self.pending.clone();
+        self.pending.pressed.clear();
+        self.pending.scrolled.clear();
+        tick
+    }
+
+    /// Forget everything, when leaving the gameplay
+    pub fn clear(&mut self) {
+        self.pending = TickInput::default();
+    }
+}
+
+/// Apply the gameplay actions of one tick of a player, `TICKS` seconds of simulation
+pub fn apply_tick_input(gamestate: &mut Gamestate, player: usize, input: &TickInput) {
+    let spaceship = &mut gamestate.players[player].spaceship;
+    let turn_rate = spaceship.get_turn_rate();
+    spaceship.tick_cooldowns(TICKS);
+
     // Thrust forward/backward
-    if keybindings.is_action_held(Action::SpeedUp) && gamestate.simulation_speed > 0.0 {
-        gamestate
-            .spaceship
-            .move_spaceship(gamestate.delta_time, true);
+    if input.is_held(Action::SpeedUp) {
+        spaceship.move_spaceship(TICKS, true);
+        if spaceship.get_life() {
+            gamestate.audio.play_throttled(SoundEffect::Thrust, 0.35);
+        }
     }
-    if keybindings.is_action_held(Action::SpeedDown) && gamestate.simulation_speed > 0.0 {
-        gamestate
-            .spaceship
-            .move_spaceship(gamestate.delta_time, false);
+    let spaceship = &mut gamestate.players[player].spaceship;
+    if input.is_held(Action::SpeedDown) {
+        spaceship.move_spaceship(TICKS, false);
     }
 
     // Rotation
-    if keybindings.is_action_held(Action::MoveLeft) && gamestate.simulation_speed > 0.0 {
-        gamestate
-            .spaceship
-            .add_rotation(-turn_rate * gamestate.delta_time as f32);
+    if input.is_held(Action::MoveLeft) {
+        spaceship.add_rotation(-turn_rate * TICKS as f32);
     }
-    if keybindings.is_action_held(Action::MoveRight) && gamestate.simulation_speed > 0.0 {
-        gamestate
-            .spaceship
-            .add_rotation(turn_rate * gamestate.delta_time as f32);
+    if input.is_held(Action::MoveRight) {
+        spaceship.add_rotation(turn_rate * TICKS as f32);
     }
 
     // Stop
-    if keybindings.is_action_pressed(Action::Stop) {
-        gamestate.spaceship.stop();
+    if input.is_pressed(Action::Stop) {
+        spaceship.stop();
+    }
+
+    // Missile capacity
+    for _ in 0..input.get_count(Action::IncreaseMissileCapacity) {
+        spaceship.modify_capacity(1);
+    }
+    for _ in 0..input.get_count(Action::DecreaseMissileCapacity) {
+        spaceship.modify_capacity(-1);
     }
 
     // Fire missiles
-    if gamestate.spaceship.get_life()
-        && gamestate.spaceship.get_firing_cooldown() <= 0.0
-        && keybindings.is_action_held(Action::Fire)
-        && gamestate.simulation_speed > 0.0
-        && (gamestate.debug || gamestate.menu.is_empty())
+    if spaceship.get_life()
+        && spaceship.get_firing_cooldown() <= 0.0
+        && input.is_active(Action::Fire)
     {
-        gamestate.summon_missile(false);
-        gamestate.spaceship.set_firing_cooldown(0.15);
+        spaceship.set_firing_cooldown(Spaceship::FIRE_COOLDOWN);
+        gamestate.summon_missile(player, false);
     }
 
     // Fire homing missiles
-    if gamestate.spaceship.get_life()
-        && gamestate.spaceship.get_missile_capacity() > 0
-        && gamestate.spaceship.get_homming_cooldown() <= 0.0
-        && keybindings.is_action_held(Action::FireHoming)
-        && gamestate.simulation_speed > 0.0
-        && (gamestate.debug || gamestate.menu.is_empty())
+    let spaceship = &mut gamestate.players[player].spaceship;
+    if spaceship.get_life()
+        && spaceship.get_missile_capacity() > 0
+        && spaceship.get_homming_cooldown() <= 0.0
+        && input.is_active(Action::FireHoming)
     {
-        gamestate.summon_missile(true);
-        gamestate.spaceship.set_homming_cooldown(0.8);
+        spaceship.set_homming_cooldown(Spaceship::HOMING_COOLDOWN);
+        gamestate.summon_missile(player, true);
     }
+}
 
-    // Missile capacity
-    if (keybindings.is_action_pressed(Action::IncreaseMissileCapacity) || keybindings.is_scrolled(Action::IncreaseMissileCapacity) != 0) && gamestate.simulation_speed > 0.0 {
-        gamestate.spaceship.modify_capacity(1);
+/// Apply the actions that do not depend on the simulation ticks: menus, camera, time manipulation
+pub fn handle_input(gamestate: &mut Gamestate, keybindings: &KeyBindings) {
+    // Text entry widgets read the raw events themselves, they ran earlier in the frame
+    if keybindings.get_context() == Some(InputContext::TextEntry) {
+        keybindings.clear_events();
+        return;
+    }
+
+    // Toggle debug, never in a network game where the peer's simulation would differ
+    if keybindings.is_action_pressed(Action::ToggleDebug) && !gamestate.lockstep {
+        gamestate.debug = !gamestate.debug;
     }
-    if (keybindings.is_action_pressed(Action::DecreaseMissileCapacity) || keybindings.is_scrolled(Action::DecreaseMissileCapacity) != 0) && gamestate.simulation_speed > 0.0 {
-        gamestate.spaceship.modify_capacity(-1);
+
+    // Camera mode
+    if keybindings.is_action_pressed(Action::CycleCamera) {
+        gamestate.camera.cycle_mode(gamestate.debug);
+    }
+
+    // Minimap
+    if keybindings.is_action_pressed(Action::ToggleMinimap) {
+        gamestate.minimap.toggle();
+        settings::save_settings(gamestate);
     }
 
-    // Time manipulation
+    // Start menu handling
+    if gamestate.get_last_menu_item() == "Start" {
+        if keybindings.is_action_pressed(Action::Confirm) {
+            gamestate.audio.play(SoundEffect::UiBlip);
+            gamestate.reset();
+        } else if keybindings.is_action_pressed(Action::Escape) {
+            keybindings.clear_events();
+            gamestate.exit = true;
+        }
+    }
+    // Pause menu
+    else if keybindings.is_action_pressed(Action::Escape) {
+        gamestate.audio.play(SoundEffect::UiBlip);
+        if gamestate.menu.is_empty() {
+            gamestate.menu.push(String::from("Main"));
+        } else {
+            gamestate.menu.pop();
+        }
+    }
+
+    // Time manipulation, the peer sets the pace in a network game
+    if gamestate.lockstep {
+        keybindings.clear_events();
+        return;
+    }
     if keybindings.is_action_held(Action::Pause) {
         gamestate.simulation_speed = 0.0;
     }
@@ -531,3 +1068,216 @@ pub fn handle_input(gamestate: &mut Gamestate, keybindings: &KeyBindings) {
 
     keybindings.clear_events();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputEvent;
+    use serde_json::json;
+
+    #[test]
+    fn combos_of_covered_layers_do_not_shadow() {
+        let mut keybindings = KeyBindings::new();
+        keybindings.bind_key(Action::SpeedDown, Key::KeyS);
+        keybindings.bind(
+            Action::Confirm,
+            KeyCombo {
+                input: KeyInput::from_key(Key::KeyS),
+                modifiers: vec![Modifier::Control],
+            },
+        );
+        let sink = keybindings.sink();
+        sink.push(InputEvent::Press("ControlLeft".to_string()));
+        sink.push(InputEvent::Press("KeyS".to_string()));
+
+        // Confirm only exists in the menus, Ctrl + S is still S while flying
+        keybindings.set_contexts(vec![InputContext::Gameplay]);
+        assert!(keybindings.is_action_pressed(Action::SpeedDown));
+        assert!(!keybindings.is_action_pressed(Action::Confirm));
+
+        keybindings.set_contexts(vec![InputContext::Gameplay, InputContext::Menu]);
+        assert!(!keybindings.is_action_pressed(Action::SpeedDown));
+        assert!(keybindings.is_action_pressed(Action::Confirm));
+    }
+
+    fn key(name: &str) -> Value {
+        json!({ "input": { "Key": name }, "modifiers": [] })
+    }
+
+    #[test]
+    fn bare_maps_are_migrated_from_version_0() {
+        let (bindings, report) = KeyBindings::from_json(json!({ "Fire": [key("Space")] })).unwrap();
+        assert_eq!(report.version, 0);
+        assert!(report.invalid.is_empty());
+        assert_eq!(
+            bindings.get_combos(Action::Fire),
+            &[KeyCombo {
+                input: KeyInput::from_key(Key::Space),
+                modifiers: vec![],
+            }]
+        );
+        assert_eq!(bindings.get_settings(), &InputSettings::default());
+
+        assert!(KeyBindings::from_json(json!([key("Space")])).is_err());
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let json = json!({ "version": SCHEMA_VERSION + 1, "bindings": {} });
+        assert!(KeyBindings::from_json(json).is_err());
+        assert!(KeyBindings::from_json(json!({ "version": "1" })).is_err());
+    }
+
+    #[test]
+    fn invalid_entries_are_reported_and_skipped() {
+        let json = json!({
+            "version": SCHEMA_VERSION,
+            "bindings": {
+                "Teleport": [key("KeyT")],
+                "Fire": [key("KeyF"), key("NotAKey")],
+                "Pause": { "input": { "Key": "Space" } },
+            }
+        });
+        let (bindings, report) = KeyBindings::from_json(json).unwrap();
+        assert_eq!(report.version, SCHEMA_VERSION);
+        assert_eq!(report.invalid.len(), 3, "{:?}", report.invalid);
+        assert!(report.invalid.iter().any(|e| e.contains("Teleport")));
+        assert!(report.invalid.iter().any(|e| e.contains("NotAKey")));
+        assert!(report.invalid.iter().any(|e| e.starts_with("Pause")));
+
+        // The valid combo of Fire is kept, without the defaults
+        assert_eq!(
+            bindings.get_combos(Action::Fire),
+            &[KeyCombo {
+                input: KeyInput::from_key(Key::KeyF),
+                modifiers: vec![],
+            }]
+        );
+        assert!(!report.merged.contains(&Action::Fire));
+    }
+
+    #[test]
+    fn actions_without_a_valid_combo_get_their_defaults() {
+        let defaults = default_keybindings();
+        let json = json!({
+            "version": SCHEMA_VERSION,
+            "bindings": {
+                "Stop": [key("NotAKey"), { "input": { "Mouse": "Side" }, "modifiers": [] }],
+                "Pause": [],
+            }
+        });
+        let (bindings, report) = KeyBindings::from_json_with_defaults(json, &defaults).unwrap();
+        assert_eq!(report.invalid.len(), 2);
+        assert_eq!(
+            bindings.get_combos(Action::Stop),
+            defaults.get_combos(Action::Stop)
+        );
+        assert!(report.merged.contains(&Action::Stop));
+
+        // An empty list is a choice of the player, not a mistake
+        assert!(bindings.get_combos(Action::Pause).is_empty());
+        assert!(!report.merged.contains(&Action::Pause));
+    }
+
+    #[test]
+    fn missing_actions_are_merged_from_the_defaults() {
+        let defaults = default_keybindings();
+        let json = json!({ "version": SCHEMA_VERSION, "bindings": { "Fire": [key("KeyF")] } });
+        let (bindings, report) = KeyBindings::from_json_with_defaults(json, &defaults).unwrap();
+
+        // Every action with a default binding except the one in the file
+        let expected: Vec<Action> = Action::ALL
+            .into_iter()
+            .filter(|a| *a != Action::Fire && !defaults.get_combos(*a).is_empty())
+            .collect();
+        assert_eq!(report.merged, expected);
+        for action in expected {
+            assert_eq!(bindings.get_combos(action), defaults.get_combos(action));
+        }
+    }
+
+    #[test]
+    fn settings_survive_a_save_and_load() {
+        let path = std::env::temp_dir().join(format!("keybindings-{}.json", std::process::id()));
+        let path = path.to_string_lossy();
+        let mut bindings = default_keybindings();
+        bindings.set_scroll_sensitivity(2.5);
+        bindings.save(&path).unwrap();
+
+        let (loaded, report) = KeyBindings::load(&path).unwrap();
+        std::fs::remove_file(path.as_ref()).unwrap();
+        assert_eq!(loaded.get_settings().scroll_sensitivity, 2.5);
+        assert!(report.invalid.is_empty());
+        assert!(report.merged.is_empty());
+        for action in Action::ALL {
+            assert_eq!(loaded.get_combos(action), bindings.get_combos(action));
+        }
+    }
+
+    /// Default bindings in gameplay, with the sink to press their keys
+    fn gameplay() -> (KeyBindings, InputSink) {
+        let mut keybindings = default_keybindings();
+        keybindings.set_contexts(vec![InputContext::Gameplay]);
+        let sink = keybindings.sink();
+        (keybindings, sink)
+    }
+
+    /// One frame of the main loop running `ticks` simulation ticks
+    fn frame(buffer: &mut InputBuffer, keybindings: &KeyBindings, ticks: usize) -> Vec<TickInput> {
+        buffer.sample(keybindings);
+        let inputs = (0..ticks).map(|_| buffer.next_tick()).collect();
+        keybindings.clear_events();
+        inputs
+    }
+
+    #[test]
+    fn presses_wait_for_the_next_tick() {
+        let (keybindings, sink) = gameplay();
+        let mut buffer = InputBuffer::new();
+
+        sink.push(InputEvent::Press("KeyQ".to_string()));
+        sink.push(InputEvent::Release("KeyQ".to_string()));
+        assert!(frame(&mut buffer, &keybindings, 0).is_empty());
+
+        let ticks = frame(&mut buffer, &keybindings, 1);
+        assert_eq!(ticks[0].get_count(Action::Fire), 1);
+        assert!(!ticks[0].is_held(Action::Fire));
+
+        let ticks = frame(&mut buffer, &keybindings, 1);
+        assert_eq!(ticks[0], TickInput::default());
+    }
+
+    #[test]
+    fn presses_go_to_the_first_tick_and_held_actions_to_all() {
+        let (keybindings, sink) = gameplay();
+        let mut buffer = InputBuffer::new();
+
+        sink.push(InputEvent::Press("KeyW".to_string()));
+        sink.push(InputEvent::Press("Middle".to_string()));
+        let ticks = frame(&mut buffer, &keybindings, 3);
+        assert!(ticks[0].is_pressed(Action::Stop));
+        assert!(ticks[1..].iter().all(|tick| !tick.is_pressed(Action::Stop)));
+        assert!(ticks.iter().all(|tick| tick.is_held(Action::SpeedUp)));
+        assert!(ticks.iter().all(|tick| tick.is_held(Action::Stop)));
+
+        // Still held on the next frame, without a new press
+        sink.push(InputEvent::Release("Middle".to_string()));
+        let ticks = frame(&mut buffer, &keybindings, 2);
+        assert!(ticks.iter().all(|tick| tick.held == vec![Action::SpeedUp]));
+        assert!(ticks.iter().all(|tick| tick.pressed.is_empty()));
+    }
+
+    #[test]
+    fn clearing_drops_pending_presses() {
+        let (keybindings, sink) = gameplay();
+        let mut buffer = InputBuffer::new();
+
+        sink.push(InputEvent::Press("KeyW".to_string()));
+        sink.push(InputEvent::Press("KeyE".to_string()));
+        sink.push(InputEvent::Scroll(1.0));
+        frame(&mut buffer, &keybindings, 0);
+        buffer.clear();
+
+        assert_eq!(buffer.next_tick(), TickInput::default());
+    }
+}
@@ -1,9 +1,30 @@
 // Make modules public
+pub mod achievements;
+pub mod ai;
 pub mod asteroid;
+pub mod audio;
 pub mod spaceship;
 pub mod missile;
+pub mod music;
 
+pub mod camera;
+pub mod controls;
+pub mod env;
 pub mod floating_text;
+pub mod highscores;
+pub mod hud;
+pub mod i18n;
+pub mod input;
 pub mod menus;
+pub mod minimap;
+pub mod netplay;
+pub mod player;
+pub mod remote;
+pub mod settings;
+pub mod spectator;
 pub mod gamestate;
-pub mod key_bindings;
\ No newline at end of file
+pub mod hot_reload;
+pub mod stats;
+pub mod key_bindings;
+pub mod synth;
+pub mod versus;
\ No newline at end of file
@@ -2,9 +2,19 @@ use chrono::Local;
 use macroquad::prelude::{Color, MouseButton, mouse_position, draw_rectangle, draw_text, measure_text, is_mouse_button_pressed, screen_width, screen_dpi_scale, clear_background, BLACK, WHITE, RED, GREEN, BLUE, PURPLE, BEIGE, GRAY, GOLD, YELLOW, ORANGE};
 use std::env;
 
-use crate::gamestate::Gamestate;
+use crate::{
+    achievements,
+    audio::{SoundEffect, next_volume_step},
+    controls,
+    gamestate::Gamestate,
+    highscores,
+    hud,
+    key_bindings::KeyBindings,
+    settings,
+    stats,
+};
 
-fn button(
+pub(crate) fn button(
     x: f32,
     y: f32,
     width: f32,
@@ -42,11 +52,16 @@ fn button(
     is_hovered && is_mouse_button_pressed(MouseButton::Left)
 }
 
-pub fn menu_draw(gamestate: &mut Gamestate, screen_width: f32, screen_height: f32) -> String {
+pub fn menu_draw(
+    gamestate: &mut Gamestate,
+    keybindings: &mut KeyBindings,
+    screen_width: f32,
+    screen_height: f32,
+) -> String {
     let current_menu = gamestate.get_last_menu_item();
 
     // During Gameplay
-    if current_menu != "Start" && !gamestate.menu.is_empty() {
+    if !["Start", "HighScores", "Achievements"].contains(&current_menu) && !gamestate.menu.is_empty() {
         // Darken first
         draw_rectangle(
             0.0,
@@ -63,9 +78,10 @@ pub fn menu_draw(gamestate: &mut Gamestate, screen_width: f32, screen_height: f3
             screen_height,
             Color::from_rgba(255, 255, 255, 32),
         );
+        let title = gamestate.locale.tr("menu.pause.title");
         draw_text(
-            "Pause Menu",
-            (screen_width - measure_text("Pause Menu", None, 80, 1.0).width) / 2.0,
+            title,
+            (screen_width - measure_text(title, None, 80, 1.0).width) / 2.0,
             100.0,
             80.0,
             WHITE,
@@ -74,114 +90,312 @@ pub fn menu_draw(gamestate: &mut Gamestate, screen_width: f32, screen_height: f3
 
     // Main menu
     if current_menu == "Main" {
+        if button(
+            screen_width / 2.0 - 0.35 * screen_width,
+            screen_height * 0.25,
+            0.35 * screen_width * 2.0,
+            0.05 * screen_height,
+            gamestate.locale.tr("menu.pause.controls"),
+            60.0,
+            BLUE,
+        ) {
+            gamestate.menu.push(String::from("Controls"));
+            gamestate.audio.play(SoundEffect::UiBlip);
+        }
         if button(
             screen_width / 2.0 - 0.35 * screen_width,
             screen_height * 0.25 + 0.1 * screen_height * 1.0,
             0.35 * screen_width * 2.0,
             0.05 * screen_height,
-            "Quit",
+            gamestate.locale.tr("menu.pause.quit"),
             60.0,
             RED,
         ) {
             return String::from("Exit");
         }
+        // The debug tools would change the simulation of a single peer
+        #[cfg(debug_assertions)]
+        if !gamestate.lockstep
+            && button(
+                screen_width / 2.0 - 0.35 * screen_width,
+                screen_height * 0.25 + 0.1 * screen_height * 2.0,
+                0.35 * screen_width * 2.0,
+                0.05 * screen_height,
+                gamestate.locale.tr("menu.pause.clear"),
+                60.0,
+                ORANGE,
+            )
+        {
+            return String::from("Clear");
+        }
         #[cfg(debug_assertions)]
+        if !gamestate.lockstep
+            && button(
+                screen_width / 2.0 - 0.35 * screen_width,
+                screen_height * 0.25 + 0.1 * screen_height * 3.0,
+                0.35 * screen_width * 2.0,
+                0.05 * screen_height,
+                gamestate.locale.tr("menu.pause.split_all"),
+                60.0,
+                ORANGE,
+            )
+        {
+            return String::from("Split All");
+        }
+
         if button(
             screen_width / 2.0 - 0.35 * screen_width,
-            screen_height * 0.25 + 0.1 * screen_height * 2.0,
+            screen_height * 0.25 + 0.1 * screen_height * 4.0,
             0.35 * screen_width * 2.0,
             0.05 * screen_height,
-            "Clear",
+            gamestate.locale.tr("menu.pause.hardware"),
             60.0,
-            ORANGE,
+            PURPLE,
         ) {
-            return String::from("Clear");
+            gamestate.menu.push(String::from("Hardware"));
+            gamestate.audio.play(SoundEffect::UiBlip);
         }
+
         #[cfg(debug_assertions)]
+        if !gamestate.lockstep
+            && button(
+                screen_width / 2.0 - 0.35 * screen_width,
+                screen_height * 0.25 + 0.1 * screen_height * 5.0,
+                0.35 * screen_width * 2.0,
+                0.05 * screen_height,
+                gamestate.locale.tr("menu.pause.debug_asteroids"),
+                60.0,
+                PURPLE,
+            )
+        {
+            return String::from("Summon Asteroid");
+        }
+
+        if button(
+            screen_width / 2.0 - 0.35 * screen_width,
+            screen_height * 0.25 + 0.1 * screen_height * 6.0,
+            0.35 * screen_width * 2.0,
+            0.05 * screen_height,
+            gamestate.locale.tr("menu.pause.settings"),
+            60.0,
+            BLUE,
+        ) {
+            gamestate.menu.push(String::from("Settings"));
+            gamestate.audio.play(SoundEffect::UiBlip);
+        }
+    }
+    // Settings menu
+    else if current_menu == "Settings" {
+        if button(
+            screen_width / 2.0 - 0.35 * screen_width,
+            screen_height * 0.25,
+            0.35 * screen_width * 2.0,
+            0.05 * screen_height,
+            &gamestate.locale.format(
+                "menu.settings.language",
+                &[(
+                    "language",
+                    gamestate.locale.get_language().native_name().to_string(),
+                )],
+            ),
+            60.0,
+            BLUE,
+        ) {
+            let language = gamestate.locale.get_language().next();
+            gamestate.locale.set_language(language);
+            settings::save_settings(gamestate);
+            gamestate.audio.play(SoundEffect::UiBlip);
+        }
+        if button(
+            screen_width / 2.0 - 0.35 * screen_width,
+            screen_height * 0.25 + 0.1 * screen_height * 1.0,
+            0.35 * screen_width * 2.0,
+            0.05 * screen_height,
+            &gamestate.locale.format(
+                "menu.settings.minimap",
+                &[(
+                    "state",
+                    gamestate
+                        .locale
+                        .tr(if gamestate.minimap.is_visible() {
+                            "menu.settings.shown"
+                        } else {
+                            "menu.settings.hidden"
+                        })
+                        .to_string(),
+                )],
+            ),
+            60.0,
+            BLUE,
+        ) {
+            gamestate.minimap.toggle();
+            settings::save_settings(gamestate);
+            gamestate.audio.play(SoundEffect::UiBlip);
+        }
+        if button(
+            screen_width / 2.0 - 0.35 * screen_width,
+            screen_height * 0.25 + 0.1 * screen_height * 2.0,
+            0.35 * screen_width * 2.0,
+            0.05 * screen_height,
+            &gamestate.locale.format(
+                "menu.settings.minimap_corner",
+                &[(
+                    "corner",
+                    gamestate
+                        .locale
+                        .tr(gamestate.minimap.get_corner().key())
+                        .to_string(),
+                )],
+            ),
+            60.0,
+            BLUE,
+        ) {
+            gamestate.minimap.cycle_corner();
+            settings::save_settings(gamestate);
+            gamestate.audio.play(SoundEffect::UiBlip);
+        }
         if button(
             screen_width / 2.0 - 0.35 * screen_width,
             screen_height * 0.25 + 0.1 * screen_height * 3.0,
             0.35 * screen_width * 2.0,
             0.05 * screen_height,
-            "Split all",
+            &gamestate.locale.format(
+                "menu.settings.minimap_scale",
+                &[(
+                    "value",
+                    format!("{:.0}", gamestate.minimap.get_scale() * 100.0),
+                )],
+            ),
             60.0,
-            ORANGE,
+            BLUE,
         ) {
-            return String::from("Split All");
+            gamestate.minimap.cycle_scale();
+            settings::save_settings(gamestate);
+            gamestate.audio.play(SoundEffect::UiBlip);
         }
 
+        let mut volume = gamestate.audio.get_volume();
         if button(
             screen_width / 2.0 - 0.35 * screen_width,
             screen_height * 0.25 + 0.1 * screen_height * 4.0,
             0.35 * screen_width * 2.0,
             0.05 * screen_height,
-            "Get Hardware",
+            &gamestate.locale.format(
+                "menu.settings.master_volume",
+                &[("value", format!("{:.0}", volume.master * 100.0))],
+            ),
             60.0,
-            PURPLE,
+            BLUE,
         ) {
-            gamestate.menu.push(String::from("Hardware"));
+            volume.master = next_volume_step(volume.master);
         }
-
-        #[cfg(debug_assertions)]
         if button(
             screen_width / 2.0 - 0.35 * screen_width,
             screen_height * 0.25 + 0.1 * screen_height * 5.0,
             0.35 * screen_width * 2.0,
             0.05 * screen_height,
-            "Spawn Debug Asteroids",
+            &gamestate.locale.format(
+                "menu.settings.sfx_volume",
+                &[("value", format!("{:.0}", volume.sfx * 100.0))],
+            ),
             60.0,
-            PURPLE,
+            BLUE,
         ) {
-            return String::from("Summon Asteroid");
+            volume.sfx = next_volume_step(volume.sfx);
         }
+        if button(
+            screen_width / 2.0 - 0.35 * screen_width,
+            screen_height * 0.25 + 0.1 * screen_height * 6.0,
+            0.35 * screen_width * 2.0,
+            0.05 * screen_height,
+            &gamestate.locale.format(
+                "menu.settings.music_volume",
+                &[("value", format!("{:.0}", volume.music * 100.0))],
+            ),
+            60.0,
+            BLUE,
+        ) {
+            volume.music = next_volume_step(volume.music);
+        }
+        if volume != gamestate.audio.get_volume() {
+            gamestate.audio.set_volume(volume);
+            settings::save_settings(gamestate);
+            gamestate.audio.play(SoundEffect::UiBlip);
+        }
+    }
+    // Controls menu
+    else if current_menu == "Controls" {
+        controls::draw_controls(gamestate, keybindings);
+    }
+    // High scores, from the start screen
+    else if current_menu == "HighScores" {
+        highscores::draw_highscores(gamestate, screen_width, screen_height);
+    }
+    // Achievement gallery, from the start screen
+    else if current_menu == "Achievements" {
+        achievements::draw_gallery(gamestate, screen_width, screen_height);
     }
     // Hardware menu
     else if current_menu == "Hardware" {
         let screen_width_start = 0.25;
         draw_text(
-            &(format!("Operating System: {}", env::consts::OS.to_uppercase())),
+            &gamestate.locale.format(
+                "menu.hardware.os",
+                &[("os", env::consts::OS.to_uppercase())],
+            ),
             screen_width * screen_width_start,
             screen_height * 0.25 + 1.0 * 50.0,
             48.0,
             GREEN,
         );
         draw_text(
-            &(format!("Screen Dimensions: {}X{}", screen_width, screen_height)),
+            &gamestate.locale.format(
+                "menu.hardware.dimensions",
+                &[
+                    ("width", screen_width.to_string()),
+                    ("height", screen_height.to_string()),
+                ],
+            ),
             screen_width * screen_width_start,
             screen_height * 0.25 + 2.0 * 50.0,
             48.0,
             GREEN,
         );
         draw_text(
-            &(format!("DPI scaling: {:.0}%", screen_dpi_scale() * 100.0)),
+            &gamestate.locale.format(
+                "menu.hardware.dpi",
+                &[("value", format!("{:.0}", screen_dpi_scale() * 100.0))],
+            ),
             screen_width * screen_width_start,
             screen_height * 0.25 + 3.0 * 50.0,
             48.0,
             GREEN,
         );
         draw_text(
-            "Note that on certain OSes the fullscreen dimensions take into account the DPI",
+            gamestate.locale.tr("menu.hardware.dpi_note"),
             screen_width * screen_width_start,
             screen_height * 0.25 + 3.75 * 50.0,
             32.0,
             BEIGE,
         );
         draw_text(
-            &format!("FPS: {}", gamestate.fps),
+            &gamestate
+                .locale
+                .format("menu.hardware.fps", &[("fps", gamestate.fps.to_string())]),
             screen_width * screen_width_start,
             screen_height * 0.25 + 5.0 * 50.0,
             48.0,
             GREEN,
         );
         draw_text(
-            "The FPS counter is updated 4 times a second and may register with innacurracy",
+            gamestate.locale.tr("menu.hardware.fps_note"),
             screen_width * screen_width_start,
             screen_height * 0.25 + 5.75 * 50.0,
             32.0,
             BEIGE,
         );
         draw_text(
-            &"Keys Down:",
+            gamestate.locale.tr("menu.hardware.keys_down"),
             screen_width * screen_width_start,
             screen_height * 0.25 + 6.5 * 50.0,
             48.0,
@@ -200,80 +414,120 @@ pub fn menu_draw(gamestate: &mut Gamestate, screen_width: f32, screen_height: f3
         let score = gamestate.score;
 
         clear_background(BLACK);
+        let title = gamestate.locale.tr("menu.start.title");
         draw_text(
-            "ASTEROIDS",
-            screen_width / 2.0
-                - measure_text("ASTEROIDS", None, 40, screen_dpi_scale()).width / 2.0,
+            title,
+            screen_width / 2.0 - measure_text(title, None, 40, screen_dpi_scale()).width / 2.0,
             screen_height / 2.0 - 50.0,
             40.0,
             WHITE,
         );
+        let start = gamestate.locale.tr("menu.start.press_start");
         draw_text(
-            "Press ENTER to start",
-            screen_width / 2.0
-                - measure_text("Press ENTER to start", None, 30, screen_dpi_scale()).width / 2.0,
+            start,
+            screen_width / 2.0 - measure_text(start, None, 30, screen_dpi_scale()).width / 2.0,
             screen_height / 2.0,
             30.0,
             GRAY,
         );
+        let quit = gamestate.locale.tr("menu.start.press_quit");
         draw_text(
-            "Press Esc to quit",
-            screen_width / 2.0
-                - measure_text("Press Esc to quit", None, 30, screen_dpi_scale()).width / 2.0,
+            quit,
+            screen_width / 2.0 - measure_text(quit, None, 30, screen_dpi_scale()).width / 2.0,
             screen_height / 2.0 + 50.0,
             30.0,
             GRAY,
         );
 
-        if gamestate.over {
+        let score_text = gamestate.locale.format(
+            "menu.start.score",
+            &[("score", score[0].to_string()), ("max", score[1].to_string())],
+        );
+        let match_winner = gamestate.versus.as_ref().and_then(|v| v.get_match_winner());
+        if let (true, Some(winner)) = (gamestate.over, match_winner) {
+            let title = gamestate
+                .locale
+                .format("versus.match_won", &[("player", (winner + 1).to_string())]);
             draw_text(
-                "GAME OVER",
-                screen_width / 2.0
-                    - measure_text("GAME OVER", None, 60, screen_dpi_scale()).width / 2.0,
+                &title,
+                screen_width / 2.0 - measure_text(&title, None, 60, screen_dpi_scale()).width / 2.0,
+                screen_height / 2.0 - 150.0,
+                60.0,
+                gamestate.players[winner].color,
+            );
+        } else if gamestate.over {
+            let title = gamestate.locale.tr("menu.start.game_over");
+            draw_text(
+                title,
+                screen_width / 2.0 - measure_text(title, None, 60, screen_dpi_scale()).width / 2.0,
                 screen_height / 2.0 - 150.0,
                 60.0,
                 RED,
             );
             draw_text(
-                &format!("Score: {}/{}", score[0], score[1]),
+                &score_text,
                 screen_width / 2.0
-                    - measure_text(
-                        &format!("Score: {}/{}", gamestate.score[0], gamestate.score[1]),
-                        None,
-                        30,
-                        screen_dpi_scale(),
-                    )
-                    .width
-                        / 2.0,
+                    - measure_text(&score_text, None, 30, screen_dpi_scale()).width / 2.0,
                 screen_height / 2.0 - 125.0,
                 30.0,
                 RED,
             );
         } else if gamestate.win {
+            let title = gamestate.locale.tr("menu.start.win");
             draw_text(
-                "YOU WIN",
-                screen_width / 2.0
-                    - measure_text("YOU WIN", None, 60, screen_dpi_scale()).width / 2.0,
+                title,
+                screen_width / 2.0 - measure_text(title, None, 60, screen_dpi_scale()).width / 2.0,
                 screen_height / 2.0 - 150.0,
                 60.0,
                 GREEN,
             );
             draw_text(
-                &format!("Score: {}/{}", score[0], score[1]),
+                &score_text,
                 screen_width / 2.0
-                    - measure_text(
-                        &format!("Score: {}/{}", score[0], score[1]),
-                        None,
-                        30,
-                        screen_dpi_scale(),
-                    )
-                    .width
-                        / 2.0,
+                    - measure_text(&score_text, None, 30, screen_dpi_scale()).width / 2.0,
                 screen_height / 2.0 - 125.0,
                 30.0,
                 GREEN,
             );
         }
+
+        if (gamestate.over || gamestate.win) && gamestate.versus.is_none() {
+            stats::draw_run_stats(
+                &gamestate.locale,
+                &gamestate.stats,
+                &gamestate.lifetime_stats,
+                screen_width * 0.03,
+                screen_height * 0.3,
+            );
+        }
+
+        highscores::draw_name_entry(gamestate, keybindings, screen_width, screen_height);
+        if gamestate.name_entry.is_none() {
+            if button(
+                screen_width / 2.0 - 0.31 * screen_width,
+                screen_height * 0.85,
+                0.3 * screen_width,
+                0.05 * screen_height,
+                gamestate.locale.tr("menu.start.highscores"),
+                40.0,
+                BLUE,
+            ) {
+                gamestate.menu.push(String::from("HighScores"));
+                gamestate.audio.play(SoundEffect::UiBlip);
+            }
+            if button(
+                screen_width / 2.0 + 0.01 * screen_width,
+                screen_height * 0.85,
+                0.3 * screen_width,
+                0.05 * screen_height,
+                gamestate.locale.tr("menu.start.achievements"),
+                40.0,
+                BLUE,
+            ) {
+                gamestate.menu.push(String::from("Achievements"));
+                gamestate.audio.play(SoundEffect::UiBlip);
+            }
+        }
     }
     return String::from("");
 }
@@ -283,30 +537,31 @@ pub fn draw_simulation(gamestate: &Gamestate) {
     if gamestate.debug {
         positions = [50.0, 100.0, 150.0, 200.0, 250.0, 25.0];
         draw_text(
-            &(format!("Loop:{}", gamestate.loop_number)),
+            &gamestate
+                .locale
+                .format("hud.loop", &[("loop", gamestate.loop_number.to_string())]),
             10.0,
             positions[3],
             48.0,
             RED,
         );
         draw_text(
-            &(format!("Time:{}", Local::now().format("%H:%M:%S"))),
+            &gamestate.locale.format(
+                "hud.time",
+                &[("time", Local::now().format("%H:%M:%S").to_string())],
+            ),
             10.0,
             positions[4],
             48.0,
             YELLOW,
         );
+        let speed = gamestate.locale.format(
+            "hud.speed_factor",
+            &[("speed", gamestate.simulation_speed.to_string())],
+        );
         draw_text(
-            &(format!("Speed factor:{}x", gamestate.simulation_speed)),
-            (screen_width()
-                - measure_text(
-                    &format!("Speed factor:{}x", gamestate.simulation_speed),
-                    None,
-                    36,
-                    screen_dpi_scale(),
-                )
-                .width)
-                / 2.0,
+            &speed,
+            (screen_width() - measure_text(&speed, None, 36, screen_dpi_scale()).width) / 2.0,
             positions[5],
             36.0,
             GOLD,
@@ -314,39 +569,51 @@ pub fn draw_simulation(gamestate: &Gamestate) {
     }
     // Always draw
     draw_text(
-        &(format!("FPS:{}", gamestate.fps)),
+        &gamestate
+            .locale
+            .format("hud.fps", &[("fps", gamestate.fps.to_string())]),
         10.0,
         positions[0],
         48.0,
         GREEN,
     );
     draw_text(
-        &(format!("Astéroides:{}", gamestate.number_of_asteroids)),
+        &gamestate.locale.format(
+            "hud.asteroids",
+            &[("count", gamestate.number_of_asteroids.to_string())],
+        ),
         10.0,
         positions[1],
         48.0,
         BLUE,
     );
     draw_text(
-        &(format!("Missiles:{}", gamestate.missiles.len())),
+        &gamestate.locale.format(
+            "hud.missiles",
+            &[("count", gamestate.missiles.len().to_string())],
+        ),
         10.0,
         positions[2],
         48.0,
         BLUE,
     );
+    let score = gamestate.locale.format(
+        "hud.score",
+        &[
+            ("score", gamestate.score[0].to_string()),
+            ("max", gamestate.score[1].to_string()),
+        ],
+    );
     draw_text(
-        &(format!("Score:{}/{}", gamestate.score[0], gamestate.score[1])),
-        screen_width()
-            - measure_text(
-                &(format!("Score:{}/{}", gamestate.score[0], gamestate.score[1])),
-                None,
-                48,
-                1.0,
-            )
-            .width
-            - 10.0,
+        &score,
+        screen_width() - measure_text(&score, None, 48, 1.0).width - 10.0,
         positions[5],
         48.0,
         WHITE,
     );
+
+    if gamestate.get_last_menu_item() != "Start" {
+        hud::draw_hud(gamestate);
+    }
+    gamestate.minimap.draw(gamestate);
 }
@@ -0,0 +1,183 @@
+use crate::gamestate::Gamestate;
+use ast_lib::CosmicEntity;
+use macroquad::prelude::{
+    Color, GRAY, MAGENTA, RED, Vec2, WHITE, draw_circle, draw_rectangle,
+    draw_rectangle_lines, screen_height, screen_width, vec2,
+};
+use serde::{Deserialize, Serialize};
+
+/// Screen corner the minimap is anchored to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// Key of the name in the localization catalogs
+    pub fn key(&self) -> &'static str {
+        match self {
+            Corner::TopLeft => "corner.top_left",
+            Corner::TopRight => "corner.top_right",
+            Corner::BottomLeft => "corner.bottom_left",
+            Corner::BottomRight => "corner.bottom_right",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Corner::TopLeft => Corner::TopRight,
+            Corner::TopRight => Corner::BottomRight,
+            Corner::BottomRight => Corner::BottomLeft,
+            Corner::BottomLeft => Corner::TopLeft,
+        }
+    }
+}
+
+/// Radar overlay showing the whole world in a corner of the screen
+pub struct Minimap {
+    visible: bool,
+    corner: Corner,
+    scale: f32,
+}
+
+#[allow(unused)]
+impl Minimap {
+    /// Available scales, as a fraction of the screen height
+    pub const SCALES: [f32; 3] = [0.15, 0.2, 0.3];
+    const MARGIN: f32 = 10.0;
+
+    pub fn new() -> Self {
+        Minimap {
+            visible: true,
+            corner: Corner::BottomRight,
+            scale: Self::SCALES[1],
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn set_corner(&mut self, corner: Corner) {
+        self.corner = corner;
+    }
+
+    /// Use the closest of the `SCALES`
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = Self::SCALES
+            .into_iter()
+            .min_by(|a, b| (a - scale).abs().total_cmp(&(b - scale).abs()))
+            .unwrap_or(Self::SCALES[1]);
+    }
+
+    pub fn cycle_corner(&mut self) {
+        self.corner = self.corner.next();
+    }
+
+    /// Go to the next scale in `SCALES`, wrapping around
+    pub fn cycle_scale(&mut self) {
+        let index = Self::SCALES
+            .iter()
+            .position(|s| *s == self.scale)
+            .unwrap_or(0);
+        self.scale = Self::SCALES[(index + 1) % Self::SCALES.len()];
+    }
+
+    /// Position and size of the minimap on the screen for a given world
+    fn frame(&self, world: Vec2) -> (Vec2, Vec2) {
+        let height = screen_height() * self.scale;
+        let size = vec2(height * world.x / world.y, height);
+        let x = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => Self::MARGIN,
+            Corner::TopRight | Corner::BottomRight => screen_width() - size.x - Self::MARGIN,
+        };
+        let y = match self.corner {
+            Corner::TopLeft | Corner::TopRight => Self::MARGIN,
+            Corner::BottomLeft | Corner::BottomRight => screen_height() - size.y - Self::MARGIN,
+        };
+        (vec2(x, y), size)
+    }
+
+    pub fn draw(&self, gamestate: &Gamestate) {
+        if !self.visible {
+            return;
+        }
+
+        let world = gamestate.camera.get_world_size();
+        let (origin, size) = self.frame(world);
+        let ratio = size.x / world.x;
+        let to_map = |position: Vec2| origin + position * ratio;
+
+        draw_rectangle(origin.x, origin.y, size.x, size.y, Color::from_rgba(0, 0, 0, 160));
+        draw_rectangle_lines(origin.x, origin.y, size.x, size.y, 2.0, GRAY);
+
+        // Part of the world currently on screen
+        let view_origin = to_map(gamestate.camera.screen_to_world(Vec2::ZERO));
+        let view_end = to_map(
+            gamestate
+                .camera
+                .screen_to_world(vec2(screen_width(), screen_height())),
+        );
+        let view_origin = view_origin.clamp(origin, origin + size);
+        let view_size = view_end.clamp(origin, origin + size) - view_origin;
+        if view_size.x < size.x || view_size.y < size.y {
+            draw_rectangle_lines(
+                view_origin.x,
+                view_origin.y,
+                view_size.x,
+                view_size.y,
+                1.0,
+                Color::from_rgba(255, 255, 255, 96),
+            );
+        }
+
+        // Asteroids, bigger dots for bigger asteroids
+        for asteroid in &gamestate.asteroids {
+            let position = to_map(asteroid.get_position());
+            draw_circle(
+                position.x,
+                position.y,
+                (asteroid.get_size() * ratio).max(1.5),
+                WHITE,
+            );
+        }
+
+        for missile in &gamestate.missiles {
+            let position = to_map(missile.get_position());
+            draw_circle(
+                position.x,
+                position.y,
+                1.5,
+                if missile.is_homing() { MAGENTA } else { RED },
+            );
+        }
+
+        for player in &gamestate.players {
+            if player.is_alive() {
+                let position = to_map(player.spaceship.get_position());
+                draw_circle(position.x, position.y, 3.0, player.color);
+            }
+        }
+    }
+
+    // === Getters ===
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn get_corner(&self) -> Corner {
+        self.corner
+    }
+
+    pub fn get_scale(&self) -> f32 {
+        self.scale
+    }
+}
@@ -2,9 +2,10 @@ use ast_lib::generate_uid;
 use mac_der::Entity;
 use ast_lib::CosmicEntity;
 use macroquad::prelude::{
-    draw_circle, draw_text, measure_text, screen_dpi_scale, screen_height, screen_width, vec2, Vec2, GOLD, GRAY, MAGENTA,
+    draw_text, measure_text, screen_dpi_scale, screen_height, screen_width, vec2, Vec2, GOLD, GRAY, MAGENTA,
     RED,
 };
+use crate::camera::Camera;
 use std::f32::consts::PI;
 
 #[derive(PartialEq, Clone, Entity)]
@@ -19,6 +20,10 @@ pub struct Missile {
     acceleration: f32,
     homing: bool,
     target: Vec2,
+    /// Homing missiles launched together share a salvo
+    salvo: Option<u64>,
+    /// Index of the player who fired it
+    owner: usize,
 }
 
 #[allow(unused)]
@@ -36,6 +41,8 @@ impl Missile {
             acceleration: 200.0,
             homing,
             target,
+            salvo: None,
+            owner: 0,
         }
     }
 
@@ -67,6 +74,22 @@ impl Missile {
         self.homing
     }
 
+    pub fn get_salvo(&self) -> Option<u64> {
+        self.salvo
+    }
+
+    pub fn set_salvo(&mut self, salvo: u64) {
+        self.salvo = Some(salvo);
+    }
+
+    pub fn get_owner(&self) -> usize {
+        self.owner
+    }
+
+    pub fn set_owner(&mut self, owner: usize) {
+        self.owner = owner;
+    }
+
     pub fn get_target(&self) -> Vec2 {
         self.target
     }
@@ -98,8 +121,13 @@ impl Missile {
         }
     }
 
-    /// Update missile state
-    pub fn update(&mut self, potential_targets: &Vec<crate::asteroid::Asteroid>, delta_time: f64) {
+    /// Update missile state, homing missiles chase the nearest of `potential_targets`
+    pub fn update<T: CosmicEntity>(
+        &mut self,
+        potential_targets: &[T],
+        delta_time: f64,
+        bounds: &Vec2,
+    ) {
         if self.homing {
             let nearest_target = self.find_nearest(potential_targets);
             self.speed += self.acceleration * delta_time as f32;
@@ -123,24 +151,24 @@ impl Missile {
             vec2(self.rotation.cos(), -self.rotation.sin()) * (self.speed) * delta_time as f32;
 
         if self.position.x < 0.0
-            || self.position.x > screen_width()
+            || self.position.x > bounds.x
             || self.position.y < 0.0
-            || self.position.y > screen_height()
+            || self.position.y > bounds.y
         {
             self.size = 0.0;
         }
     }
 
     /// Draw the missile
-    pub fn draw(&self, debug: bool) {
+    pub fn draw(&self, camera: &Camera, debug: bool) {
         if self.homing {
             if self.lifetime > 0.0 {
-                draw_circle(self.position.x, self.position.y, self.size * 1.25, MAGENTA);
+                camera.draw_circle(self.position, self.size * 1.25, MAGENTA);
             } else {
-                draw_circle(self.position.x, self.position.y, self.size, GRAY);
+                camera.draw_circle(self.position, self.size, GRAY);
             }
         } else {
-            draw_circle(self.position.x, self.position.y, self.size, RED);
+            camera.draw_circle(self.position, self.size, RED);
         }
 
         if debug {
@@ -170,12 +198,13 @@ impl Missile {
 
             let text_size = *debug_text_sizes.iter().max().unwrap() as f32;
 
-            let x_offset = if screen_width() - position.x >= text_size + 25.0 {
+            let screen_position = camera.world_to_screen(position);
+            let x_offset = if screen_width() - screen_position.x >= text_size + 25.0 {
                 5.0
             } else {
                 -text_size + 5.0
             };
-            let y_offset = if screen_height() - position.y >= font_size * texts.len() as f32 {
+            let y_offset = if screen_height() - screen_position.y >= font_size * texts.len() as f32 {
                 10.0
             } else {
                 -font_size * texts.len() as f32
@@ -184,8 +213,8 @@ impl Missile {
             for (index, field) in &mut texts.iter_mut().enumerate() {
                 draw_text(
                     field,
-                    position.x + x_offset,
-                    (position.y + y_offset) + index as f32 * 20.0,
+                    screen_position.x + x_offset,
+                    (screen_position.y + y_offset) + index as f32 * 20.0,
                     font_size,
                     GOLD,
                 );
@@ -0,0 +1,206 @@
+/// Looping layers of the music, all played together and mixed by volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stem {
+    /// Calm layer of the menu track
+    MenuPad,
+    /// Base layer of the gameplay track, always there during a run
+    Pad,
+    /// Comes in when a few asteroids get close
+    Bass,
+    /// Comes in when the ship is surrounded
+    Drums,
+    /// Comes in when the shield is low
+    Alarm,
+}
+
+impl Stem {
+    pub const ALL: [Stem; 5] = [
+        Stem::MenuPad,
+        Stem::Pad,
+        Stem::Bass,
+        Stem::Drums,
+        Stem::Alarm,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            Stem::MenuPad => 0,
+            Stem::Pad => 1,
+            Stem::Bass => 2,
+            Stem::Drums => 3,
+            Stem::Alarm => 4,
+        }
+    }
+}
+
+/// What the music reacts to, computed from the gamestate every frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Intensity {
+    /// Asteroids close to the spaceship
+    pub nearby_asteroids: u32,
+    /// Shield of the spaceship, from 0 to 100
+    pub shield: f32,
+    /// The spaceship is destroyed and the game runs in slow motion
+    pub dying: bool,
+    /// A menu covers the game (pause, start or game over screen)
+    pub in_menu: bool,
+}
+
+/// Fades the stems in and out following the intensity of the game
+pub struct MusicMixer {
+    gains: [f32; 5],
+    fade_speed: f32,
+}
+
+#[allow(unused)]
+impl MusicMixer {
+    /// Distance under which an asteroid counts as nearby
+    pub const NEARBY_RADIUS: f32 = 400.0;
+
+    pub fn new() -> Self {
+        MusicMixer {
+            gains: [0.0; 5],
+            fade_speed: 1.5,
+        }
+    }
+
+    /// Volume each stem should reach for a given intensity
+    pub fn target(intensity: &Intensity) -> [f32; 5] {
+        let mut target = [0.0; 5];
+        let near = intensity.nearby_asteroids as f32;
+
+        target[Stem::Pad.index()] = 1.0;
+        target[Stem::Bass.index()] = (near / 3.0).clamp(0.0, 1.0);
+        target[Stem::Drums.index()] = ((near - 3.0) / 5.0).clamp(0.0, 1.0);
+        target[Stem::Alarm.index()] = ((33.0 - intensity.shield) / 33.0).clamp(0.0, 1.0);
+
+        // Death sequence, only a quiet pad stays while the field breaks apart
+        if intensity.dying {
+            target = [0.0; 5];
+            target[Stem::Pad.index()] = 0.4;
+        }
+
+        // Crossfade to the menu track
+        if intensity.in_menu {
+            target = [0.0; 5];
+            target[Stem::MenuPad.index()] = 1.0;
+        }
+
+        target
+    }
+
+    /// Move every gain toward its target, `delta_time` is real time so fades
+    /// keep going while the simulation is paused
+    pub fn update(&mut self, intensity: &Intensity, delta_time: f32) {
+        let target = Self::target(intensity);
+        let step = (self.fade_speed * delta_time).min(1.0);
+        for (gain, target) in self.gains.iter_mut().zip(target) {
+            *gain += (target - *gain) * step;
+        }
+    }
+
+    pub fn get_gain(&self, stem: Stem) -> f32 {
+        self.gains[stem.index()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALM: Intensity = Intensity {
+        nearby_asteroids: 0,
+        shield: 100.0,
+        dying: false,
+        in_menu: false,
+    };
+
+    fn gain(target: &[f32; 5], stem: Stem) -> f32 {
+        target[stem.index()]
+    }
+
+    #[test]
+    fn nearby_asteroids_bring_in_bass_then_drums() {
+        let calm = MusicMixer::target(&CALM);
+        assert_eq!(calm, [0.0, 1.0, 0.0, 0.0, 0.0]);
+
+        let few = MusicMixer::target(&Intensity {
+            nearby_asteroids: 3,
+            ..CALM
+        });
+        assert_eq!(gain(&few, Stem::Bass), 1.0);
+        assert_eq!(gain(&few, Stem::Drums), 0.0);
+
+        let crowded = MusicMixer::target(&Intensity {
+            nearby_asteroids: 20,
+            ..CALM
+        });
+        assert_eq!(gain(&crowded, Stem::Bass), 1.0);
+        assert_eq!(gain(&crowded, Stem::Drums), 1.0);
+    }
+
+    #[test]
+    fn low_shield_raises_the_alarm() {
+        for (shield, alarm) in [(100.0, 0.0), (33.0, 0.0), (16.5, 0.5), (0.0, 1.0)] {
+            let target = MusicMixer::target(&Intensity { shield, ..CALM });
+            assert_eq!(gain(&target, Stem::Alarm), alarm, "shield {}", shield);
+        }
+    }
+
+    #[test]
+    fn dying_keeps_a_quiet_pad_and_menus_crossfade() {
+        let tense = Intensity {
+            nearby_asteroids: 20,
+            shield: 0.0,
+            ..CALM
+        };
+        let dying = MusicMixer::target(&Intensity {
+            dying: true,
+            ..tense
+        });
+        assert_eq!(dying, [0.0, 0.4, 0.0, 0.0, 0.0]);
+
+        // The menu wins over everything happening behind it
+        let menu = MusicMixer::target(&Intensity {
+            dying: true,
+            in_menu: true,
+            ..tense
+        });
+        assert_eq!(menu, [1.0, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn gains_converge_without_overshooting() {
+        let mut mixer = MusicMixer::new();
+        let intensity = Intensity {
+            nearby_asteroids: 5,
+            shield: 10.0,
+            ..CALM
+        };
+        let target = MusicMixer::target(&intensity);
+
+        for _ in 0..600 {
+            mixer.update(&intensity, 1.0 / 60.0);
+            for stem in Stem::ALL {
+                assert!(mixer.get_gain(stem) <= gain(&target, stem) + 1e-6);
+            }
+        }
+        for stem in Stem::ALL {
+            assert!((mixer.get_gain(stem) - gain(&target, stem)).abs() < 1e-3);
+        }
+
+        // A long frame jumps to the target instead of going past it, then fading out works the same way
+        mixer.update(&CALM, 10.0);
+        assert_eq!(mixer.gains, MusicMixer::target(&CALM));
+        let menu = Intensity {
+            in_menu: true,
+            ..CALM
+        };
+        for _ in 0..600 {
+            mixer.update(&menu, 1.0 / 60.0);
+            assert!(mixer.get_gain(Stem::Pad) >= 0.0);
+            assert!(mixer.get_gain(Stem::MenuPad) <= 1.0);
+        }
+        assert!(mixer.get_gain(Stem::Pad) < 1e-3);
+    }
+}
@@ -0,0 +1,551 @@
+use crate::{
+    gamestate::Gamestate,
+    i18n::Localization,
+    key_bindings::{InputBuffer, TickInput},
+    versus::Versus,
+};
+use ast_lib::{current_time, seed_simulation_rng};
+use macroquad::prelude::{Color, GREEN, RED, YELLOW, draw_text, screen_height, vec2};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+/// Ticks between the sampling of an input and the tick using it, hides the latency of the network
+pub const INPUT_DELAY: u64 = 4;
+/// Ticks between two state hashes sent to the peer
+pub const HASH_INTERVAL: u64 = 60;
+/// Seconds without news of the peer before the game is considered lost
+pub const TIMEOUT: f64 = 5.0;
+/// Seconds between two requests of the joining peer
+const HELLO_INTERVAL: f64 = 0.25;
+
+/// What goes through the socket, one JSON datagram each
+#[derive(Debug, Serialize, Deserialize)]
+enum Packet {
+    /// The joining peer asks for a game
+    Hello,
+    /// The host answers with what both games must share
+    Welcome {
+        seed: u64,
+        world: (f32, f32),
+        versus: bool,
+        lives: u8,
+        waves: u32,
+    },
+    /// Inputs of the sender from the tick `first`, every tick the receiver did not acknowledge yet
+    Inputs {
+        first: u64,
+        inputs: Vec<TickInput>,
+        /// First tick of the receiver's inputs the sender is still missing
+        ack: u64,
+        /// Latest state hash of the sender, with its tick
+        hash: Option<(u64, u64)>,
+    },
+}
+
+/// Setup of the local game replaced by the network game, given back when it ends
+#[derive(Debug)]
+struct LocalSetup {
+    versus: bool,
+    lives: u8,
+    waves: u32,
+    players: usize,
+}
+
+/// Network game of two peers in deterministic lockstep.
+///
+/// Only the inputs are exchanged: a tick runs once both inputs are known, on both
+/// games, from the same seed and world, so the simulations stay identical. The host
+/// is the first player and the joining peer the second one. The debug mode and the time
+/// controls are off for the whole game, they would only change one of the simulations.
+pub struct Session {
+    socket: UdpSocket,
+    peer: Option<SocketAddr>,
+    host: bool,
+    connected: bool,
+    /// Shared setup, picked by the host
+    seed: u64,
+    world: (f32, f32),
+    versus: bool,
+    lives: u8,
+    waves: u32,
+    /// Setup to restore in [`Session::close`], taken when the game starts
+    local_setup: Option<LocalSetup>,
+    /// Next tick to simulate
+    tick: u64,
+    local: BTreeMap<u64, TickInput>,
+    remote: BTreeMap<u64, TickInput>,
+    /// First tick of the local inputs the peer is still missing
+    peer_ack: u64,
+    local_hashes: BTreeMap<u64, u64>,
+    remote_hashes: BTreeMap<u64, u64>,
+    last_hash: Option<(u64, u64)>,
+    /// Latest tick whose hashes were compared
+    last_compared: u64,
+    /// First tick whose hashes differed
+    desync: Option<u64>,
+    /// The last tick had to wait for the peer
+    stalled: bool,
+    last_received: f64,
+    last_hello: f64,
+}
+
+#[allow(unused)]
+impl Session {
+    fn new(socket: UdpSocket, peer: Option<SocketAddr>) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Session {
+            socket,
+            host: peer.is_none(),
+            peer,
+            connected: false,
+            seed: 0,
+            world: (0.0, 0.0),
+            versus: false,
+            lives: Gamestate::STARTING_LIVES,
+            waves: 1,
+            local_setup: None,
+            tick: 0,
+            local: BTreeMap::new(),
+            remote: BTreeMap::new(),
+            peer_ack: 0,
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            last_hash: None,
+            last_compared: 0,
+            desync: None,
+            stalled: false,
+            last_received: current_time(),
+            // The first request goes right away
+            last_hello: f64::NEG_INFINITY,
+        })
+    }
+
+    /// Wait for a peer on `port`
+    pub fn host(port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        ast_lib::log!("[INFO] Waiting for a peer on port {}", port);
+        Self::new(socket, None)
+    }
+
+    /// Join the host at `address`, like `127.0.0.1:7777`
+    pub fn join(address: &str) -> io::Result<Self> {
+        let peer = address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "the address resolves to nothing")
+        })?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        ast_lib::log!("[INFO] Joining {}", peer);
+        Self::new(socket, Some(peer))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// No news of the peer for `TIMEOUT` seconds
+    pub fn is_lost(&self) -> bool {
+        self.connected && current_time() - self.last_received > TIMEOUT
+    }
+
+    pub fn get_desync(&self) -> Option<u64> {
+        self.desync
+    }
+
+    /// Player driven by this game
+    pub fn get_local_player(&self) -> usize {
+        if self.host { 0 } else { 1 }
+    }
+
+    /// Exchange the packets, call it once per frame before the ticks.
+    /// Returns true on the frame the game starts, the gamestate is reset by then
+    pub fn poll(&mut self, gamestate: &mut Gamestate) -> bool {
+        let mut started = false;
+        let mut buffer = [0u8; 65536];
+        loop {
+            let (length, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("[WARN] Netplay receive failed: {}", e);
+                    break;
+                }
+            };
+            let packet: Packet = match serde_json::from_slice(&buffer[..length]) {
+                Ok(packet) => packet,
+                Err(e) => {
+                    eprintln!("[WARN] Invalid netplay packet from {}: {}", from, e);
+                    continue;
+                }
+            };
+            if self.peer.is_some_and(|peer| peer != from) {
+                continue;
+            }
+            match packet {
+                Packet::Hello if self.host => {
+                    if !self.connected {
+                        self.peer = Some(from);
+                        self.seed = ::rand::random();
+                        let world = gamestate.camera.get_world_size();
+                        self.world = (world.x, world.y);
+                        self.versus = gamestate.versus.is_some();
+                        self.lives = gamestate.starting_lives;
+                        self.waves = gamestate.max_waves;
+                        self.start(gamestate);
+                        started = true;
+                    }
+                    // Answered every time, the previous answer may be lost
+                    self.send(&Packet::Welcome {
+                        seed: self.seed,
+                        world: self.world,
+                        versus: self.versus,
+                        lives: self.lives,
+                        waves: self.waves,
+                    });
+                }
+                Packet::Welcome {
+                    seed,
+                    world,
+                    versus,
+                    lives,
+                    waves,
+                } if !self.host => {
+                    if !self.connected {
+                        self.seed = seed;
+                        self.world = world;
+                        self.versus = versus;
+                        self.lives = lives;
+                        self.waves = waves;
+                        self.start(gamestate);
+                        started = true;
+                    }
+                }
+                Packet::Inputs {
+                    first,
+                    inputs,
+                    ack,
+                    hash,
+                } if self.connected => {
+                    for (offset, input) in inputs.into_iter().enumerate() {
+                        let tick = first + offset as u64;
+                        if tick >= self.tick {
+                            self.remote.entry(tick).or_insert(input);
+                        }
+                    }
+                    self.peer_ack = self.peer_ack.max(ack);
+                    if let Some((tick, hash)) = hash
+                        && tick > self.last_compared
+                    {
+                        self.remote_hashes.insert(tick, hash);
+                        self.compare_hashes();
+                    }
+                }
+                _ => continue,
+            }
+            self.last_received = current_time();
+        }
+
+        if self.connected {
+            self.send_inputs();
+        } else if !self.host && current_time() - self.last_hello >= HELLO_INTERVAL {
+            self.last_hello = current_time();
+            self.send(&Packet::Hello);
+        }
+        started
+    }
+
+    /// Inputs of every player for the next tick, `None` while the peer's input is missing.
+    /// The local input is scheduled `INPUT_DELAY` ticks ahead
+    pub fn advance(&mut self, local: &mut InputBuffer) -> Option<Vec<TickInput>> {
+        if !self.connected {
+            return None;
+        }
+        self.local
+            .entry(self.tick + INPUT_DELAY)
+            .or_insert_with(|| local.next_tick());
+
+        let Some(remote) = self.remote.remove(&self.tick) else {
+            self.stalled = true;
+            return None;
+        };
+        self.stalled = false;
+        let own = self.local[&self.tick].clone();
+        self.tick += 1;
+
+        // The inputs the peer acknowledged are not sent anymore
+        let keep_from = self.peer_ack.min(self.tick);
+        self.local.retain(|tick, _| *tick >= keep_from);
+
+        Some(if self.host {
+            vec![own, remote]
+        } else {
+            vec![remote, own]
+        })
+    }
+
+    /// Hash the state after a tick every `HASH_INTERVAL` ticks and compare it with the peer's
+    pub fn check_state(&mut self, gamestate: &Gamestate) {
+        if self.tick % HASH_INTERVAL != 0 {
+            return;
+        }
+        let hash = gamestate.get_state_hash();
+        self.local_hashes.insert(self.tick, hash);
+        self.last_hash = Some((self.tick, hash));
+        self.compare_hashes();
+    }
+
+    /// Same seed, same world and a fresh game on both peers, with the first ticks empty
+    fn start(&mut self, gamestate: &mut Gamestate) {
+        self.connected = true;
+        self.last_received = current_time();
+        self.local_setup = Some(LocalSetup {
+            versus: gamestate.versus.is_some(),
+            lives: gamestate.starting_lives,
+            waves: gamestate.max_waves,
+            players: gamestate.players.len(),
+        });
+        seed_simulation_rng(self.seed);
+        gamestate.debug = false;
+        gamestate.lockstep = true;
+        gamestate
+            .camera
+            .lock_world_size(Some(vec2(self.world.0, self.world.1)));
+        gamestate.versus = self.versus.then(Versus::new);
+        gamestate.starting_lives = self.lives;
+        gamestate.max_waves = self.waves;
+        gamestate.set_player_count(2);
+        gamestate.loop_number = 0;
+        gamestate.discard_cooldown = 0.0;
+        gamestate.accumulator = 0.0;
+        gamestate.menu = vec![String::from("Start")];
+        gamestate.reset();
+
+        for tick in 0..INPUT_DELAY {
+            self.local.insert(tick, TickInput::default());
+            self.remote.insert(tick, TickInput::default());
+        }
+        ast_lib::log!(
+            "[INFO] Netplay game started with {:?}, seed {}",
+            self.peer,
+            self.seed
+        );
+    }
+
+    /// End the network game and give the local setup back, the start screen waits for a
+    /// new local run
+    pub fn close(self, gamestate: &mut Gamestate) {
+        let Some(setup) = self.local_setup else {
+            return;
+        };
+        seed_simulation_rng(::rand::random());
+        gamestate.lockstep = false;
+        gamestate.camera.lock_world_size(None);
+        gamestate.versus = setup.versus.then(Versus::new);
+        gamestate.starting_lives = setup.lives;
+        gamestate.max_waves = setup.waves;
+        gamestate.set_player_count(setup.players);
+        gamestate.accumulator = 0.0;
+        if gamestate.menu.first().is_none_or(|menu| menu != "Start") {
+            gamestate.menu = vec![String::from("Start")];
+        }
+        ast_lib::log!("[INFO] Netplay game closed, back to the local game");
+    }
+
+    /// First tick of the peer's inputs still missing
+    fn get_ack(&self) -> u64 {
+        let mut tick = self.tick;
+        while self.remote.contains_key(&tick) {
+            tick += 1;
+        }
+        tick
+    }
+
+    fn send_inputs(&mut self) {
+        let first = self
+            .peer_ack
+            .max(self.local.keys().next().copied().unwrap_or(0));
+        let inputs: Vec<TickInput> = self.local.range(first..).map(|(_, i)| i.clone()).collect();
+        self.send(&Packet::Inputs {
+            first,
+            inputs,
+            ack: self.get_ack(),
+            hash: self.last_hash,
+        });
+    }
+
+    fn send(&self, packet: &Packet) {
+        let Some(peer) = self.peer else {
+            return;
+        };
+        match serde_json::to_vec(packet) {
+            Ok(bytes) => {
+                if let Err(e) = self.socket.send_to(&bytes, peer)
+                    && e.kind() != io::ErrorKind::WouldBlock
+                {
+                    eprintln!("[WARN] Netplay send failed: {}", e);
+                }
+            }
+            Err(e) => eprintln!("[ERROR] Couldn't encode a netplay packet: {}", e),
+        }
+    }
+
+    fn compare_hashes(&mut self) {
+        let common: Vec<u64> = self
+            .local_hashes
+            .keys()
+            .filter(|tick| self.remote_hashes.contains_key(tick))
+            .copied()
+            .collect();
+        for tick in common {
+            let local = self.local_hashes.remove(&tick);
+            let remote = self.remote_hashes.remove(&tick);
+            self.last_compared = self.last_compared.max(tick);
+            if local != remote && self.desync.is_none() {
+                eprintln!("[ERROR] Netplay desync detected at tick {}", tick);
+                self.desync = Some(tick);
+            }
+        }
+    }
+
+    /// State of the connection in the bottom left corner
+    pub fn draw_status(&self, locale: &Localization) {
+        let (text, color): (String, Color) = if let Some(tick) = self.desync {
+            (
+                locale.format("netplay.desync", &[("tick", tick.to_string())]),
+                RED,
+            )
+        } else if self.is_lost() {
+            (locale.tr("netplay.lost").to_string(), RED)
+        } else if !self.connected {
+            (locale.tr("netplay.connecting").to_string(), YELLOW)
+        } else if self.stalled {
+            (locale.tr("netplay.waiting").to_string(), YELLOW)
+        } else {
+            (locale.tr("netplay.connected").to_string(), GREEN)
+        };
+        let font_size = (screen_height() / 40.0).max(14.0);
+        draw_text(&text, 10.0, screen_height() - 10.0, font_size, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::{InputBackend, InputEvent, ScriptedBackend},
+        key_bindings::default_keybindings,
+    };
+    use ast_lib::HEADLESS_SCREEN;
+    use macroquad::prelude::Vec2;
+    use std::{
+        thread::{sleep, spawn},
+        time::{Duration, Instant},
+    };
+
+    /// Ticks played by both peers, the hashes are compared several times
+    const TICKS_PLAYED: u64 = 4 * HASH_INTERVAL;
+
+    /// Keys of a peer: thrust and turn, fire now and then
+    fn script(turn: &str) -> ScriptedBackend {
+        let mut script = vec![
+            (0, InputEvent::Press("KeyW".to_string())),
+            (0, InputEvent::Press(turn.to_string())),
+            (90, InputEvent::Release("KeyW".to_string())),
+        ];
+        for frame in (10..400).step_by(25) {
+            script.push((frame, InputEvent::Press("KeyQ".to_string())));
+            script.push((frame + 3, InputEvent::Release("KeyQ".to_string())));
+        }
+        ScriptedBackend::new(script)
+    }
+
+    /// Play `TICKS_PLAYED` ticks against the peer, then keep answering it for a while so
+    /// it gets the last inputs and hashes. Returns the final state hash and world size
+    fn play(mut session: Session, gamestate: &mut Gamestate, turn: &str) -> (u64, Vec2) {
+        let keybindings = default_keybindings();
+        let mut backend = script(turn);
+        keybindings.start_backend(&mut backend);
+        let mut buffer = InputBuffer::new();
+
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let mut hash = None;
+        let mut linger = 0;
+        while linger < 200 {
+            assert!(Instant::now() < deadline, "the peers never finished");
+            session.poll(gamestate);
+            backend.poll();
+            buffer.sample(&keybindings);
+            keybindings.clear_events();
+
+            if session.tick < TICKS_PLAYED
+                && let Some(inputs) = session.advance(&mut buffer)
+            {
+                gamestate.tick(&inputs);
+                session.check_state(gamestate);
+            } else {
+                sleep(Duration::from_millis(1));
+            }
+            if session.tick == TICKS_PLAYED && session.last_compared == TICKS_PLAYED {
+                hash.get_or_insert(gamestate.get_state_hash());
+                linger += 1;
+            }
+        }
+        assert_eq!(session.get_desync(), None);
+        let world = gamestate.camera.get_world_size();
+
+        session.close(gamestate);
+        assert!(!gamestate.lockstep);
+        assert!(gamestate.versus.is_none());
+        assert_eq!(gamestate.players.len(), 1);
+        assert_eq!(gamestate.menu, vec![String::from("Start")]);
+        (hash.unwrap(), world)
+    }
+
+    #[test]
+    fn local_menus_and_time_controls_do_not_reach_the_ticks() {
+        let mut gamestate = Gamestate::headless();
+        gamestate.lockstep = true;
+        gamestate.menu.push(String::from("Main"));
+        // Held pause key of the last frame
+        gamestate.simulation_speed = 0.0;
+
+        gamestate.tick(&[TickInput::default()]);
+        assert_eq!(gamestate.simulation_speed, 1.0);
+
+        gamestate.lockstep = false;
+        gamestate.update_simulation_speed();
+        assert_eq!(gamestate.simulation_speed, 0.0);
+    }
+
+    #[test]
+    fn loopback_peers_stay_in_sync() {
+        let host = Session::host(0).unwrap();
+        let port = host.socket.local_addr().unwrap().port();
+        let join = Session::join(&format!("127.0.0.1:{}", port)).unwrap();
+
+        // The host plays in a bigger world, the joining peer takes it for the game only
+        let host = spawn(move || {
+            let mut gamestate = Gamestate::headless();
+            gamestate.camera.cycle_mode(false);
+            gamestate.camera.reset_world();
+            play(host, &mut gamestate, "KeyA")
+        });
+        let join = spawn(move || {
+            let mut gamestate = Gamestate::headless();
+            gamestate.debug = true;
+            let played = play(join, &mut gamestate, "KeyD");
+            assert!(!gamestate.debug);
+            gamestate.reset();
+            (played, gamestate.camera.get_world_size())
+        });
+
+        let (host_hash, host_world) = host.join().unwrap();
+        let ((join_hash, join_world), join_world_after) = join.join().unwrap();
+        assert_eq!(host_hash, join_hash);
+        assert_eq!(host_world, HEADLESS_SCREEN * 2.0);
+        assert_eq!(join_world, host_world);
+        assert_eq!(join_world_after, HEADLESS_SCREEN);
+    }
+}
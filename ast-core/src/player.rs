@@ -0,0 +1,256 @@
+use crate::{
+    gamestate::Gamestate,
+    key_bindings::{Action, InputBuffer, KEYBINDINGS_FILE, KeyBindings},
+    spaceship::Spaceship,
+};
+use macroquad::prelude::{Color, LIME, PINK, SKYBLUE, YELLOW};
+use rdev::Key;
+use std::path::Path;
+
+/// Players sharing the keyboard
+pub const MAX_PLAYERS: usize = 4;
+
+/// Colour of the ship of each player
+pub const PLAYER_COLORS: [Color; MAX_PLAYERS] = [YELLOW, SKYBLUE, LIME, PINK];
+
+/// A ship on the field and what belongs to whoever controls it
+pub struct Player {
+    pub spaceship: Spaceship,
+    pub input_buffer: InputBuffer,
+    pub color: Color,
+    pub score: u128,
+    pub lives: u8,
+}
+
+#[allow(unused)]
+impl Player {
+    pub fn new(index: usize) -> Self {
+        let color = PLAYER_COLORS[index % MAX_PLAYERS];
+        let mut spaceship = Spaceship::new();
+        spaceship.set_color(color);
+        Player {
+            spaceship,
+            input_buffer: InputBuffer::new(),
+            color,
+            score: 0,
+            lives: Gamestate::STARTING_LIVES,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.spaceship.get_life()
+    }
+}
+
+/// File of the bindings of a player, the first one uses the main keybindings file
+pub fn profile_file(index: usize) -> String {
+    match index {
+        0 => KEYBINDINGS_FILE.to_string(),
+        _ => format!("keybindings_p{}.json", index + 1),
+    }
+}
+
+/// Gameplay bindings of the players after the first one, menus stay on the main bindings
+pub fn default_profile(index: usize) -> KeyBindings {
+    let mut kb = KeyBindings::new();
+    let keys = match index {
+        1 => [
+            Key::UpArrow,
+            Key::DownArrow,
+            Key::LeftArrow,
+            Key::RightArrow,
+            Key::Comma,
+            Key::Dot,
+            Key::Slash,
+            Key::PageUp,
+            Key::PageDown,
+        ],
+        2 => [
+            Key::Kp8,
+            Key::Kp5,
+            Key::Kp4,
+            Key::Kp6,
+            Key::KpDelete,
+            Key::Kp0,
+            Key::KpReturn,
+            Key::KpPlus,
+            Key::KpMinus,
+        ],
+        _ => [
+            Key::KeyT,
+            Key::KeyG,
+            Key::KeyF,
+            Key::KeyH,
+            Key::KeyV,
+            Key::KeyR,
+            Key::KeyY,
+            Key::KeyU,
+            Key::KeyJ,
+        ],
+    };
+    let actions = [
+        Action::SpeedUp,
+        Action::SpeedDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Stop,
+        Action::Fire,
+        Action::FireHoming,
+        Action::IncreaseMissileCapacity,
+        Action::DecreaseMissileCapacity,
+    ];
+    for (action, key) in actions.into_iter().zip(keys) {
+        kb.bind_key(action, key);
+    }
+    kb
+}
+
+/// Bindings of an extra player, reading the input of the main bindings
+pub fn load_profile(index: usize, main: &KeyBindings) -> KeyBindings {
+    let path = profile_file(index);
+    let defaults = default_profile(index);
+    let profile = match KeyBindings::load_with_defaults(&path, &defaults) {
+        Ok((profile, report)) => {
+            for invalid in &report.invalid {
+                eprintln!("[WARN] {}: {}", path, invalid);
+            }
+            profile
+        }
+        Err(e) => {
+            if Path::new(&path).exists() {
+                eprintln!("[ERROR] Couldn't load {:?}: {}", path, e);
+            } else if let Err(e) = defaults.save(&path) {
+                eprintln!("[ERROR] Couldn't write {:?}: {}", path, e);
+            }
+            defaults
+        }
+    };
+    profile.with_input_of(main)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asteroid::Asteroid, key_bindings::default_keybindings, missile::Missile};
+    use ast_lib::CosmicEntity;
+    use macroquad::prelude::{Vec2, vec2};
+
+    fn coop(players: usize, lives: u8) -> Gamestate {
+        let mut gamestate = Gamestate::headless();
+        gamestate.starting_lives = lives;
+        gamestate.set_player_count(players);
+        gamestate.reset();
+        gamestate.asteroids.clear();
+        gamestate
+    }
+
+    /// A still asteroid on `position`
+    fn asteroid_at(position: Vec2) -> Asteroid {
+        Asteroid::new(
+            Some(position),
+            Some(0.0),
+            Some(Asteroid::SCALE * 3.0),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Put the ship of `index` alone under an asteroid with its shield almost gone
+    fn crash(gamestate: &mut Gamestate, index: usize) {
+        let position = gamestate.players[index].spaceship.get_position();
+        let spaceship = &mut gamestate.players[index].spaceship;
+        spaceship.set_invulnerability(0.0);
+        spaceship.modify_shield(1.0 - spaceship.get_shield());
+        gamestate.asteroids = vec![asteroid_at(position)];
+        gamestate.resolve_collisions();
+    }
+
+    #[test]
+    fn every_player_gets_a_ship_a_colour_and_the_lives() {
+        let gamestate = coop(3, 2);
+        assert_eq!(gamestate.players.len(), 3);
+        for (index, player) in gamestate.players.iter().enumerate() {
+            assert_eq!(player.color, PLAYER_COLORS[index]);
+            assert_eq!(player.spaceship.get_color(), PLAYER_COLORS[index]);
+            assert_eq!(player.lives, 2);
+            assert_eq!(player.score, 0);
+        }
+        let spawns: Vec<Vec2> = gamestate
+            .players
+            .iter()
+            .map(|player| player.spaceship.get_position())
+            .collect();
+        assert!(spawns[0] != spawns[1] && spawns[1] != spawns[2]);
+
+        assert_eq!(coop(0, 1).players.len(), 1);
+        assert_eq!(coop(MAX_PLAYERS + 3, 1).players.len(), MAX_PLAYERS);
+        assert_eq!(coop(2, 0).players[1].lives, 1);
+    }
+
+    #[test]
+    fn each_player_loses_their_own_lives() {
+        let mut gamestate = coop(2, 2);
+        gamestate.players[0].spaceship.set_position(Vec2::ZERO);
+
+        crash(&mut gamestate, 1);
+        assert_eq!(gamestate.players[1].lives, 1);
+        assert!(gamestate.players[1].is_alive());
+        // Back in the middle with a full shield
+        let world = gamestate.camera.get_world_size();
+        assert_eq!(gamestate.players[1].spaceship.get_position(), world / 2.0);
+        assert_eq!(
+            gamestate.players[1].spaceship.get_shield(),
+            Spaceship::MAX_SHIELD
+        );
+
+        crash(&mut gamestate, 1);
+        assert_eq!(gamestate.players[1].lives, 0);
+        assert!(!gamestate.players[1].is_alive());
+
+        // The run goes on while a ship is left
+        assert_eq!(gamestate.players[0].lives, 2);
+        assert!(gamestate.is_any_alive());
+        assert_eq!(gamestate.stats.lives_lost, 2);
+    }
+
+    #[test]
+    fn kills_score_for_the_owner_of_the_missile() {
+        let mut gamestate = coop(2, 1);
+        let position = vec2(100.0, 100.0);
+        gamestate.asteroids = vec![asteroid_at(position)];
+        let mut missile = Missile::new(position, 0.0, 0.0, false, position);
+        missile.set_owner(1);
+        gamestate.missiles = vec![missile];
+
+        gamestate.resolve_collisions();
+        assert!(gamestate.players[1].score > 0);
+        assert_eq!(gamestate.players[0].score, 0);
+        // The team score counts every kill
+        assert_eq!(gamestate.score[0], gamestate.players[1].score);
+    }
+
+    #[test]
+    fn profiles_share_no_gameplay_key() {
+        let main = default_keybindings();
+        let mut keyboards = vec![main];
+        keyboards.extend((1..MAX_PLAYERS).map(default_profile));
+
+        let mut seen = Vec::new();
+        for keyboard in &keyboards {
+            for action in InputBuffer::GAMEPLAY {
+                for combo in keyboard.get_combos(action) {
+                    assert!(!seen.contains(&combo.input), "{:?} is shared", combo.input);
+                }
+            }
+            seen.extend(
+                InputBuffer::GAMEPLAY
+                    .into_iter()
+                    .flat_map(|action| keyboard.get_combos(action))
+                    .map(|combo| combo.input.clone()),
+            );
+        }
+    }
+}
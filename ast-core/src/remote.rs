@@ -0,0 +1,459 @@
+use crate::{
+    asteroid::Asteroid,
+    gamestate::Gamestate,
+    key_bindings::{Action, TickInput},
+    player::MAX_PLAYERS,
+    spectator::Snapshot,
+};
+use ast_lib::Change;
+use macroquad::prelude::vec2;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    io::{BufRead, Write, stdin, stdout},
+    sync::mpsc::{Receiver, TryRecvError, channel},
+    thread,
+};
+
+/// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    /// Missing for a notification, which gets no answer
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Error sent back to the client
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(message: impl Into<String>) -> Self {
+        RpcError {
+            code: INVALID_PARAMS,
+            message: message.into(),
+        }
+    }
+}
+
+/// Drives the game with JSON-RPC 2.0 requests read from stdin, one per line, and answers on stdout.
+///
+/// The simulation only moves with the `step` method, the keyboard is ignored. The answers
+/// are the lines of stdout starting with `{`, the other lines are the logs of the game.
+///
+/// Methods, with their parameters:
+/// - `press`, `release`: `action` like `"Fire"`, `player` (0 by default)
+/// - `step`: `ticks` (1 by default), answers the tick reached
+/// - `get_state`: answers the same snapshot as the spectator stream
+/// - `reset`: starts a new run
+/// - `spawn_asteroid`: `x`, `y`, `size` from 1 to 3, `speed`, `direction`, all optional,
+///   in front of the first ship without a position, like the debug menu without any parameter
+/// - `split_all_asteroids`
+/// - `set_config`: `key` among `debug`, `asteroid_limit`, `asteroids_children`, `max_waves`,
+///   `lives` and `players`, with its `value`. The lives apply from the next `reset`
+/// - `quit`
+pub struct RemoteControl {
+    lines: Receiver<String>,
+    /// Actions held by each player
+    held: Vec<Vec<Action>>,
+    /// Actions pressed since the last step, delivered to its first tick
+    pressed: Vec<Vec<Action>>,
+}
+
+#[allow(unused)]
+impl RemoteControl {
+    /// Start reading stdin in the background
+    pub fn start() -> Self {
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        ast_lib::log!("[INFO] Remote control on stdin");
+        RemoteControl {
+            lines,
+            held: vec![Vec::new(); MAX_PLAYERS],
+            pressed: vec![Vec::new(); MAX_PLAYERS],
+        }
+    }
+
+    /// Answer every request received since the last frame, the game exits once stdin is closed
+    pub fn poll(&mut self, gamestate: &mut Gamestate) {
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    // The log lines go to stderr, stdout only carries the answers
+                    if let Some(answer) = self.handle_line(&line, gamestate) {
+                        let mut stdout = stdout().lock();
+                        if writeln!(stdout, "{}", answer)
+                            .and_then(|_| stdout.flush())
+                            .is_err()
+                        {
+                            gamestate.exit = true;
+                            break;
+                        }
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    gamestate.exit = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Answer of one line, `None` for a notification
+    pub fn handle_line(&mut self, line: &str, gamestate: &mut Gamestate) -> Option<Value> {
+        let value: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(e) => return Some(error_answer(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+        let request: Request = match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => return Some(error_answer(Value::Null, INVALID_REQUEST, e.to_string())),
+        };
+
+        let result = self.call(&request.method, &request.params, gamestate);
+        let id = request.id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_answer(id, e.code, e.message),
+        })
+    }
+
+    fn call(
+        &mut self,
+        method: &str,
+        params: &Value,
+        gamestate: &mut Gamestate,
+    ) -> Result<Value, RpcError> {
+        match method {
+            "press" | "release" => {
+                let action: Action = serde_json::from_value(params["action"].clone())
+                    .map_err(|e| RpcError::invalid_params(format!("action: {}", e)))?;
+                let player = get_player(params, gamestate)?;
+                let held = &mut self.held[player];
+                if method == "press" {
+                    if !held.contains(&action) {
+                        held.push(action.clone());
+                    }
+                    self.pressed[player].push(action);
+                } else {
+                    held.retain(|a| *a != action);
+                }
+                Ok(Value::Bool(true))
+            }
+            "step" => {
+                let ticks = match &params["ticks"] {
+                    Value::Null => 1,
+                    ticks => ticks.as_u64().ok_or_else(|| {
+                        RpcError::invalid_params("ticks must be a positive integer")
+                    })?,
+                };
+                for _ in 0..ticks {
+                    let inputs: Vec<TickInput> = (0..gamestate.players.len())
+                        .map(|player| TickInput {
+                            held: self.held[player].clone(),
+                            pressed: std::mem::take(&mut self.pressed[player]),
+                            scrolled: Vec::new(),
+                        })
+                        .collect();
+                    gamestate.update_simulation_speed();
+                    gamestate.tick(&inputs);
+                }
+                Ok(json!({ "tick": gamestate.loop_number }))
+            }
+            "get_state" => serde_json::to_value(Snapshot::new(gamestate)).map_err(|e| RpcError {
+                code: INTERNAL_ERROR,
+                message: e.to_string(),
+            }),
+            "reset" => {
+                gamestate.menu.clear();
+                gamestate.reset();
+                Ok(Value::Bool(true))
+            }
+            "spawn_asteroid" => {
+                let float = |name: &str| params[name].as_f64().map(|value| value as f32);
+                if params.is_null() {
+                    // In front of the first ship, like the debug menu
+                    gamestate.create_debug_asteroid();
+                } else {
+                    let position = match (float("x"), float("y")) {
+                        (Some(x), Some(y)) => Some(vec2(x, y)),
+                        (None, None) => Some(
+                            gamestate.players[0]
+                                .spaceship
+                                .position_in_front_with_rotation(500.0, 0.0),
+                        ),
+                        _ => return Err(RpcError::invalid_params("x and y go together")),
+                    };
+                    let size =
+                        float("size").map(|size| size.clamp(1.0, 3.0).round() * Asteroid::SCALE);
+                    gamestate.asteroid_changes.push(Change::Add(Asteroid::new(
+                        position,
+                        float("speed"),
+                        size,
+                        None,
+                        float("direction"),
+                        None,
+                        None,
+                        None,
+                    )));
+                }
+                Ok(Value::Bool(true))
+            }
+            "split_all_asteroids" => {
+                gamestate.split_all_asteroids();
+                Ok(Value::Bool(true))
+            }
+            "set_config" => {
+                set_config(params, gamestate)?;
+                Ok(Value::Bool(true))
+            }
+            "quit" => {
+                gamestate.exit = true;
+                Ok(Value::Bool(true))
+            }
+            _ => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("unknown method {:?}", method),
+            }),
+        }
+    }
+}
+
+fn error_answer(id: Value, code: i64, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// The `player` parameter, the first player when missing
+fn get_player(params: &Value, gamestate: &Gamestate) -> Result<usize, RpcError> {
+    match &params["player"] {
+        Value::Null => Ok(0),
+        player => player
+            .as_u64()
+            .map(|player| player as usize)
+            .filter(|player| *player < gamestate.players.len())
+            .ok_or_else(|| RpcError::invalid_params("no such player")),
+    }
+}
+
+/// Change the setting `key` to `value`
+fn set_config(params: &Value, gamestate: &mut Gamestate) -> Result<(), RpcError> {
+    let value = &params["value"];
+    let number = || {
+        value
+            .as_u64()
+            .ok_or_else(|| RpcError::invalid_params("value must be a positive integer"))
+    };
+    let small = || {
+        number()?
+            .try_into()
+            .map_err(|_| RpcError::invalid_params("value is too large"))
+    };
+    match params["key"].as_str().unwrap_or_default() {
+        "debug" => {
+            gamestate.debug = value
+                .as_bool()
+                .ok_or_else(|| RpcError::invalid_params("value must be a boolean"))?;
+        }
+        "asteroid_limit" => gamestate.asteroid_limit = small()?,
+        "asteroids_children" => gamestate.asteroids_children = small()?,
+        "max_waves" => gamestate.max_waves = (number()? as u32).max(1),
+        "lives" => gamestate.starting_lives = small().map(|lives: u8| lives.max(1))?,
+        "players" => gamestate.set_player_count(number()? as usize),
+        key => {
+            return Err(RpcError::invalid_params(format!(
+                "unknown setting {:?}",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast_lib::CosmicEntity;
+
+    fn remote() -> (RemoteControl, Gamestate) {
+        let gamestate = Gamestate::headless();
+        let (_, lines) = channel();
+        let remote = RemoteControl {
+            lines,
+            held: vec![Vec::new(); MAX_PLAYERS],
+            pressed: vec![Vec::new(); MAX_PLAYERS],
+        };
+        (remote, gamestate)
+    }
+
+    fn request(remote: &mut RemoteControl, gamestate: &mut Gamestate, line: &str) -> Value {
+        remote.handle_line(line, gamestate).expect("an answer")
+    }
+
+    fn error_code(answer: &Value) -> i64 {
+        answer["error"]["code"].as_i64().expect("an error")
+    }
+
+    #[test]
+    fn malformed_requests_are_rejected() {
+        let (mut remote, mut gamestate) = remote();
+        let answer = request(&mut remote, &mut gamestate, "{not json");
+        assert_eq!(error_code(&answer), PARSE_ERROR);
+        assert_eq!(answer["id"], Value::Null);
+
+        let answer = request(
+            &mut remote,
+            &mut gamestate,
+            r#"{"jsonrpc": "2.0", "id": 1}"#,
+        );
+        assert_eq!(error_code(&answer), INVALID_REQUEST);
+
+        let answer = request(&mut remote, &mut gamestate, r#"{"id": 7, "method": "fly"}"#);
+        assert_eq!(error_code(&answer), METHOD_NOT_FOUND);
+        assert_eq!(answer["id"], json!(7));
+        assert_eq!(answer["jsonrpc"], json!("2.0"));
+
+        for params in [
+            r#"{"action": "Jump"}"#,
+            r#"{"action": "Fire", "player": 3}"#,
+        ] {
+            let line = format!(r#"{{"id": 1, "method": "press", "params": {}}}"#, params);
+            let answer = request(&mut remote, &mut gamestate, &line);
+            assert_eq!(error_code(&answer), INVALID_PARAMS, "{}", params);
+        }
+        for line in [
+            r#"{"id": 1, "method": "step", "params": {"ticks": -1}}"#,
+            r#"{"id": 1, "method": "spawn_asteroid", "params": {"x": 10}}"#,
+            r#"{"id": 1, "method": "set_config", "params": {"key": "gravity", "value": 1}}"#,
+            r#"{"id": 1, "method": "set_config", "params": {"key": "lives", "value": 300}}"#,
+        ] {
+            let answer = request(&mut remote, &mut gamestate, line);
+            assert_eq!(error_code(&answer), INVALID_PARAMS, "{}", line);
+        }
+    }
+
+    #[test]
+    fn notifications_get_no_answer() {
+        let (mut remote, mut gamestate) = remote();
+        assert_eq!(
+            remote.handle_line(r#"{"jsonrpc": "2.0", "method": "quit"}"#, &mut gamestate),
+            None
+        );
+        assert!(gamestate.exit);
+        // Not even an error
+        assert_eq!(
+            remote.handle_line(r#"{"method": "fly"}"#, &mut gamestate),
+            None
+        );
+    }
+
+    #[test]
+    fn presses_and_steps_drive_the_ship() {
+        let (mut remote, mut gamestate) = remote();
+        gamestate.asteroids.clear();
+        let line = r#"{"id": 1, "method": "press", "params": {"action": "SpeedUp"}}"#;
+        assert_eq!(
+            request(&mut remote, &mut gamestate, line)["result"],
+            json!(true)
+        );
+        let line = r#"{"id": 2, "method": "press", "params": {"action": "Fire"}}"#;
+        request(&mut remote, &mut gamestate, line);
+
+        let line = r#"{"id": 3, "method": "step", "params": {"ticks": 10}}"#;
+        let answer = request(&mut remote, &mut gamestate, line);
+        assert_eq!(answer["result"], json!({ "tick": 10 }));
+        assert!(gamestate.players[0].spaceship.get_speed() > 0.0);
+        let fired = gamestate.stats.get_fired();
+        assert!(fired > 0);
+
+        // Held until released
+        for action in ["SpeedUp", "Fire"] {
+            let line = format!(
+                r#"{{"id": 4, "method": "release", "params": {{"action": "{}"}}}}"#,
+                action
+            );
+            request(&mut remote, &mut gamestate, &line);
+        }
+        assert!(remote.held[0].is_empty());
+        let line = r#"{"id": 5, "method": "step", "params": {"ticks": 60}}"#;
+        let answer = request(&mut remote, &mut gamestate, line);
+        assert_eq!(answer["result"], json!({ "tick": 70 }));
+        assert_eq!(gamestate.stats.get_fired(), fired);
+
+        let state = request(
+            &mut remote,
+            &mut gamestate,
+            r#"{"id": 6, "method": "get_state"}"#,
+        );
+        assert_eq!(state["result"]["tick"], json!(70));
+        assert_eq!(state["result"]["ships"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn settings_and_spawns_apply() {
+        let (mut remote, mut gamestate) = remote();
+        for (key, value) in [("players", "2"), ("lives", "0"), ("max_waves", "3")] {
+            let line = format!(
+                r#"{{"id": 1, "method": "set_config", "params": {{"key": "{}", "value": {}}}}}"#,
+                key, value
+            );
+            assert_eq!(
+                request(&mut remote, &mut gamestate, &line)["result"],
+                json!(true)
+            );
+        }
+        request(
+            &mut remote,
+            &mut gamestate,
+            r#"{"id": 2, "method": "reset"}"#,
+        );
+        assert_eq!(gamestate.players.len(), 2);
+        assert!(gamestate.players.iter().all(|player| player.lives == 1));
+        assert_eq!(gamestate.max_waves, 3);
+
+        // The second player can be driven too
+        let line = r#"{"id": 3, "method": "press", "params": {"action": "Fire", "player": 1}}"#;
+        assert_eq!(
+            request(&mut remote, &mut gamestate, line)["result"],
+            json!(true)
+        );
+
+        gamestate.asteroids.clear();
+        let line = r#"{"id": 4, "method": "spawn_asteroid", "params": {"x": 100, "y": 200, "size": 2, "speed": 0}}"#;
+        request(&mut remote, &mut gamestate, line);
+        request(
+            &mut remote,
+            &mut gamestate,
+            r#"{"id": 5, "method": "step"}"#,
+        );
+        assert_eq!(gamestate.asteroids.len(), 1);
+        assert_eq!(gamestate.asteroids[0].get_size(), 2.0 * Asteroid::SCALE);
+    }
+}
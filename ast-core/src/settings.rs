@@ -0,0 +1,90 @@
+use crate::{
+    audio::Volume,
+    gamestate::Gamestate,
+    i18n::Language,
+    minimap::{Corner, Minimap},
+};
+use serde::{Deserialize, Serialize};
+use std::fs::{read_to_string, write};
+
+/// Default location of the settings
+pub const SETTINGS_FILE: &str = "settings.json";
+/// Version written in the settings file
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// Options of the settings menu, saved as soon as one changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub language: Language,
+    pub minimap_visible: bool,
+    pub minimap_corner: Corner,
+    /// Fraction of the screen height, one of [`Minimap::SCALES`]
+    pub minimap_scale: f32,
+    pub volume: Volume,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let minimap = Minimap::new();
+        Settings {
+            language: Language::REFERENCE,
+            minimap_visible: minimap.is_visible(),
+            minimap_corner: minimap.get_corner(),
+            minimap_scale: minimap.get_scale(),
+            volume: Volume::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SettingsFile {
+    version: u32,
+    settings: Settings,
+}
+
+#[allow(unused)]
+impl Settings {
+    /// Settings currently applied to the game
+    pub fn of(gamestate: &Gamestate) -> Self {
+        Settings {
+            language: gamestate.locale.get_language(),
+            minimap_visible: gamestate.minimap.is_visible(),
+            minimap_corner: gamestate.minimap.get_corner(),
+            minimap_scale: gamestate.minimap.get_scale(),
+            volume: gamestate.audio.get_volume(),
+        }
+    }
+
+    pub fn apply(&self, gamestate: &mut Gamestate) {
+        gamestate.locale.set_language(self.language);
+        gamestate.minimap.set_visible(self.minimap_visible);
+        gamestate.minimap.set_corner(self.minimap_corner);
+        gamestate.minimap.set_scale(self.minimap_scale);
+        gamestate.audio.set_volume(self.volume);
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file: SettingsFile = serde_json::from_str(&read_to_string(path)?)?;
+        if file.version != SETTINGS_VERSION {
+            return Err(format!("unsupported version {}", file.version).into());
+        }
+        Ok(file.settings)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = SettingsFile {
+            version: SETTINGS_VERSION,
+            settings: self.clone(),
+        };
+        write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+}
+
+/// Write the settings of the game to [`SETTINGS_FILE`], called after each change
+pub fn save_settings(gamestate: &Gamestate) {
+    if let Err(e) = Settings::of(gamestate).save(SETTINGS_FILE) {
+        eprintln!("[ERROR] Couldn't save the settings: {}", e);
+    }
+}
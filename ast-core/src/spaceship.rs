@@ -1,9 +1,10 @@
-use ast_lib::generate_uid;
+use ast_lib::{generate_uid, screen_size};
 use mac_der::Entity;
 use ast_lib::CosmicEntity;
+use crate::camera::Camera;
 use macroquad::prelude::{
-    draw_circle, draw_circle_lines, draw_line, draw_triangle, draw_text, measure_text, screen_dpi_scale,
-    screen_height, screen_width, vec2, Color, Vec2, BLUE, LIME, PINK, RED, YELLOW,
+    draw_text, measure_text, screen_dpi_scale, screen_height, screen_width, vec2, Color, Vec2, BLUE, LIME,
+    PINK, RED, YELLOW,
 };
 use std::f32::consts::PI;
 
@@ -24,14 +25,21 @@ pub struct Spaceship {
     alive: bool,
     hom_cooldown: f64,
     fire_cooldown: f64,
+    color: Color,
 }
 
 #[allow(unused)]
 impl Spaceship {
+    /// Seconds between two cannon shots
+    pub const FIRE_COOLDOWN: f64 = 0.15;
+    /// Seconds between two homing salvos
+    pub const HOMING_COOLDOWN: f64 = 0.8;
+    pub const MAX_SHIELD: f32 = 100.0;
+
     pub fn new() -> Self {
         Spaceship {
             id: generate_uid(),
-            position: vec2(screen_width() / 2.0, screen_height() / 2.0),
+            position: screen_size() / 2.0,
             speed: 0.0,
             max_speed: 500.0,
             rotation: 0.0,
@@ -39,15 +47,26 @@ impl Spaceship {
             missile_capacity: 2,
             special_radius: 55.0,
             size: 25.0,
-            shield: 100.0,
+            shield: Self::MAX_SHIELD,
             shield_timer: 0.0,
             invulnerability: 3.0,
             alive: true,
             hom_cooldown: 0.0,
             fire_cooldown: 0.0,
+            color: YELLOW,
         }
     }
 
+    /// Bring the spaceship back after losing a life, the missile capacity and colour are kept
+    pub fn respawn(&mut self, position: Vec2) {
+        let capacity = self.missile_capacity;
+        let color = self.color;
+        *self = Self::new();
+        self.position = position;
+        self.missile_capacity = capacity;
+        self.color = color;
+    }
+
     /// Returns a position `distance` units in front of the spaceship.
     pub fn position_in_front_with_rotation(&self, distance: f32, rotation_offset: f32) -> Vec2 {
         Vec2::new(
@@ -67,7 +86,7 @@ impl Spaceship {
         )
     }
 
-    pub fn draw_trajectory(&self, length: Option<f32>, rotation_angle: Option<f32>) {
+    pub fn draw_trajectory(&self, camera: &Camera, length: Option<f32>, rotation_angle: Option<f32>) {
         let length = length.unwrap_or(8000.0);
         let rotation_angle = rotation_angle.unwrap_or(0.0);
 
@@ -75,18 +94,16 @@ impl Spaceship {
         let end_point = self.position_in_front_with_rotation(length, rotation_angle);
 
         // Draw the trajectory arrow
-        draw_line(
-            self.position.x,
-            self.position.y,
-            end_point.x,
-            end_point.y,
+        camera.draw_line(
+            self.position,
+            end_point,
             2.0,
             Color::from_rgba(255, 255, 255, 64),
         );
     }
 
     // Draw the spaceship and its shield
-    pub fn draw(&mut self, size: f32, delta_time: f64, debug: bool) {
+    pub fn draw(&mut self, camera: &Camera, size: f32, delta_time: f64, debug: bool) {
         let position = self.get_position();
 
         // === Spaceship triangle ===
@@ -101,38 +118,35 @@ impl Spaceship {
         let rotated_right = self.rotate_point(right, -self.rotation);
 
         if !debug {
-            draw_triangle(
+            camera.draw_triangle(
                 self.position + rotated_front,
                 self.position + rotated_left,
                 self.position + rotated_right,
-                YELLOW,
+                self.color,
             );
         }
 
         // === Shield rings based on strength ===
         let shield_strength = self.shield;
         if shield_strength > 0.0 {
-            draw_circle_lines(
-                position.x,
-                position.y,
+            camera.draw_circle_lines(
+                position,
                 self.size + 2.0,
                 5.0,
                 Color::from_rgba(255, 0, 0, ((shield_strength) / 33.3 * 255.0) as u8),
             );
         }
         if shield_strength > 33.0 {
-            draw_circle_lines(
-                position.x,
-                position.y,
+            camera.draw_circle_lines(
+                position,
                 self.size + 7.0,
                 5.0,
                 Color::from_rgba(255, 255, 0, ((shield_strength - 33.3) / 33.3 * 255.0) as u8),
             );
         }
         if shield_strength > 66.0 {
-            draw_circle_lines(
-                position.x,
-                position.y,
+            camera.draw_circle_lines(
+                position,
                 self.size + 13.0,
                 5.0,
                 Color::from_rgba(0, 255, 0, ((shield_strength - 66.6) / 33.3 * 255.0) as u8),
@@ -152,9 +166,8 @@ impl Spaceship {
             let sine = (self.shield_timer / blink_period * 2.0 * std::f64::consts::PI).sin();
             let alpha = ((sine * 0.5 + 0.5) * (255.0 - 64.0) + 64.0) as u8;
 
-            draw_circle_lines(
-                position.x,
-                position.y,
+            camera.draw_circle_lines(
+                position,
                 self.size,
                 20.0,
                 Color::from_rgba(128, 255, 255, alpha),
@@ -164,10 +177,10 @@ impl Spaceship {
         // === Debug rendering ===
         if debug {
             // Hitbox
-            draw_circle_lines(position.x, position.y, self.size, 3.0, BLUE);
+            camera.draw_circle_lines(position, self.size, 3.0, BLUE);
 
             // Direction line
-            self.draw_trajectory(Some(4000.0), Some(0.0));
+            self.draw_trajectory(camera, Some(4000.0), Some(0.0));
 
             // use full real ranges, same as you normally do
             let positions = self.generate_positions_angles(
@@ -183,10 +196,10 @@ impl Spaceship {
             for i in 0..total {
                 let (pos, _) = positions[i as usize]; // get the position corresponding to this index
                 let color = if i < half { LIME } else { RED };
-                draw_circle(pos.x, pos.y, 5.0, color);
+                camera.draw_circle(pos, 5.0, color);
             }
 
-            draw_circle(self.get_position().x, self.get_position().y, 7.5, YELLOW);
+            camera.draw_circle(self.get_position(), 7.5, YELLOW);
 
             let font_size = 20.0;
             let mut texts = Vec::from([
@@ -215,12 +228,14 @@ impl Spaceship {
 
             let text_size = *debug_text_sizes.iter().max().unwrap() as f32;
 
-            let x_offset = if screen_width() - position.x >= text_size + 25.0 {
+            // Texts are placed on the screen, next to where the camera shows the spaceship
+            let screen_position = camera.world_to_screen(position);
+            let x_offset = if screen_width() - screen_position.x >= text_size + 25.0 {
                 25.0
             } else {
                 -text_size + 25.0
             };
-            let y_offset = if screen_height() - position.y >= font_size * texts.len() as f32 {
+            let y_offset = if screen_height() - screen_position.y >= font_size * texts.len() as f32 {
                 20.0
             } else {
                 -font_size * texts.len() as f32
@@ -229,8 +244,8 @@ impl Spaceship {
             for (index, field) in &mut texts.iter_mut().enumerate() {
                 draw_text(
                     field,
-                    position.x + x_offset,
-                    (position.y + y_offset) + index as f32 * 20.0,
+                    screen_position.x + x_offset,
+                    (screen_position.y + y_offset) + index as f32 * 20.0,
                     font_size,
                     PINK,
                 );
@@ -238,7 +253,7 @@ impl Spaceship {
         }
     }
 
-    pub fn update(&mut self, delta_time: f64) {
+    pub fn update(&mut self, delta_time: f64, bounds: &Vec2) {
         // Calculate velocity based on rotation and max speed
         let direction = vec2(self.rotation.cos(), -self.rotation.sin());
 
@@ -249,28 +264,32 @@ impl Spaceship {
         // Update position using the current speed and direction
         self.position += direction * self.speed * delta_time as f32;
 
-        // Handle screen wrapping (loop the spaceship)
+        // Handle world wrapping (loop the spaceship)
         if self.position.x < 0.0 {
-            self.position.x = screen_width();
-        } else if self.position.x > screen_width() {
+            self.position.x = bounds.x;
+        } else if self.position.x > bounds.x {
             self.position.x = 0.0;
         }
 
         if self.position.y < 0.0 {
-            self.position.y = screen_height();
-        } else if self.position.y > screen_height() {
+            self.position.y = bounds.y;
+        } else if self.position.y > bounds.y {
             self.position.y = 0.0;
         }
 
+        if self.invulnerability > 0.0 {
+            self.invulnerability = (self.invulnerability - delta_time).max(0.0);
+        }
+    }
+
+    /// Weapon cooldowns go down with the simulation ticks, so the fire rate does not depend on the FPS
+    pub fn tick_cooldowns(&mut self, delta_time: f64) {
         if self.hom_cooldown > 0.0 {
             self.hom_cooldown = (self.hom_cooldown - delta_time).max(0.0);
         }
         if self.fire_cooldown > 0.0 {
             self.fire_cooldown = (self.fire_cooldown - delta_time).max(0.0);
         }
-        if self.invulnerability > 0.0 {
-            self.invulnerability = (self.invulnerability - delta_time).max(0.0);
-        }
     }
 
     pub fn move_spaceship(&mut self, delta_time: f64, movement_type: bool) {
@@ -440,6 +459,10 @@ impl Spaceship {
         self.hom_cooldown
     }
 
+    pub fn get_color(&self) -> Color {
+        self.color
+    }
+
     pub fn get_firing_cooldown(&self) -> f64 {
         self.fire_cooldown
     }
@@ -467,6 +490,10 @@ impl Spaceship {
         }
     }
 
+    pub fn set_position(&mut self, position: Vec2) {
+        self.position = position
+    }
+
     pub fn set_invulnerability(&mut self, amount: f64) {
         self.invulnerability = amount;
     }
@@ -487,6 +514,10 @@ impl Spaceship {
         self.hom_cooldown = amount
     }
 
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
     pub fn set_firing_cooldown(&mut self, amount: f64) {
         self.fire_cooldown = amount
     }
@@ -0,0 +1,321 @@
+use crate::gamestate::Gamestate;
+use ast_lib::CosmicEntity;
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    net::{TcpListener, TcpStream},
+};
+
+/// One line of the stream, everything on the field after a tick
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub tick: u128,
+    pub wave: u32,
+    pub score: u128,
+    pub max_score: u128,
+    pub over: bool,
+    pub win: bool,
+    pub ships: Vec<ShipSnapshot>,
+    pub asteroids: Vec<AsteroidSnapshot>,
+    pub missiles: Vec<MissileSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShipSnapshot {
+    pub player: usize,
+    pub x: f32,
+    pub y: f32,
+    pub rotation: f32,
+    pub speed: f32,
+    pub shield: f32,
+    pub alive: bool,
+    pub lives: u8,
+    pub score: u128,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AsteroidSnapshot {
+    pub id: u64,
+    pub x: f32,
+    pub y: f32,
+    pub size: f32,
+    pub rotation: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissileSnapshot {
+    pub id: u64,
+    pub x: f32,
+    pub y: f32,
+    pub rotation: f32,
+    pub homing: bool,
+    pub owner: usize,
+}
+
+impl Snapshot {
+    pub fn new(gamestate: &Gamestate) -> Self {
+        Snapshot {
+            tick: gamestate.loop_number,
+            wave: gamestate.wave,
+            score: gamestate.score[0],
+            max_score: gamestate.score[1],
+            over: gamestate.over,
+            win: gamestate.win,
+            ships: gamestate
+                .players
+                .iter()
+                .enumerate()
+                .map(|(player, p)| ShipSnapshot {
+                    player,
+                    x: p.spaceship.get_position().x,
+                    y: p.spaceship.get_position().y,
+                    rotation: p.spaceship.get_rotation(),
+                    speed: p.spaceship.get_speed(),
+                    shield: p.spaceship.get_shield(),
+                    alive: p.is_alive(),
+                    lives: p.lives,
+                    score: p.score,
+                })
+                .collect(),
+            asteroids: gamestate
+                .asteroids
+                .iter()
+                .map(|a| AsteroidSnapshot {
+                    id: a.get_id(),
+                    x: a.get_position().x,
+                    y: a.get_position().y,
+                    size: a.get_size(),
+                    rotation: a.get_rotation(),
+                })
+                .collect(),
+            missiles: gamestate
+                .missiles
+                .iter()
+                .map(|m| MissileSnapshot {
+                    id: m.get_id(),
+                    x: m.get_position().x,
+                    y: m.get_position().y,
+                    rotation: m.get_rotation(),
+                    homing: m.is_homing(),
+                    owner: m.get_owner(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Publishes a JSON line [`Snapshot`] every `interval` ticks to the TCP clients on the local machine.
+///
+/// The game never waits for a client: one that can't keep up is disconnected.
+pub struct SpectatorServer {
+    listener: TcpListener,
+    clients: Vec<TcpStream>,
+    interval: u128,
+}
+
+#[allow(unused)]
+impl SpectatorServer {
+    /// Ticks between two snapshots, 10 per second
+    pub const DEFAULT_INTERVAL: u128 = 6;
+
+    pub fn start(port: u16, interval: u128) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        ast_lib::log!("[INFO] Spectator stream on 127.0.0.1:{}", port);
+        Ok(SpectatorServer {
+            listener,
+            clients: Vec::new(),
+            interval: interval.max(1),
+        })
+    }
+
+    pub fn get_client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Take the clients waiting to connect, call it once per frame
+    pub fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        eprintln!("[WARN] Couldn't set up the spectator {}: {}", address, e);
+                        continue;
+                    }
+                    let _ = stream.set_nodelay(true);
+                    ast_lib::log!("[INFO] Spectator connected from {}", address);
+                    self.clients.push(stream);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("[WARN] Spectator accept failed: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Send the snapshot of the tick that just ran when it falls on the interval
+    pub fn publish(&mut self, gamestate: &Gamestate) {
+        if self.clients.is_empty() || gamestate.loop_number % self.interval != 0 {
+            return;
+        }
+        let mut line = match serde_json::to_string(&Snapshot::new(gamestate)) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("[ERROR] Couldn't encode the snapshot: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+
+        self.clients
+            .retain_mut(|client| match client.write_all(line.as_bytes()) {
+                Ok(()) => true,
+                Err(e) => {
+                    ast_lib::log!(
+                        "[INFO] Spectator {} disconnected: {}",
+                        client
+                            .peer_addr()
+                            .map(|a| a.to_string())
+                            .unwrap_or_default(),
+                        e
+                    );
+                    false
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asteroid::Asteroid, missile::Missile};
+    use macroquad::prelude::vec2;
+    use serde_json::{Value, json};
+    use std::{
+        io::{BufRead, BufReader},
+        thread::sleep,
+        time::Duration,
+    };
+
+    fn field() -> Gamestate {
+        let mut gamestate = Gamestate::headless();
+        gamestate.loop_number = 12;
+        gamestate.score = [300, 2400];
+        gamestate.players[0].score = 300;
+        gamestate.players[0]
+            .spaceship
+            .set_position(vec2(10.0, 20.0));
+        gamestate.asteroids = vec![Asteroid::new(
+            Some(vec2(30.0, 40.0)),
+            Some(0.0),
+            Some(Asteroid::SCALE * 2.0),
+            Some(1.5),
+            None,
+            None,
+            None,
+            None,
+        )];
+        let mut missile = Missile::new(vec2(50.0, 60.0), 0.0, 0.5, true, vec2(0.0, 0.0));
+        missile.set_owner(0);
+        gamestate.missiles = vec![missile];
+        gamestate
+    }
+
+    fn keys(value: &Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn snapshots_have_a_stable_json_shape() {
+        let gamestate = field();
+        let snapshot = serde_json::to_value(Snapshot::new(&gamestate)).unwrap();
+
+        assert_eq!(
+            keys(&snapshot),
+            [
+                "asteroids",
+                "max_score",
+                "missiles",
+                "over",
+                "score",
+                "ships",
+                "tick",
+                "wave",
+                "win"
+            ]
+        );
+        assert_eq!(snapshot["tick"], json!(12));
+        assert_eq!(snapshot["wave"], json!(1));
+        assert_eq!(snapshot["score"], json!(300));
+        assert_eq!(snapshot["max_score"], json!(2400));
+        assert_eq!(snapshot["over"], json!(false));
+        assert_eq!(snapshot["win"], json!(false));
+
+        let ship = &snapshot["ships"][0];
+        assert_eq!(
+            keys(ship),
+            [
+                "alive", "lives", "player", "rotation", "score", "shield", "speed", "x", "y"
+            ]
+        );
+        assert_eq!(ship["player"], json!(0));
+        assert_eq!(
+            (ship["x"].clone(), ship["y"].clone()),
+            (json!(10.0), json!(20.0))
+        );
+        assert_eq!(ship["alive"], json!(true));
+        assert_eq!(ship["lives"], json!(1));
+        assert_eq!(ship["score"], json!(300));
+
+        let asteroid = &snapshot["asteroids"][0];
+        assert_eq!(keys(asteroid), ["id", "rotation", "size", "x", "y"]);
+        assert_eq!(asteroid["id"], json!(gamestate.asteroids[0].get_id()));
+        assert_eq!(asteroid["size"], json!(60.0));
+        assert_eq!(asteroid["rotation"], json!(1.5));
+
+        let missile = &snapshot["missiles"][0];
+        assert_eq!(
+            keys(missile),
+            ["homing", "id", "owner", "rotation", "x", "y"]
+        );
+        assert_eq!(missile["homing"], json!(true));
+        assert_eq!(missile["owner"], json!(0));
+        assert_eq!(missile["rotation"], json!(0.5));
+    }
+
+    #[test]
+    fn clients_get_one_line_per_interval() {
+        let mut gamestate = field();
+        let mut server = SpectatorServer::start(0, 4).unwrap();
+        let address = server.listener.local_addr().unwrap();
+        let client = TcpStream::connect(address).unwrap();
+        for _ in 0..100 {
+            server.accept();
+            if server.get_client_count() == 1 {
+                break;
+            }
+            sleep(Duration::from_millis(5));
+        }
+        assert_eq!(server.get_client_count(), 1);
+
+        // Off the interval, then on it
+        gamestate.loop_number = 6;
+        server.publish(&gamestate);
+        gamestate.loop_number = 8;
+        server.publish(&gamestate);
+
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        let snapshot: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(snapshot["tick"], json!(8));
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{asteroid::Asteroid, i18n::Localization};
+use macroquad::prelude::{BEIGE, WHITE, draw_text};
+use serde::{Deserialize, Serialize};
+use std::fs::{read_to_string, write};
+
+/// Default location of the lifetime statistics
+pub const STATS_FILE: &str = "stats.json";
+/// Version written in the statistics file
+pub const STATS_VERSION: u32 = 1;
+
+/// What happened during a run, reset with the game
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunStats {
+    pub cannon_fired: u32,
+    pub homing_fired: u32,
+    pub cannon_kills: u32,
+    pub homing_kills: u32,
+    /// Asteroids destroyed, by size from the smallest
+    pub destroyed: [u32; 3],
+    /// Shield lost to collisions
+    pub damage_taken: f32,
+    pub lives_lost: u32,
+    /// Seconds of simulation with the ship alive
+    pub time_alive: f64,
+    pub distance: f64,
+    pub peak_speed: f32,
+}
+
+#[allow(unused)]
+impl RunStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_fire(&mut self, count: u32, homing: bool) {
+        if homing {
+            self.homing_fired += count;
+        } else {
+            self.cannon_fired += count;
+        }
+    }
+
+    /// A missile hit an asteroid of this size
+    pub fn record_kill(&mut self, size: f32, homing: bool) {
+        if homing {
+            self.homing_kills += 1;
+        } else {
+            self.cannon_kills += 1;
+        }
+        let index = ((size / Asteroid::SCALE).round() as usize).clamp(1, 3) - 1;
+        self.destroyed[index] += 1;
+    }
+
+    pub fn record_damage(&mut self, amount: f32) {
+        self.damage_taken += amount.max(0.0);
+    }
+
+    pub fn record_life_lost(&mut self) {
+        self.lives_lost += 1;
+    }
+
+    /// At least one ship was alive during `delta_time` seconds of simulation
+    pub fn record_time(&mut self, delta_time: f64) {
+        self.time_alive += delta_time;
+    }
+
+    /// A ship flew at `speed` during `delta_time` seconds of simulation
+    pub fn record_flight(&mut self, delta_time: f64, speed: f32) {
+        self.distance += speed.abs() as f64 * delta_time;
+        self.peak_speed = self.peak_speed.max(speed.abs());
+    }
+
+    pub fn get_fired(&self) -> u32 {
+        self.cannon_fired + self.homing_fired
+    }
+
+    pub fn get_hits(&self) -> u32 {
+        self.cannon_kills + self.homing_kills
+    }
+
+    /// Percentage of the missiles that hit an asteroid
+    pub fn get_accuracy(&self) -> f64 {
+        if self.get_fired() == 0 {
+            return 0.0;
+        }
+        self.get_hits() as f64 / self.get_fired() as f64 * 100.0
+    }
+
+    /// Value of a statistic from its name, as used in the achievement definitions
+    pub fn get_stat(&self, name: &str) -> Option<f64> {
+        Some(match name {
+            "cannon_fired" => self.cannon_fired as f64,
+            "homing_fired" => self.homing_fired as f64,
+            "fired" => self.get_fired() as f64,
+            "cannon_kills" => self.cannon_kills as f64,
+            "homing_kills" => self.homing_kills as f64,
+            "kills" => self.get_hits() as f64,
+            "destroyed_small" => self.destroyed[0] as f64,
+            "destroyed_medium" => self.destroyed[1] as f64,
+            "destroyed_large" => self.destroyed[2] as f64,
+            "accuracy" => self.get_accuracy(),
+            "damage_taken" => self.damage_taken as f64,
+            "lives_lost" => self.lives_lost as f64,
+            "time_alive" => self.time_alive,
+            "distance" => self.distance,
+            "peak_speed" => self.peak_speed as f64,
+            _ => return None,
+        })
+    }
+
+    /// Add a run to these totals, the peak speed is the best of both
+    pub fn add(&mut self, run: &RunStats) {
+        self.cannon_fired += run.cannon_fired;
+        self.homing_fired += run.homing_fired;
+        self.cannon_kills += run.cannon_kills;
+        self.homing_kills += run.homing_kills;
+        for (total, count) in self.destroyed.iter_mut().zip(run.destroyed) {
+            *total += count;
+        }
+        self.damage_taken += run.damage_taken;
+        self.lives_lost += run.lives_lost;
+        self.time_alive += run.time_alive;
+        self.distance += run.distance;
+        self.peak_speed = self.peak_speed.max(run.peak_speed);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StatsFile {
+    version: u32,
+    runs: u32,
+    totals: RunStats,
+}
+
+/// Statistics of every finished run, kept on disk
+#[derive(Debug, Clone, Default)]
+pub struct LifetimeStats {
+    runs: u32,
+    totals: RunStats,
+}
+
+#[allow(unused)]
+impl LifetimeStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file: StatsFile = serde_json::from_str(&read_to_string(path)?)?;
+        if file.version != STATS_VERSION {
+            return Err(format!("unsupported version {}", file.version).into());
+        }
+        Ok(LifetimeStats {
+            runs: file.runs,
+            totals: file.totals,
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = StatsFile {
+            version: STATS_VERSION,
+            runs: self.runs,
+            totals: self.totals.clone(),
+        };
+        write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+
+    pub fn add_run(&mut self, run: &RunStats) {
+        self.runs += 1;
+        self.totals.add(run);
+    }
+
+    /// Like [`RunStats::get_stat`] on the totals, with "runs" in addition
+    pub fn get_stat(&self, name: &str) -> Option<f64> {
+        match name {
+            "runs" => Some(self.runs as f64),
+            _ => self.totals.get_stat(name),
+        }
+    }
+
+    pub fn get_runs(&self) -> u32 {
+        self.runs
+    }
+
+    pub fn get_totals(&self) -> &RunStats {
+        &self.totals
+    }
+}
+
+/// Lines describing a run, for the game over screen
+pub fn describe_run(locale: &Localization, stats: &RunStats) -> Vec<String> {
+    vec![
+        locale.format(
+            "stats.accuracy",
+            &[
+                ("value", format!("{:.0}", stats.get_accuracy())),
+                ("hits", stats.get_hits().to_string()),
+                ("fired", stats.get_fired().to_string()),
+            ],
+        ),
+        locale.format(
+            "stats.destroyed",
+            &[
+                ("large", stats.destroyed[2].to_string()),
+                ("medium", stats.destroyed[1].to_string()),
+                ("small", stats.destroyed[0].to_string()),
+            ],
+        ),
+        locale.format(
+            "stats.kills",
+            &[
+                ("cannon", stats.cannon_kills.to_string()),
+                ("homing", stats.homing_kills.to_string()),
+            ],
+        ),
+        locale.format(
+            "stats.damage",
+            &[
+                ("value", format!("{:.0}", stats.damage_taken)),
+                ("lives", stats.lives_lost.to_string()),
+            ],
+        ),
+        locale.format(
+            "stats.time_alive",
+            &[("value", format!("{:.1}", stats.time_alive))],
+        ),
+        locale.format(
+            "stats.distance",
+            &[("value", format!("{:.0}", stats.distance))],
+        ),
+        locale.format(
+            "stats.peak_speed",
+            &[("value", format!("{:.0}", stats.peak_speed))],
+        ),
+    ]
+}
+
+/// Statistics of the last run and the lifetime totals, top-left anchored
+pub fn draw_run_stats(
+    locale: &Localization,
+    stats: &RunStats,
+    lifetime: &LifetimeStats,
+    x: f32,
+    y: f32,
+) {
+    let font_size = 26.0;
+    draw_text(locale.tr("stats.title"), x, y, font_size * 1.2, WHITE);
+    let mut line_y = y + font_size * 1.4;
+    for line in describe_run(locale, stats) {
+        draw_text(&line, x, line_y, font_size, BEIGE);
+        line_y += font_size;
+    }
+
+    let totals = lifetime.get_totals();
+    let lifetime_line = locale.format(
+        "stats.lifetime",
+        &[
+            ("runs", lifetime.get_runs().to_string()),
+            ("kills", totals.get_hits().to_string()),
+            ("accuracy", format!("{:.0}", totals.get_accuracy())),
+        ],
+    );
+    draw_text(
+        &lifetime_line,
+        x,
+        line_y + font_size * 0.5,
+        font_size,
+        WHITE,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(fired: u32, kills: u32, peak_speed: f32) -> RunStats {
+        let mut stats = RunStats::new();
+        stats.record_fire(fired, false);
+        stats.record_fire(1, true);
+        for _ in 0..kills {
+            stats.record_kill(Asteroid::SCALE * 3.0, false);
+        }
+        stats.record_kill(Asteroid::SCALE, true);
+        stats.record_damage(12.5);
+        stats.record_damage(-4.0);
+        stats.record_life_lost();
+        stats.record_time(2.0);
+        stats.record_flight(2.0, -peak_speed);
+        stats
+    }
+
+    #[test]
+    fn runs_record_what_happened() {
+        let stats = run(9, 4, 150.0);
+        assert_eq!(stats.get_fired(), 10);
+        assert_eq!(stats.get_hits(), 5);
+        assert_eq!(stats.get_accuracy(), 50.0);
+        assert_eq!(stats.destroyed, [1, 0, 4]);
+        assert_eq!(stats.damage_taken, 12.5);
+        assert_eq!(stats.distance, 300.0);
+        assert_eq!(stats.peak_speed, 150.0);
+        assert_eq!(RunStats::new().get_accuracy(), 0.0);
+    }
+
+    #[test]
+    fn lifetime_totals_add_the_runs_up() {
+        let mut lifetime = LifetimeStats::new();
+        lifetime.add_run(&run(9, 4, 150.0));
+        lifetime.add_run(&run(3, 0, 90.0));
+
+        assert_eq!(lifetime.get_runs(), 2);
+        let totals = lifetime.get_totals();
+        assert_eq!(totals.cannon_fired, 12);
+        assert_eq!(totals.homing_fired, 2);
+        assert_eq!(totals.cannon_kills, 4);
+        assert_eq!(totals.homing_kills, 2);
+        assert_eq!(totals.destroyed, [2, 0, 4]);
+        assert_eq!(totals.damage_taken, 25.0);
+        assert_eq!(totals.lives_lost, 2);
+        assert_eq!(totals.time_alive, 4.0);
+        assert_eq!(totals.distance, 480.0);
+        // The best run, not the sum
+        assert_eq!(totals.peak_speed, 150.0);
+
+        assert_eq!(lifetime.get_stat("runs"), Some(2.0));
+        assert_eq!(lifetime.get_stat("kills"), Some(6.0));
+        assert_eq!(lifetime.get_stat("destroyed_large"), Some(4.0));
+        assert_eq!(lifetime.get_stat("unknown"), None);
+    }
+
+    #[test]
+    fn lifetime_stats_survive_a_save() {
+        let mut lifetime = LifetimeStats::new();
+        lifetime.add_run(&run(9, 4, 150.0));
+        let path = std::env::temp_dir().join(format!("stats-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        lifetime.save(path).unwrap();
+        let loaded = LifetimeStats::load(path).unwrap();
+        assert_eq!(loaded.get_runs(), 1);
+        assert_eq!(loaded.get_totals(), lifetime.get_totals());
+
+        write(path, r#"{"version": 99, "runs": 1, "totals": {}}"#).unwrap();
+        assert!(LifetimeStats::load(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
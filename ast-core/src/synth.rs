@@ -0,0 +1,349 @@
+use crate::{audio::SoundEffect, music::Stem};
+use std::f32::consts::PI;
+
+pub const SAMPLE_RATE: u32 = 44100;
+/// Length of the music loops in seconds, every frequency used by the stems
+/// completes a whole number of cycles in it so the loops are seamless
+pub const LOOP_DURATION: f32 = 4.0;
+
+/// Weapons have their own laser sound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weapon {
+    Cannon,
+    Homing,
+}
+
+/// Mono sound, samples are between -1.0 and 1.0
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcmBuffer {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+impl PcmBuffer {
+    fn with_duration(duration: f32) -> Self {
+        PcmBuffer {
+            sample_rate: SAMPLE_RATE,
+            samples: vec![0.0; (duration * SAMPLE_RATE as f32) as usize],
+        }
+    }
+
+    /// Duration in seconds
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate as f32
+    }
+
+    pub fn peak(&self) -> f32 {
+        self.samples.iter().fold(0.0, |max, s| s.abs().max(max))
+    }
+
+    /// Root mean square, the average loudness
+    pub fn rms(&self) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        (self.samples.iter().map(|s| s * s).sum::<f32>() / self.samples.len() as f32).sqrt()
+    }
+
+    /// Sign changes per second, higher for brighter sounds
+    pub fn zero_crossing_rate(&self) -> f32 {
+        let crossings = self
+            .samples
+            .windows(2)
+            .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
+            .count();
+        crossings as f32 / self.duration().max(f32::EPSILON)
+    }
+
+    /// Scale the samples so the loudest one reaches `peak`
+    fn normalize(mut self, peak: f32) -> Self {
+        let current = self.peak();
+        if current > 0.0 {
+            for sample in &mut self.samples {
+                *sample *= peak / current;
+            }
+        }
+        self
+    }
+
+    /// Encode as a 16 bit mono WAV file, the format macroquad can load from memory
+    pub fn to_wav(&self) -> Vec<u8> {
+        let data_size = (self.samples.len() * 2) as u32;
+        let mut bytes = Vec::with_capacity(44 + data_size as usize);
+
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes()); // Chunk size
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(self.sample_rate * 2).to_le_bytes()); // Byte rate
+        bytes.extend_from_slice(&2u16.to_le_bytes()); // Block align
+        bytes.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.to_le_bytes());
+        for sample in &self.samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bytes
+    }
+}
+
+/// Small xorshift generator, the same seed gives the same noise on every platform
+struct Noise(u32);
+
+impl Noise {
+    fn new(seed: u32) -> Self {
+        Noise(seed.max(1))
+    }
+
+    /// Next value between -1.0 and 1.0
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+/// Noise burst, bigger asteroids give longer and deeper explosions
+pub fn explosion(size: u8) -> PcmBuffer {
+    let size = size.max(1) as f32;
+    let mut buffer = PcmBuffer::with_duration(0.2 + 0.25 * size);
+    let mut noise = Noise::new(0xA57E_0000 + size as u32);
+
+    // One pole low pass, the cutoff goes down with the size
+    let cutoff = 2400.0 / size;
+    let alpha = 1.0 - (-2.0 * PI * cutoff / SAMPLE_RATE as f32).exp();
+    let decay = 6.0 / size;
+
+    let mut filtered = 0.0;
+    for (index, sample) in buffer.samples.iter_mut().enumerate() {
+        let time = index as f32 / SAMPLE_RATE as f32;
+        filtered += alpha * (noise.next() - filtered);
+        *sample = filtered * (-decay * time).exp();
+    }
+
+    buffer.normalize(0.9)
+}
+
+/// Frequency sweep, falling for the cannon and rising for the homing missiles
+pub fn laser(weapon: Weapon) -> PcmBuffer {
+    let (duration, start, end, volume) = match weapon {
+        Weapon::Cannon => (0.12, 1400.0, 300.0, 0.5),
+        Weapon::Homing => (0.3, 500.0, 1300.0, 0.6),
+    };
+    let mut buffer = PcmBuffer::with_duration(duration);
+    let length = buffer.samples.len() as f32;
+
+    let mut phase = 0.0;
+    for (index, sample) in buffer.samples.iter_mut().enumerate() {
+        let progress = index as f32 / length;
+        let frequency = start + (end - start) * progress;
+        phase += 2.0 * PI * frequency / SAMPLE_RATE as f32;
+        // Slightly squared sine for a brighter tone
+        let tone = phase.sin() + 0.3 * (3.0 * phase).sin();
+        *sample = tone * (1.0 - progress).powf(1.5);
+    }
+
+    buffer.normalize(volume)
+}
+
+/// Low rumble of the engine
+pub fn thrust() -> PcmBuffer {
+    let mut buffer = PcmBuffer::with_duration(0.4);
+    let mut noise = Noise::new(0x7A2C_0001);
+    let length = buffer.samples.len() as f32;
+
+    // Brown noise, integrated white noise with a leak so it stays centered
+    let mut brown = 0.0;
+    for (index, sample) in buffer.samples.iter_mut().enumerate() {
+        let progress = index as f32 / length;
+        brown = (brown + noise.next() * 0.02) * 0.995;
+        // Fade in and out so it can be chained
+        *sample = brown * (PI * progress).sin();
+    }
+
+    buffer.normalize(0.4)
+}
+
+/// Dull thump with a bit of crackle, for hits on the shield
+pub fn impact() -> PcmBuffer {
+    let mut buffer = PcmBuffer::with_duration(0.25);
+    let mut noise = Noise::new(0x5D1E_1D00);
+
+    let mut phase = 0.0;
+    for (index, sample) in buffer.samples.iter_mut().enumerate() {
+        let time = index as f32 / SAMPLE_RATE as f32;
+        let frequency = 180.0 - 300.0 * time;
+        phase += 2.0 * PI * frequency / SAMPLE_RATE as f32;
+        *sample = (phase.sin() + 0.35 * noise.next()) * (-14.0 * time).exp();
+    }
+
+    buffer.normalize(0.8)
+}
+
+/// Short tone for the menus
+pub fn blip() -> PcmBuffer {
+    let mut buffer = PcmBuffer::with_duration(0.05);
+    let length = buffer.samples.len() as f32;
+
+    for (index, sample) in buffer.samples.iter_mut().enumerate() {
+        let time = index as f32 / SAMPLE_RATE as f32;
+        let progress = index as f32 / length;
+        *sample = (2.0 * PI * 880.0 * time).sin() * (1.0 - progress);
+    }
+
+    buffer.normalize(0.3)
+}
+
+/// Sum of sines, used for the chords of the pads
+fn chord(time: f32, frequencies: &[f32]) -> f32 {
+    frequencies
+        .iter()
+        .map(|frequency| (2.0 * PI * frequency * time).sin())
+        .sum::<f32>()
+        / frequencies.len() as f32
+}
+
+/// One loop of a music layer
+pub fn stem(stem: Stem) -> PcmBuffer {
+    let mut buffer = PcmBuffer::with_duration(LOOP_DURATION);
+    let mut noise = Noise::new(0x4D55_5100);
+    // 120 bpm, a beat every half second
+    let beat = 0.5;
+
+    for (index, sample) in buffer.samples.iter_mut().enumerate() {
+        let time = index as f32 / SAMPLE_RATE as f32;
+        let in_beat = time % beat;
+        let in_half_beat = time % (beat / 2.0);
+
+        *sample = match stem {
+            // A minor, low and slow
+            Stem::MenuPad => {
+                chord(time, &[110.0, 130.75, 164.75]) * (0.8 + 0.2 * (2.0 * PI * 0.25 * time).sin())
+            }
+            // A minor, breathing twice per loop
+            Stem::Pad => {
+                chord(time, &[220.0, 261.5, 329.5]) * (0.7 + 0.3 * (2.0 * PI * 0.5 * time).sin())
+            }
+            // Plucked eighth notes
+            Stem::Bass => {
+                let tone = (2.0 * PI * 55.0 * time).sin() + 0.5 * (2.0 * PI * 110.0 * time).sin();
+                tone * (-8.0 * in_half_beat).exp()
+            }
+            // Kick on the beat, hi-hat between the beats
+            Stem::Drums => {
+                let kick = (2.0 * PI * (50.0 + 80.0 * (-30.0 * in_beat).exp()) * in_beat).sin()
+                    * (-10.0 * in_beat).exp();
+                let off_beat = (time + beat / 2.0) % beat;
+                let hat = noise.next() * 0.4 * (-60.0 * off_beat).exp();
+                kick + hat
+            }
+            // Beeps at the start of every second
+            Stem::Alarm => {
+                if time % 1.0 < 0.2 {
+                    (2.0 * PI * 660.0 * time).sin()
+                } else {
+                    0.0
+                }
+            }
+        };
+    }
+
+    buffer.normalize(0.5)
+}
+
+/// The sound of each effect of the game
+pub fn effect_buffer(effect: SoundEffect) -> PcmBuffer {
+    match effect {
+        SoundEffect::Fire => laser(Weapon::Cannon),
+        SoundEffect::HomingLaunch => laser(Weapon::Homing),
+        SoundEffect::AsteroidSplit(size) => explosion(size),
+        SoundEffect::ShieldHit => impact(),
+        SoundEffect::ShipDeath => explosion(5),
+        SoundEffect::Thrust => thrust(),
+        SoundEffect::UiBlip => blip(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthesis_is_deterministic() {
+        for effect in SoundEffect::ALL {
+            assert_eq!(effect_buffer(effect), effect_buffer(effect), "{:?}", effect);
+        }
+        for stem in Stem::ALL {
+            assert_eq!(super::stem(stem), super::stem(stem), "{:?}", stem);
+        }
+    }
+
+    #[test]
+    fn effects_are_normalized() {
+        for effect in SoundEffect::ALL {
+            let buffer = effect_buffer(effect);
+            assert!(!buffer.samples.is_empty(), "{:?}", effect);
+            assert!(buffer.peak() > 0.1 && buffer.peak() <= 1.0, "{:?}", effect);
+            assert!(
+                buffer.rms() > 0.0 && buffer.rms() < buffer.peak(),
+                "{:?}",
+                effect
+            );
+        }
+    }
+
+    #[test]
+    fn bigger_explosions_are_longer_and_deeper() {
+        let small = explosion(1);
+        let medium = explosion(2);
+        let big = explosion(3);
+        assert!(small.duration() < medium.duration() && medium.duration() < big.duration());
+        assert!(small.zero_crossing_rate() > medium.zero_crossing_rate());
+        assert!(medium.zero_crossing_rate() > big.zero_crossing_rate());
+    }
+
+    #[test]
+    fn weapons_sound_different() {
+        let cannon = laser(Weapon::Cannon);
+        let homing = laser(Weapon::Homing);
+        assert!(cannon.duration() < homing.duration());
+        assert!(cannon.peak() < homing.peak());
+        assert_ne!(cannon.rms(), homing.rms());
+    }
+
+    #[test]
+    fn thrust_rumbles_below_the_lasers() {
+        let thrust = thrust();
+        assert!(thrust.zero_crossing_rate() < laser(Weapon::Cannon).zero_crossing_rate());
+        assert!(thrust.zero_crossing_rate() < blip().zero_crossing_rate());
+    }
+
+    #[test]
+    fn stems_last_one_loop() {
+        for stem in Stem::ALL {
+            let buffer = super::stem(stem);
+            assert_eq!(
+                buffer.samples.len(),
+                (LOOP_DURATION * SAMPLE_RATE as f32) as usize
+            );
+        }
+    }
+
+    #[test]
+    fn wav_has_a_header_and_two_bytes_per_sample() {
+        let buffer = blip();
+        let wav = buffer.to_wav();
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(wav.len(), 44 + buffer.samples.len() * 2);
+    }
+}
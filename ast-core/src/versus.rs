@@ -0,0 +1,369 @@
+use crate::{
+    asteroid::Asteroid,
+    audio::SoundEffect,
+    gamestate::{Gamestate, TICKS},
+    spaceship::Spaceship,
+};
+use ast_lib::{Change, CosmicEntity};
+use macroquad::prelude::{GOLD, WHITE, draw_text, measure_text, screen_height, screen_width};
+
+/// Competitive mode: the ships shoot each other among asteroids that only block the way.
+///
+/// The score of each player counts the rounds won, the first to `rounds_to_win` takes the match.
+#[derive(Debug, Clone)]
+pub struct Versus {
+    pub rounds_to_win: u32,
+    round: u32,
+    /// Seconds left before the next round, once the current one is decided
+    intermission: Option<f64>,
+    /// Winner of the last decided round, `None` for a draw
+    round_winner: Option<usize>,
+    match_winner: Option<usize>,
+}
+
+#[allow(unused)]
+impl Versus {
+    pub const ROUNDS_TO_WIN: u32 = 3;
+    /// Asteroids on the field, they are not destroyed
+    pub const OBSTACLES: u32 = 10;
+    pub const CANNON_DAMAGE: f32 = 10.0;
+    pub const HOMING_DAMAGE: f32 = 20.0;
+    /// Seconds between two rounds
+    pub const INTERMISSION: f64 = 2.5;
+
+    pub fn new() -> Self {
+        Versus {
+            rounds_to_win: Self::ROUNDS_TO_WIN,
+            round: 0,
+            intermission: None,
+            round_winner: None,
+            match_winner: None,
+        }
+    }
+
+    pub fn get_round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn get_match_winner(&self) -> Option<usize> {
+        self.match_winner
+    }
+
+    pub fn is_round_over(&self) -> bool {
+        self.intermission.is_some()
+    }
+}
+
+/// Clear the field and put the ships back on their side, facing the center
+pub fn start_round(gamestate: &mut Gamestate) {
+    gamestate.asteroids.clear();
+    gamestate.missiles.clear();
+    gamestate.texts.clear();
+
+    let world = gamestate.camera.get_world_size();
+    for _ in 0..Versus::OBSTACLES {
+        gamestate.asteroids.push(Asteroid::new_in_bounds(&world));
+    }
+    gamestate.number_of_asteroids = gamestate.asteroids.len() as u32;
+
+    for index in 0..gamestate.players.len() {
+        let position = gamestate.get_spawn_point(index);
+        let to_center = world / 2.0 - position;
+        let player = &mut gamestate.players[index];
+        player.spaceship.respawn(position);
+        player
+            .spaceship
+            .set_rotation((-to_center.y).atan2(to_center.x));
+        player.lives = 1;
+    }
+
+    if let Some(versus) = &mut gamestate.versus {
+        versus.round += 1;
+        versus.intermission = None;
+    }
+}
+
+/// Missiles damage the ships of the other players, checked once per tick
+pub fn resolve_missile_hits(gamestate: &mut Gamestate) {
+    for missile in &gamestate.missiles {
+        for (index, player) in gamestate.players.iter_mut().enumerate() {
+            let spaceship: &mut Spaceship = &mut player.spaceship;
+            if index == missile.get_owner()
+                || !spaceship.get_life()
+                || spaceship.get_invulnerability() > 0.0
+                || !missile.collides_with(spaceship)
+            {
+                continue;
+            }
+
+            gamestate
+                .missile_changes
+                .push(Change::Remove(missile.get_id()));
+            spaceship.modify_shield(-if missile.is_homing() {
+                Versus::HOMING_DAMAGE
+            } else {
+                Versus::CANNON_DAMAGE
+            });
+            spaceship.set_invulnerability(0.2);
+            gamestate.camera.add_trauma(0.3);
+            gamestate.audio.play(SoundEffect::ShieldHit);
+
+            if spaceship.get_shield() <= 0.0 {
+                player.lives = 0;
+                player.spaceship.set_life(false);
+                gamestate.audio.play(SoundEffect::ShipDeath);
+                gamestate.camera.add_trauma(1.0);
+            }
+            break;
+        }
+    }
+}
+
+/// Decide the round once a single ship is left, then start the next one or end the match
+pub fn update_round(gamestate: &mut Gamestate) {
+    let Some(mut versus) = gamestate.versus.take() else {
+        return;
+    };
+
+    if let Some(remaining) = &mut versus.intermission {
+        *remaining -= TICKS;
+        if *remaining <= 0.0 {
+            if versus.match_winner.is_some() {
+                versus.intermission = None;
+                gamestate.over = true;
+                if gamestate.menu.is_empty() {
+                    gamestate.menu.push(String::from("Start"));
+                }
+            } else {
+                gamestate.versus = Some(versus);
+                start_round(gamestate);
+                return;
+            }
+        }
+    } else if gamestate.simulation_speed > 0.0 {
+        let alive: Vec<usize> = (0..gamestate.players.len())
+            .filter(|index| gamestate.players[*index].is_alive())
+            .collect();
+        if alive.len() <= 1 {
+            versus.round_winner = alive.first().copied();
+            if let Some(winner) = versus.round_winner {
+                let player = &mut gamestate.players[winner];
+                player.score += 1;
+                if player.score >= versus.rounds_to_win as u128 {
+                    versus.match_winner = Some(winner);
+                }
+            }
+            versus.intermission = Some(Versus::INTERMISSION);
+        }
+    }
+
+    gamestate.versus = Some(versus);
+}
+
+/// Round and wins of each player at the top of the screen, with the result of a decided round
+pub fn draw_scoreboard(gamestate: &Gamestate, versus: &Versus) {
+    let unit = screen_height() / 1000.0;
+
+    let wins = gamestate
+        .players
+        .iter()
+        .enumerate()
+        .map(|(index, player)| {
+            gamestate.locale.format(
+                "versus.wins",
+                &[
+                    ("player", (index + 1).to_string()),
+                    ("wins", player.score.to_string()),
+                ],
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("   ");
+    let round = gamestate.locale.format(
+        "versus.round",
+        &[
+            ("round", versus.round.to_string()),
+            ("wins", versus.rounds_to_win.to_string()),
+        ],
+    );
+    for (text, y, font_size) in [(&round, 60.0, 40.0), (&wins, 95.0, 32.0)] {
+        let font_size = font_size * unit;
+        let width = measure_text(text, None, font_size as u16, 1.0).width;
+        draw_text(
+            text,
+            (screen_width() - width) / 2.0,
+            y * unit,
+            font_size,
+            WHITE,
+        );
+    }
+
+    if versus.is_round_over() {
+        let result = match (versus.match_winner, versus.round_winner) {
+            (Some(winner), _) => gamestate
+                .locale
+                .format("versus.match_won", &[("player", (winner + 1).to_string())]),
+            (None, Some(winner)) => gamestate
+                .locale
+                .format("versus.round_won", &[("player", (winner + 1).to_string())]),
+            (None, None) => gamestate.locale.tr("versus.draw").to_string(),
+        };
+        let font_size = 80.0 * unit;
+        let width = measure_text(&result, None, font_size as u16, 1.0).width;
+        draw_text(
+            &result,
+            (screen_width() - width) / 2.0,
+            screen_height() / 2.0,
+            font_size,
+            GOLD,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::missile::Missile;
+
+    fn duel() -> Gamestate {
+        let mut gamestate = Gamestate::headless();
+        gamestate.versus = Some(Versus::new());
+        gamestate.set_player_count(2);
+        gamestate.reset();
+        gamestate.menu.clear();
+        gamestate.simulation_speed = 1.0;
+        for player in &mut gamestate.players {
+            player.spaceship.set_invulnerability(0.0);
+        }
+        gamestate
+    }
+
+    /// A missile of `owner` right on the ship of `target`
+    fn shoot(gamestate: &mut Gamestate, owner: usize, target: usize, homing: bool) {
+        let position = gamestate.players[target].spaceship.get_position();
+        let mut missile = Missile::new(position, 0.0, 0.0, homing, position);
+        missile.set_owner(owner);
+        gamestate.missiles = vec![missile];
+        gamestate.missile_changes.clear();
+        resolve_missile_hits(gamestate);
+    }
+
+    fn shield(gamestate: &Gamestate, index: usize) -> f32 {
+        gamestate.players[index].spaceship.get_shield()
+    }
+
+    /// Run the intermission out
+    fn next_round(gamestate: &mut Gamestate) {
+        for _ in 0..=(Versus::INTERMISSION / TICKS) as usize {
+            update_round(gamestate);
+        }
+    }
+
+    #[test]
+    fn rounds_start_with_both_ships_facing_the_center() {
+        let gamestate = duel();
+        let versus = gamestate.versus.as_ref().unwrap();
+        assert_eq!(versus.get_round(), 1);
+        assert_eq!(gamestate.asteroids.len() as u32, Versus::OBSTACLES);
+        let first = gamestate.players[0].spaceship.get_position();
+        let second = gamestate.players[1].spaceship.get_position();
+        assert!(first.x < second.x);
+        assert_eq!(gamestate.players[0].spaceship.get_rotation(), 0.0);
+        assert!(
+            (gamestate.players[1].spaceship.get_rotation().abs() - std::f32::consts::PI).abs()
+                < 1e-5
+        );
+    }
+
+    #[test]
+    fn missiles_only_hurt_the_other_ships() {
+        let mut gamestate = duel();
+        shoot(&mut gamestate, 0, 0, false);
+        assert_eq!(shield(&gamestate, 0), Spaceship::MAX_SHIELD);
+        assert!(gamestate.missile_changes.is_empty());
+
+        shoot(&mut gamestate, 0, 1, false);
+        assert_eq!(
+            shield(&gamestate, 1),
+            Spaceship::MAX_SHIELD - Versus::CANNON_DAMAGE
+        );
+        assert_eq!(gamestate.missile_changes.len(), 1);
+
+        // Invulnerable for a moment after a hit
+        shoot(&mut gamestate, 0, 1, true);
+        assert_eq!(
+            shield(&gamestate, 1),
+            Spaceship::MAX_SHIELD - Versus::CANNON_DAMAGE
+        );
+
+        gamestate.players[1].spaceship.set_invulnerability(0.0);
+        shoot(&mut gamestate, 0, 1, true);
+        assert_eq!(
+            shield(&gamestate, 1),
+            Spaceship::MAX_SHIELD - Versus::CANNON_DAMAGE - Versus::HOMING_DAMAGE
+        );
+        assert!(gamestate.players[1].is_alive());
+    }
+
+    #[test]
+    fn the_last_ship_standing_wins_the_round_and_the_match() {
+        let mut gamestate = duel();
+        for round in 1..=Versus::ROUNDS_TO_WIN {
+            let versus = gamestate.versus.as_ref().unwrap();
+            assert_eq!(versus.get_round(), round);
+            assert!(gamestate.players.iter().all(|player| player.is_alive()));
+
+            for player in &mut gamestate.players {
+                player.spaceship.set_invulnerability(0.0);
+            }
+            gamestate.players[0]
+                .spaceship
+                .modify_shield(-Spaceship::MAX_SHIELD + 1.0);
+            shoot(&mut gamestate, 1, 0, false);
+            assert!(!gamestate.players[0].is_alive());
+            assert_eq!(gamestate.players[0].lives, 0);
+
+            update_round(&mut gamestate);
+            let versus = gamestate.versus.as_ref().unwrap();
+            assert!(versus.is_round_over());
+            assert_eq!(gamestate.players[1].score, round as u128);
+            assert_eq!(gamestate.players[0].score, 0);
+            next_round(&mut gamestate);
+        }
+
+        assert_eq!(
+            gamestate.versus.as_ref().unwrap().get_match_winner(),
+            Some(1)
+        );
+        assert!(gamestate.over);
+        assert_eq!(gamestate.menu, vec![String::from("Start")]);
+    }
+
+    #[test]
+    fn nobody_scores_a_draw() {
+        let mut gamestate = duel();
+        for player in &mut gamestate.players {
+            player.spaceship.set_life(false);
+        }
+        update_round(&mut gamestate);
+        assert!(gamestate.versus.as_ref().unwrap().is_round_over());
+        assert!(gamestate.players.iter().all(|player| player.score == 0));
+
+        next_round(&mut gamestate);
+        assert_eq!(gamestate.versus.as_ref().unwrap().get_round(), 2);
+        assert!(!gamestate.over);
+    }
+
+    #[test]
+    fn asteroids_stay_whole() {
+        let mut gamestate = duel();
+        let position = gamestate.asteroids[0].get_position();
+        let mut missile = Missile::new(position, 0.0, 0.0, false, position);
+        missile.set_owner(0);
+        gamestate.missiles = vec![missile];
+        gamestate.resolve_collisions();
+
+        assert!(gamestate.asteroid_changes.is_empty());
+        assert_eq!(gamestate.players[0].score, 0);
+    }
+}
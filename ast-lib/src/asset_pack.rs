@@ -0,0 +1,462 @@
+use crate::{MISSING_TEXTURE, NamedTexture};
+use ::rand::{
+    distributions::{Distribution, WeightedIndex},
+    thread_rng,
+};
+use futures::stream::{self, StreamExt};
+use macroquad::prelude::{FilterMode, load_texture};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+use walkdir::WalkDir;
+
+// ==== CONSTANTS ====
+
+/// Name of the manifest file at the root of every pack
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Pack used when none is selected
+pub const DEFAULT_PACK: &str = "./assets/textures";
+
+/// Sprites the game needs, a pack missing one of them is invalid
+pub const REQUIRED_SPRITES: [&str; 2] = ["asteroid", "background"];
+
+// ==== MANIFEST ====
+
+/// How a sprite is sampled when scaled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Filter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+impl From<Filter> for FilterMode {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Linear => FilterMode::Linear,
+            Filter::Nearest => FilterMode::Nearest,
+        }
+    }
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// One image of a sprite, picked randomly according to its weight
+#[derive(Debug, Clone, Deserialize)]
+pub struct Variant {
+    /// Path relative to the root of the pack
+    pub file: PathBuf,
+    /// Relative chance of being picked, weights do not need to sum to anything
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+impl Variant {
+    /// Name of the loaded texture, the path of the file in the pack so two folders
+    /// can hold images with the same file name
+    pub fn texture_name(&self) -> String {
+        self.file
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sprite {
+    pub variants: Vec<Variant>,
+    #[serde(default)]
+    pub filter: Filter,
+}
+
+/// Content of `manifest.json`
+#[derive(Debug, Clone, Deserialize)]
+pub struct PackManifest {
+    pub name: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub sprites: BTreeMap<String, Sprite>,
+}
+
+/// Everything that can be wrong with a pack
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackError {
+    ManifestUnreadable(PathBuf, String),
+    ManifestInvalid(PathBuf, String),
+    MissingSprite(String),
+    NoVariant(String),
+    InvalidWeight(String, PathBuf, f32),
+    MissingFile(String, PathBuf),
+    TextureUnreadable(String, PathBuf),
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackError::ManifestUnreadable(path, e) => {
+                write!(f, "Couldn't read the manifest {:?}: {}", path, e)
+            }
+            PackError::ManifestInvalid(path, e) => {
+                write!(f, "The manifest {:?} is invalid: {}", path, e)
+            }
+            PackError::MissingSprite(sprite) => {
+                write!(f, "The sprite {:?} is required but not declared", sprite)
+            }
+            PackError::NoVariant(sprite) => write!(f, "The sprite {:?} has no variant", sprite),
+            PackError::InvalidWeight(sprite, file, weight) => write!(
+                f,
+                "The variant {:?} of {:?} has an invalid weight ({}), it must be positive",
+                file, sprite, weight
+            ),
+            PackError::MissingFile(sprite, file) => {
+                write!(f, "The file {:?} of {:?} does not exist", file, sprite)
+            }
+            PackError::TextureUnreadable(sprite, file) => {
+                write!(
+                    f,
+                    "The file {:?} of {:?} is not a valid image",
+                    file, sprite
+                )
+            }
+        }
+    }
+}
+
+impl PackManifest {
+    /// Read `manifest.json` from the root of a pack
+    pub fn read(root: &Path) -> Result<Self, PackError> {
+        let path = root.join(MANIFEST_FILE);
+        let json = read_to_string(&path)
+            .map_err(|e| PackError::ManifestUnreadable(path.clone(), e.to_string()))?;
+        serde_json::from_str(&json).map_err(|e| PackError::ManifestInvalid(path, e.to_string()))
+    }
+
+    /// Check the manifest against the files of the pack, every problem is reported
+    pub fn validate(&self, root: &Path) -> Vec<PackError> {
+        let mut errors = Vec::new();
+
+        for required in REQUIRED_SPRITES {
+            if !self.sprites.contains_key(required) {
+                errors.push(PackError::MissingSprite(required.to_string()));
+            }
+        }
+
+        for (name, sprite) in &self.sprites {
+            if sprite.variants.is_empty() {
+                errors.push(PackError::NoVariant(name.clone()));
+            }
+            for variant in &sprite.variants {
+                if !variant.weight.is_finite() || variant.weight <= 0.0 {
+                    errors.push(PackError::InvalidWeight(
+                        name.clone(),
+                        variant.file.clone(),
+                        variant.weight,
+                    ));
+                }
+                if !root.join(&variant.file).is_file() {
+                    errors.push(PackError::MissingFile(name.clone(), variant.file.clone()));
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Images in the pack that no sprite uses, probably a mistake in the manifest
+    pub fn unused_files(&self, root: &Path) -> Vec<PathBuf> {
+        let used: Vec<&PathBuf> = self
+            .sprites
+            .values()
+            .flat_map(|sprite| sprite.variants.iter().map(|v| &v.file))
+            .collect();
+
+        WalkDir::new(root)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| e.path().strip_prefix(root).ok().map(Path::to_path_buf))
+            .filter(|p| {
+                matches!(
+                    p.extension().and_then(|x| x.to_str()).map(|s| s.to_lowercase()),
+                    Some(ext) if ["png", "jpg", "jpeg"].contains(&ext.as_str())
+                )
+            })
+            .filter(|p| !used.contains(&p))
+            .collect()
+    }
+}
+
+// ==== PACK ====
+
+/// Loaded textures of a pack, grouped by sprite with their weights
+pub struct AssetPack {
+    pub root: PathBuf,
+    pub manifest: Option<PackManifest>,
+    sprites: BTreeMap<String, Vec<(NamedTexture, f32)>>,
+}
+
+impl AssetPack {
+    /// A pack without any sprite, everything is drawn with the missing texture
+    pub fn empty() -> Self {
+        AssetPack {
+            root: PathBuf::new(),
+            manifest: None,
+            sprites: BTreeMap::new(),
+        }
+    }
+
+    /// Read, validate and load a pack, nothing is loaded if the manifest has errors
+    pub async fn load(root: &Path) -> Result<Self, Vec<PackError>> {
+        let manifest = PackManifest::read(root).map_err(|e| vec![e])?;
+        let errors = manifest.validate(root);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        for unused in manifest.unused_files(root) {
+            eprintln!("[WARN] {:?} is not used by the manifest", unused);
+        }
+
+        let variants: Vec<(String, Filter, Variant)> = manifest
+            .sprites
+            .iter()
+            .flat_map(|(name, sprite)| {
+                sprite
+                    .variants
+                    .iter()
+                    .map(move |variant| (name.clone(), sprite.filter, variant.clone()))
+            })
+            .collect();
+
+        let concurrency = 8usize;
+        let loaded_vec = stream::iter(variants.into_iter().map(|(name, filter, variant)| {
+            let path = root.join(&variant.file);
+            async move {
+                let texture = load_texture(path.to_string_lossy().as_ref()).await;
+                (name, filter, variant, texture)
+            }
+        }))
+        .buffered(concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+        let mut errors = Vec::new();
+        let mut sprites: BTreeMap<String, Vec<(NamedTexture, f32)>> = BTreeMap::new();
+        for (name, filter, variant, texture) in loaded_vec {
+            match texture {
+                Ok(texture) => {
+                    texture.set_filter(filter.into());
+                    crate::log!("[INFO] Loaded texture: {:?}", variant.file);
+                    sprites.entry(name).or_default().push((
+                        NamedTexture {
+                            texture,
+                            name: variant.texture_name(),
+                        },
+                        variant.weight,
+                    ));
+                }
+                Err(_) => errors.push(PackError::TextureUnreadable(name, variant.file)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(AssetPack {
+            root: root.to_path_buf(),
+            manifest: Some(manifest),
+            sprites,
+        })
+    }
+
+    /// Pick a variant of a sprite according to the weights of the manifest
+    pub fn select_variant(&self, sprite: &str) -> NamedTexture {
+        let Some(variants) = self.sprites.get(sprite) else {
+            return MISSING_TEXTURE.clone();
+        };
+        match WeightedIndex::new(variants.iter().map(|(_, weight)| *weight)) {
+            Ok(dist) => variants[dist.sample(&mut thread_rng())].0.clone(),
+            Err(_) => MISSING_TEXTURE.clone(),
+        }
+    }
+
+    /// First variant of a sprite, for sprites that only have one
+    pub fn get(&self, sprite: &str) -> NamedTexture {
+        self.sprites
+            .get(sprite)
+            .and_then(|variants| variants.first())
+            .map(|(texture, _)| texture.clone())
+            .unwrap_or_else(|| MISSING_TEXTURE.clone())
+    }
+
+    /// Variant of a sprite by its path in the pack, used to keep the same look after a reload
+    pub fn find_variant(&self, sprite: &str, name: &str) -> Option<NamedTexture> {
+        self.sprites
+            .get(sprite)?
+            .iter()
+            .find(|(texture, _)| texture.name == name)
+            .map(|(texture, _)| texture.clone())
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.manifest
+            .as_ref()
+            .map(|m| m.name.as_str())
+            .unwrap_or("None")
+    }
+}
+
+/// Pack currently in use, set at startup with [`set_asset_pack`]
+pub static ASSET_PACK: Lazy<RwLock<AssetPack>> = Lazy::new(|| RwLock::new(AssetPack::empty()));
+
+pub fn set_asset_pack(pack: AssetPack) {
+    *ASSET_PACK.write().unwrap() = pack;
+}
+
+/// Shortcut for [`AssetPack::select_variant`] on the current pack
+pub fn select_variant(sprite: &str) -> NamedTexture {
+    ASSET_PACK.read().unwrap().select_variant(sprite)
+}
+
+/// Shortcut for [`AssetPack::get`] on the current pack
+pub fn get_sprite(sprite: &str) -> NamedTexture {
+    ASSET_PACK.read().unwrap().get(sprite)
+}
+
+/// Load the pack at `root`, falling back to the default pack and then to an empty one.
+///
+/// Validation errors are printed instead of panicking.
+pub async fn load_asset_pack_or_default(root: &Path) -> AssetPack {
+    let mut candidates = vec![root.to_path_buf()];
+    if root != Path::new(DEFAULT_PACK) {
+        candidates.push(PathBuf::from(DEFAULT_PACK));
+    }
+
+    for candidate in candidates {
+        match AssetPack::load(&candidate).await {
+            Ok(pack) => {
+                crate::log!("[INFO] Asset pack: {} ({:?})", pack.get_name(), candidate);
+                return pack;
+            }
+            Err(errors) => {
+                eprintln!("[ERROR] The asset pack {:?} can't be used:", candidate);
+                for error in errors {
+                    eprintln!("    {}", error);
+                }
+            }
+        }
+    }
+
+    eprintln!("[WARN] No usable asset pack, textures will be missing");
+    AssetPack::empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Empty pack in a temporary directory with the given image files
+    fn pack_dir(name: &str, files: &[&str]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("pack-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        root
+    }
+
+    fn manifest(sprites: &str) -> PackManifest {
+        serde_json::from_str(&format!(
+            r#"{{ "name": "Test", "sprites": {{ {} }} }}"#,
+            sprites
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn valid_pack_has_no_error() {
+        let root = pack_dir("valid", &["bg.png", "rocks/a.png", "rocks/b.png"]);
+        let manifest = manifest(
+            r#""background": { "variants": [{ "file": "bg.png" }] },
+            "asteroid": { "variants": [{ "file": "rocks/a.png" }, { "file": "rocks/b.png", "weight": 0.5 }] }"#,
+        );
+        assert_eq!(manifest.validate(&root), vec![]);
+        assert_eq!(manifest.unused_files(&root), Vec::<PathBuf>::new());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let root = pack_dir("invalid", &["bg.png", "rock.png"]);
+        let mut manifest = manifest(
+            r#""background": { "variants": [] },
+            "ship": { "variants": [{ "file": "rock.png", "weight": 0.0 }, { "file": "gone.png", "weight": -1.0 }] }"#,
+        );
+        manifest.sprites.get_mut("ship").unwrap().variants[0].weight = f32::NAN;
+
+        let errors = manifest.validate(&root);
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert_eq!(errors[0], PackError::MissingSprite("asteroid".to_string()));
+        assert_eq!(errors[1], PackError::NoVariant("background".to_string()));
+        assert!(matches!(
+            &errors[2],
+            PackError::InvalidWeight(sprite, file, weight)
+                if sprite == "ship" && file == Path::new("rock.png") && weight.is_nan()
+        ));
+        assert_eq!(
+            errors[3],
+            PackError::InvalidWeight("ship".to_string(), PathBuf::from("gone.png"), -1.0)
+        );
+        assert_eq!(
+            errors[4],
+            PackError::MissingFile("ship".to_string(), PathBuf::from("gone.png"))
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn images_missing_from_the_manifest_are_unused() {
+        let root = pack_dir(
+            "unused",
+            &["bg.png", "rocks/a.png", "rocks/extra.JPG", "notes.txt"],
+        );
+        let manifest = manifest(
+            r#""background": { "variants": [{ "file": "bg.png" }] },
+            "asteroid": { "variants": [{ "file": "rocks/a.png" }] }"#,
+        );
+        assert_eq!(
+            manifest.unused_files(&root),
+            vec![PathBuf::from("rocks/extra.JPG")]
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn texture_names_keep_the_folder() {
+        let manifest = manifest(
+            r#""asteroid": { "variants": [{ "file": "a/rock.png" }, { "file": "b/rock.png" }] }"#,
+        );
+        let names: Vec<String> = manifest.sprites["asteroid"]
+            .variants
+            .iter()
+            .map(Variant::texture_name)
+            .collect();
+        assert_eq!(names, vec!["a/rock.png", "b/rock.png"]);
+    }
+}
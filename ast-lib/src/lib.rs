@@ -1,12 +1,21 @@
-use std::{sync::atomic::{AtomicU64, Ordering}, collections::BTreeMap, path::PathBuf};
-use macroquad::prelude::{Vec2, Texture2D, FilterMode, Image, load_texture};
-use walkdir::WalkDir;
-use futures::stream::{self, StreamExt};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use macroquad::miniquad::{RawId, TextureId};
+use macroquad::prelude::{Vec2, Texture2D, FilterMode, Image, get_time, screen_height, screen_width};
 use once_cell::sync::Lazy;
-use ::rand::{distributions::{Distribution, WeightedIndex}, thread_rng};
+
+pub mod asset_pack;
+pub mod rng;
+pub use asset_pack::{ASSET_PACK, AssetPack, PackError, get_sprite, select_variant, set_asset_pack};
+pub use rng::{SimulationRng, seed_simulation_rng, simulation_rng};
 
 // ==== CONSTANTS ====
 pub static NEXT_UID: AtomicU64 = AtomicU64::new(1);
+/// Running without a window, see [`set_headless`]
+pub static HEADLESS: AtomicBool = AtomicBool::new(false);
+/// Screen size reported without a window
+pub const HEADLESS_SCREEN: Vec2 = Vec2::new(1920.0, 1080.0);
+/// Stdout only carries a protocol, see [`reserve_stdout`]
+pub static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
 
 // ==== STRUCTURES ====
 
@@ -19,6 +28,13 @@ pub struct NamedTexture {
 // ==== TEXTURES ====
 
 pub static MISSING_TEXTURE: Lazy<NamedTexture> = Lazy::new(|| {
+    // Nothing can be uploaded without a window, the texture is never drawn anyway
+    if is_headless() {
+        return NamedTexture {
+            texture: Texture2D::from_miniquad_texture(TextureId::from_raw_id(RawId::OpenGl(0))),
+            name: "MISSING_TEXTURE".to_string(),
+        };
+    }
     let pixels: Vec<u8> = vec![
         255, 0, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 0, 255, 255,
     ];
@@ -36,105 +52,6 @@ pub static MISSING_TEXTURE: Lazy<NamedTexture> = Lazy::new(|| {
     }
 });
 
-pub static TEXTURE_SET: Lazy<BTreeMap<PathBuf, Texture2D>> = Lazy::new(|| {
-    pollster::block_on(async {
-        load_textures_recursive_parallel(PathBuf::from("./assets/textures")).await
-    })
-});
-
-/// Load textures recursively
-pub async fn load_textures_recursive_parallel(root: PathBuf) -> BTreeMap<PathBuf, Texture2D> {
-    let paths: Vec<PathBuf> = WalkDir::new(&root)
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter(|e| e.file_type().is_file())
-        .filter_map(|e| {
-            let p = e.into_path();
-            match p
-                .extension()
-                .and_then(|x| x.to_str())
-                .map(|s| s.to_lowercase())
-            {
-                Some(ext) if ["png", "jpg", "jpeg"].contains(&ext.as_str()) => Some(p),
-                _ => None,
-            }
-        })
-        .collect();
-
-    let concurrency = 8usize;
-    let loaded_vec = stream::iter(paths.into_iter().map(|path| {
-        let root_clone = root.clone();
-        async move {
-            let rel_path = path.strip_prefix(&root_clone).unwrap().to_path_buf();
-            let tex = load_texture(path.to_string_lossy().as_ref())
-                .await
-                .expect(&format!("Failed to load: {:?}", rel_path));
-
-            if rel_path.ends_with("background2.png") {
-                tex.set_filter(FilterMode::Nearest);
-            }
-
-            println!("[INFO] Loaded texture: {:?}", rel_path);
-            (rel_path, tex)
-        }
-    }))
-    .buffer_unordered(concurrency)
-    .collect::<Vec<_>>()
-    .await;
-
-    let mut textures = BTreeMap::new();
-    for (k, v) in loaded_vec {
-        textures.insert(k, v);
-    }
-    textures
-}
-
-/// Random texture selector with strict weights
-/// `custom_weights` must be provided and sum to 100.0
-pub fn select_weighted_texture<'a>(
-    textures: &'a BTreeMap<PathBuf, Texture2D>,
-    subdir: &str,
-    custom_weights: Vec<f32>,
-) -> Option<NamedTexture> {
-    // Filter keys to only include ones in the given subdir
-    let filtered_keys: Vec<&PathBuf> = textures
-        .keys()
-        .filter(|k| k.to_string_lossy().contains(subdir))
-        .collect();
-
-    let amount = filtered_keys.len();
-    if amount == 0 {
-        return None; // no textures in this subdir
-    }
-
-    if custom_weights.len() != amount {
-        panic!(
-            "Number of weights ({}) does not match number of textures ({})",
-            custom_weights.len(),
-            amount
-        );
-    }
-
-    let sum: f32 = custom_weights.iter().sum();
-    if (sum - 100.0).abs() > f32::EPSILON {
-        panic!("Sum of weights must be exactly 100.0, got {}", sum);
-    }
-
-    let mut rng = thread_rng();
-    let dist = WeightedIndex::new(&custom_weights).unwrap();
-    let selected_index = dist.sample(&mut rng);
-
-    let selected_path = filtered_keys[selected_index];
-    textures.get(selected_path).map(|tex| NamedTexture {
-        texture: tex.clone(),
-        name: selected_path
-            .file_stem()
-            .unwrap()
-            .to_string_lossy()
-            .to_string(),
-    })
-}
-
 // ==== TRAITS ====
 
 /// Trait defining entity behavior
@@ -177,6 +94,54 @@ pub trait CosmicEntity {
     }
 }
 
+// ==== HEADLESS ====
+
+/// Run the simulation without a window, before creating anything: the screen, the clock
+/// and the textures of macroquad are replaced by stand-ins
+pub fn set_headless(headless: bool) {
+    HEADLESS.store(headless, Ordering::Relaxed);
+}
+
+pub fn is_headless() -> bool {
+    HEADLESS.load(Ordering::Relaxed)
+}
+
+/// Print the log lines on stderr from now on, call it before anything is printed
+/// when stdout carries the answers of the remote control
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::Relaxed)
+}
+
+/// `println!` for the log lines, on stderr once [`reserve_stdout`] was called
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if $crate::is_stdout_reserved() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Size of the window, [`HEADLESS_SCREEN`] without one
+pub fn screen_size() -> Vec2 {
+    if is_headless() {
+        HEADLESS_SCREEN
+    } else {
+        Vec2::new(screen_width(), screen_height())
+    }
+}
+
+/// Seconds since the start of the game, always 0 without a window
+pub fn current_time() -> f64 {
+    if is_headless() { 0.0 } else { get_time() }
+}
+
 // ==== MISC ====
 
 /// Free function to generate unique IDs
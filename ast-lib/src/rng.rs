@@ -0,0 +1,81 @@
+use ::rand::{Error, RngCore, SeedableRng, rngs::SmallRng, thread_rng};
+use std::cell::RefCell;
+
+thread_local! {
+    /// Generator of everything random in the simulation, seeded from the entropy
+    /// until [`seed_simulation_rng`] makes it reproducible.
+    ///
+    /// One per thread, so simulations running side by side (tests, two peers of a
+    /// network game in one process) do not draw each other's numbers.
+    static SIMULATION_RNG: RefCell<SmallRng> =
+        RefCell::new(SmallRng::from_rng(thread_rng()).expect("thread_rng never fails"));
+}
+
+/// Restart the simulation generator of this thread from `seed`, two games seeded alike
+/// draw the same numbers
+pub fn seed_simulation_rng(seed: u64) {
+    SIMULATION_RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+/// Handle on the simulation generator of the current thread, used like `thread_rng()`
+///
+/// Every draw borrows the generator only while drawing, so handles can be nested freely.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimulationRng;
+
+pub fn simulation_rng() -> SimulationRng {
+    SimulationRng
+}
+
+impl RngCore for SimulationRng {
+    fn next_u32(&mut self) -> u32 {
+        SIMULATION_RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        SIMULATION_RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        SIMULATION_RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        SIMULATION_RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::spawn;
+
+    fn draws() -> Vec<u64> {
+        (0..8).map(|_| simulation_rng().next_u64()).collect()
+    }
+
+    #[test]
+    fn same_seed_same_numbers() {
+        seed_simulation_rng(7);
+        let first = draws();
+        seed_simulation_rng(7);
+        assert_eq!(draws(), first);
+        seed_simulation_rng(8);
+        assert_ne!(draws(), first);
+    }
+
+    #[test]
+    fn threads_do_not_share_the_generator() {
+        seed_simulation_rng(7);
+        let expected = draws();
+        seed_simulation_rng(7);
+        // Another thread drawing in between does not move this one
+        spawn(|| {
+            seed_simulation_rng(7);
+            draws()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(draws(), expected);
+    }
+}
@@ -1,14 +1,22 @@
-use ::rand::{Rng, thread_rng};
 use ast_core::{
-    asteroid::Asteroid,
-    floating_text::LifetimedText,
+    achievements::{ACHIEVEMENT_DEFINITIONS, ACHIEVEMENTS_FILE},
+    ai::{Autopilot, Difficulty},
+    audio::{MacroquadBackend, SoundEffect},
+    i18n::Localization,
     gamestate::{Gamestate, TICKS},
-    key_bindings, menus,
-};
-use ast_lib::{Change, CosmicEntity, apply_changes};
-use macroquad::prelude::{
-    GOLD, GREEN, MAGENTA, Vec2, WHITE, get_time, next_frame, screen_height, screen_width, vec2,
+    highscores::{HIGHSCORES_FILE, HighScoreTable},
+    hot_reload::{HotReload, Watched},
+    input::{self, InputBackend, RdevBackend, ScriptedBackend},
+    key_bindings, menus, netplay, player,
+    remote::RemoteControl,
+    settings::{SETTINGS_FILE, Settings},
+    spectator::SpectatorServer,
+    stats::{LifetimeStats, STATS_FILE},
+    versus::Versus,
 };
+use ast_lib::{asset_pack, set_asset_pack};
+use macroquad::prelude::{Vec2, get_time, next_frame, screen_height, screen_width};
+use std::path::Path;
 
 fn window_conf() -> macroquad::window::Conf {
     macroquad::window::Conf {
@@ -35,28 +43,51 @@ Altough it's outdated and vastly different
 /// # Responsibilities
 ///
 /// - **Initialization**
+///   - Loads the asset pack given with `--pack <dir>`, falling back to `assets/textures`.
 ///   - Creates a new [`Gamestate`] instance.
 ///   - Loads keybindings from `keybindings.json`, falling back to defaults if the file is missing.
-///   - Starts listening for key events.
+///     Invalid entries are reported on screen and actions missing from the file get their defaults.
+///   - Loads the string catalogs from `assets/lang`.
+///   - Restores the options of the settings menu from `settings.json`.
+///   - Synthesizes the sound effects, unless `--no-audio` is passed.
+///   - Starts the input backend: the saved setting or the game window, `--input global` for the
+///     system wide hook, `--input-script <file>` to replay scripted events.
+///   - Adds the co-op players asked with `--players <n>`, the extra ones read their bindings
+///     from `keybindings_p<n>.json`, created with the defaults when missing.
+///   - Switches to the versus rounds with `--versus`, with two players at least.
+///   - Gives each ship `--lives <n>` lives and the run `--waves <n>` waves, one of each by default.
+///   - Hosts a network game with `--host <port>` or joins one with `--join <address>`. It
+///     lasts one run, then the local setup comes back.
+///   - Streams snapshots to the local TCP clients on `--spectate <port>`, every
+///     `--spectate-interval <ticks>` ticks.
+///   - Hands the last player to the bot with `--autopilot <easy|normal|hard>`, alone for the
+///     attract mode and the soak tests, or as the opponent in versus.
+///   - Reads JSON-RPC requests on stdin with `--remote`, the simulation then only moves
+///     when they ask for it. The answers are the only lines on stdout, the logs go to stderr.
+///   - Watches the keybindings, the asset pack and the catalogs to reload them when they change.
 ///   - Sets up random number generation and time tracking.
 ///
 /// - **Game loop**
 ///   Runs continuously until the player exits. Each iteration:
 ///
-///   1. Computes delta time and updates the simulation accumulator.
-///   2. Register input by recording currently held keys.
-///   3. Performs fixed-timestep updates while the accumulator exceeds the tick interval:
-///      - Removes missiles outside the screen bounds.
-///      - Applies queued changes to the missile list.
-///      - Updates asteroids:
-///        - Removes destroyed asteroids.
-///        - Detects and resolves collisions with the spaceship.
-///        - Detects and resolves collisions with missiles, updating score and spawning text.
-///      - Removes expired text popups.
-///      - Randomly discards asteroids (cooldown-based).
-///   4. Renders the current state (`update_all`, `draw_all`).
-///   5. Processes input handling via [`key_bindings::handle_input`].
-///   6. Draws simulation menus and executes menu-driven actions such as:
+///   1. Reloads the watched files that changed, parse errors are shown on screen.
+///   2. Computes delta time and updates the simulation accumulator.
+///   3. Polls the input backend, records currently held keys and samples the gameplay
+///      actions into the per-tick input buffer. In a network game only the local player
+///      is sampled and the packets of the peer are exchanged. Under remote control the
+///      requests are answered instead, stepping the simulation themselves.
+///   4. Performs fixed-timestep updates while the accumulator exceeds the tick interval:
+///      - Waits for the input of the peer in a network game, the tick runs on a later frame.
+///        The bot decides the input of its ship otherwise.
+///      - Runs [`Gamestate::tick`] with the buffered input of each player: thrust, rotation
+///        and firing, collisions, score, text popups, the random discards of the game over,
+///        then everything moves by one tick.
+///      - Hashes the state for the peer in a network game.
+///      - Publishes the snapshot of the tick to the spectators.
+///   5. Renders the current state (`update_all`, `draw_all`).
+///   6. Draws simulation menus, the key captured by the controls editor is read there.
+///   7. Processes menu, camera and time input via [`key_bindings::handle_input`], which
+///      clears the events of the frame, then executes menu-driven actions such as:
 ///      - Exit the game
 ///      - Clear all asteroids and reset score
 ///      - Split all asteroids
@@ -70,8 +101,8 @@ Altough it's outdated and vastly different
 /// - The simulation speed is managed using `delta_time` and `accumulator`
 ///   to ensure fixed-timestep updates (`TICKS` constant).
 /// - Collisions are resolved deterministically inside the asteroid update loop.
-/// - Randomness (`thread_rng`) is used for asteroid splitting, collision knockback,
-///   and text placement offsets.
+/// - Randomness of the simulation (asteroid splitting, collision knockback) comes from the
+///   seeded `simulation_rng`, the text placement offsets use `thread_rng`.
 /// - UI and menus are drawn each frame after simulation updates.
 ///
 /// # Panics
@@ -83,25 +114,226 @@ Altough it's outdated and vastly different
 #[macroquad::main(window_conf)]
 
 async fn main() {
+    // Under remote control stdout only carries the answers, the logs go to stderr
+    let remote_control = std::env::args().any(|arg| arg == "--remote");
+    if remote_control {
+        ast_lib::reserve_stdout();
+    }
+
+    // Load the asset pack first, asteroids pick their texture when created
+    let pack = std::env::args()
+        .skip_while(|arg| arg != "--pack")
+        .nth(1)
+        .unwrap_or_else(|| asset_pack::DEFAULT_PACK.to_string());
+    set_asset_pack(asset_pack::load_asset_pack_or_default(Path::new(&pack)).await);
+
     let mut gamestate = Gamestate::new();
 
     let mut previous_time = 0.0;
-    let mut end_cooldown = get_time();
-    let mut rng = thread_rng();
+
+    // Initialize the string catalogs
+    gamestate.locale = Localization::load("./assets/lang");
+
+    // Initialize the settings of the settings menu
+    match Settings::load(SETTINGS_FILE) {
+        Ok(settings) => settings.apply(&mut gamestate),
+        Err(e) => {
+            if Path::new(SETTINGS_FILE).exists() {
+                eprintln!("[ERROR] Couldn't load the settings: {}", e);
+            }
+        }
+    }
+
+    // Initialize the high scores
+    match HighScoreTable::load(HIGHSCORES_FILE) {
+        Ok(table) => gamestate.highscores = table,
+        Err(e) => {
+            if Path::new(HIGHSCORES_FILE).exists() {
+                eprintln!("[ERROR] Couldn't load the high scores: {}", e);
+            }
+        }
+    }
+
+    // Initialize the lifetime statistics
+    match LifetimeStats::load(STATS_FILE) {
+        Ok(stats) => gamestate.lifetime_stats = stats,
+        Err(e) => {
+            if Path::new(STATS_FILE).exists() {
+                eprintln!("[ERROR] Couldn't load the statistics: {}", e);
+            }
+        }
+    }
+
+    // Initialize the achievements
+    if let Err(e) = gamestate
+        .achievements
+        .load_definitions(ACHIEVEMENT_DEFINITIONS)
+    {
+        eprintln!("[ERROR] Couldn't load the achievements: {}", e);
+    }
+    if let Err(e) = gamestate.achievements.load_unlocked(ACHIEVEMENTS_FILE)
+        && Path::new(ACHIEVEMENTS_FILE).exists()
+    {
+        eprintln!("[ERROR] Couldn't load the unlocked achievements: {}", e);
+    }
+
+    // Initialize audio, the null backend stays in place for headless runs
+    if std::env::args().any(|arg| arg == "--no-audio") {
+        ast_lib::log!("Audio disabled");
+    } else {
+        gamestate
+            .audio
+            .set_backend(Box::new(MacroquadBackend::synthesize().await));
+    }
+
+    // Watch the files that can be edited while playing
+    let mut hot_reload = HotReload::new(key_bindings::KEYBINDINGS_FILE, &pack, "./assets/lang");
 
     // Initialize keybindings
-    let keybindings: key_bindings::KeyBindings =
-        match key_bindings::KeyBindings::load("keybindings.json") {
-            Ok(kb) => kb,
-            Err(_) => {
-                println!("Couldn't find the keybinds file");
+    let mut keybindings: key_bindings::KeyBindings =
+        match key_bindings::KeyBindings::load(key_bindings::KEYBINDINGS_FILE) {
+            Ok((kb, report)) => {
+                hot_reload.report_bindings(&report);
+                kb
+            }
+            Err(e) => {
+                if Path::new(key_bindings::KEYBINDINGS_FILE).exists() {
+                    hot_reload.report(Watched::KeyBindings, e.to_string());
+                } else {
+                    ast_lib::log!("Couldn't find the keybinds file");
+                }
                 key_bindings::default_keybindings()
             }
         };
-    println!("Don't forget that keybinds do not update automatically if the file is there !");
-    keybindings.start_listening();
+
+    // Initialize the input backend, the game window only unless asked otherwise
+    let mut input_backend: Box<dyn InputBackend> = if let Some(script) = std::env::args()
+        .skip_while(|arg| arg != "--input-script")
+        .nth(1)
+    {
+        match ScriptedBackend::load(&script) {
+            Ok(backend) => Box::new(backend),
+            Err(e) => {
+                eprintln!("[ERROR] Couldn't load the input script {:?}: {}", script, e);
+                Box::new(input::MacroquadBackend::new())
+            }
+        }
+    } else {
+        // The command line wins over the saved setting
+        match std::env::args()
+            .skip_while(|arg| arg != "--input")
+            .nth(1)
+            .unwrap_or_else(|| keybindings.get_settings().backend.clone())
+            .as_str()
+        {
+            "global" => Box::new(RdevBackend::new()),
+            "window" => Box::new(input::MacroquadBackend::new()),
+            other => {
+                eprintln!(
+                    "[WARN] Unknown input backend {:?}, using the window input",
+                    other
+                );
+                Box::new(input::MacroquadBackend::new())
+            }
+        }
+    };
+    keybindings.start_backend(input_backend.as_mut());
+
+    // Local co-op, the extra players have their own bindings on the same keyboard
+    let player_count = std::env::args()
+        .skip_while(|arg| arg != "--players")
+        .nth(1)
+        .map(|count| {
+            count.parse().unwrap_or_else(|_| {
+                eprintln!("[WARN] Invalid number of players {:?}, playing alone", count);
+                1
+            })
+        })
+        .unwrap_or(1);
+    // Versus needs an opponent
+    let player_count = if std::env::args().any(|arg| arg == "--versus") {
+        gamestate.versus = Some(Versus::new());
+        player_count.max(2)
+    } else {
+        player_count
+    };
+    // Respawns and waves, the original game has a single life and a single field
+    if let Some(lives) = std::env::args().skip_while(|arg| arg != "--lives").nth(1) {
+        match lives.parse::<u8>() {
+            Ok(lives) => gamestate.starting_lives = lives.max(1),
+            Err(_) => eprintln!("[WARN] Invalid number of lives {:?}", lives),
+        }
+    }
+    if let Some(waves) = std::env::args().skip_while(|arg| arg != "--waves").nth(1) {
+        match waves.parse::<u32>() {
+            Ok(waves) => gamestate.max_waves = waves.max(1),
+            Err(_) => eprintln!("[WARN] Invalid number of waves {:?}", waves),
+        }
+    }
+    gamestate.set_player_count(player_count);
+    let mut profiles: Vec<key_bindings::KeyBindings> = (1..gamestate.players.len())
+        .map(|index| player::load_profile(index, &keybindings))
+        .collect();
+
+    // Network game, two peers exchanging their inputs
+    let host_port = std::env::args().skip_while(|arg| arg != "--host").nth(1);
+    let join_address = std::env::args().skip_while(|arg| arg != "--join").nth(1);
+    let mut netplay: Option<netplay::Session> = match (host_port, join_address) {
+        (Some(port), _) => match port.parse() {
+            Ok(port) => netplay::Session::host(port)
+                .inspect_err(|e| eprintln!("[ERROR] Couldn't host on port {}: {}", port, e))
+                .ok(),
+            Err(_) => {
+                eprintln!("[ERROR] Invalid port {:?}", port);
+                None
+            }
+        },
+        (None, Some(address)) => netplay::Session::join(&address)
+            .inspect_err(|e| eprintln!("[ERROR] Couldn't join {:?}: {}", address, e))
+            .ok(),
+        (None, None) => None,
+    };
+
+    // Bot playing the last ship
+    let mut autopilot: Option<Autopilot> = std::env::args()
+        .skip_while(|arg| arg != "--autopilot")
+        .nth(1)
+        .and_then(|difficulty| {
+            difficulty
+                .parse::<Difficulty>()
+                .inspect_err(|e| eprintln!("[ERROR] {}", e))
+                .ok()
+        })
+        .map(|difficulty| Autopilot::new(gamestate.players.len() - 1, difficulty));
+
+    // Remote control for the automation
+    let mut remote = remote_control.then(RemoteControl::start);
+
+    // Spectator stream for the external tools
+    let mut spectator: Option<SpectatorServer> = std::env::args()
+        .skip_while(|arg| arg != "--spectate")
+        .nth(1)
+        .and_then(|port| {
+            let interval = std::env::args()
+                .skip_while(|arg| arg != "--spectate-interval")
+                .nth(1)
+                .and_then(|ticks| ticks.parse().ok())
+                .unwrap_or(SpectatorServer::DEFAULT_INTERVAL);
+            let port = port
+                .parse()
+                .inspect_err(|_| eprintln!("[ERROR] Invalid spectator port {:?}", port))
+                .ok()?;
+            SpectatorServer::start(port, interval)
+                .inspect_err(|e| {
+                    eprintln!("[ERROR] Couldn't start the spectator stream: {}", e)
+                })
+                .ok()
+        });
 
     loop {
+        // Reload the files edited since the last frame
+        hot_reload.poll(&mut gamestate, &mut keybindings).await;
+
         // Delta-time
         gamestate.delta_time = (get_time() - previous_time) * gamestate.simulation_speed;
         previous_time = get_time();
@@ -112,133 +344,110 @@ async fn main() {
             y: screen_height(),
         };
 
-        // Register input
+        // Register input, the gameplay actions are buffered for the simulation ticks
+        input_backend.poll();
         gamestate.input = keybindings.get_held_keys();
+        let contexts = gamestate.get_input_contexts();
+        for profile in &mut profiles {
+            profile.set_contexts(contexts.clone());
+        }
+        keybindings.set_contexts(contexts);
+        if let Some(remote) = &mut remote {
+            remote.poll(&mut gamestate);
+        } else if let Some(session) = &mut netplay {
+            session.poll(&mut gamestate);
+            let local = session.get_local_player();
+            gamestate.players[local].input_buffer.sample(&keybindings);
+        } else {
+            for (index, player) in gamestate.players.iter_mut().enumerate() {
+                let profile = match index {
+                    0 => &keybindings,
+                    _ => &profiles[index - 1],
+                };
+                player.input_buffer.sample(profile);
+            }
+        }
 
-        // Update simulation
-        gamestate.accumulator += gamestate.delta_time;
-        while gamestate.accumulator >= TICKS {
-            gamestate.loop_number += 1;
-
-            gamestate.discard_out_of_bounds_missiles(&bounds);
-
-            apply_changes(&mut gamestate.missiles, &mut gamestate.missile_changes);
+        if let Some(server) = &mut spectator {
+            server.accept();
+        }
 
-            // Main part of the loop
-            for asteroid in &mut gamestate.asteroids {
-                if asteroid.get_size() == 0.0 {
-                    gamestate
-                        .asteroid_changes
-                        .push(Change::Remove(asteroid.get_id()));
-                }
-                // Check the collision between the SPACESHIP and ASTEROIDS
-                let spaceship_collision = asteroid.collides_with(&gamestate.spaceship);
-
-                if gamestate.spaceship.get_life()
-                    && gamestate.spaceship.get_invulnerability() <= 0.0
-                    && spaceship_collision
-                {
-                    gamestate
-                        .asteroid_changes
-                        .push(Change::Remove(asteroid.get_id()));
-                    asteroid.split(
-                        (gamestate.number_of_asteroids + gamestate.asteroids_children as u32)
-                            < gamestate.asteroid_limit.into(),
-                        gamestate.asteroids_children,
-                        &mut gamestate.asteroid_changes,
-                    );
-
-                    gamestate.spaceship.modify_shield(
-                        -(5.0 / 3.0 * (asteroid.get_size() / Asteroid::SCALE + 1.0).powf(2.0)),
-                    );
-
-                    gamestate.spaceship.set_invulnerability(0.4);
-                    gamestate
-                        .spaceship
-                        .set_speed(gamestate.spaceship.get_speed() * 0.25);
-                    gamestate
-                        .spaceship
-                        .add_rotation(rng.gen_range(1.0..std::f32::consts::PI));
-
-                    if gamestate.spaceship.get_shield() <= 0.0 {
-                        gamestate.spaceship.set_life(false);
+        // Update simulation, only through the requests under remote control
+        if remote.is_none() {
+            gamestate.accumulator += gamestate.delta_time;
+        }
+        while gamestate.accumulator >= TICKS {
+            let tick_inputs: Vec<key_bindings::TickInput> = match &mut netplay {
+                Some(session) => {
+                    let local = session.get_local_player();
+                    match session.advance(&mut gamestate.players[local].input_buffer) {
+                        Some(inputs) => inputs,
+                        // Wait for the peer instead of piling up ticks to catch up
+                        None => {
+                            gamestate.accumulator = gamestate.accumulator.min(TICKS);
+                            break;
+                        }
                     }
                 }
-
-                // Missile collisions
-                for missile in &gamestate.missiles {
-                    let collision = asteroid.collides_with(missile);
-                    if collision {
-                        gamestate
-                            .missile_changes
-                            .push(Change::Remove(missile.get_id()));
-                        let already_removed = gamestate
-                            .asteroid_changes
-                            .iter()
-                            .any(|c| matches!(c, Change::Remove(a) if *a == asteroid.get_id()));
-
-                        if !already_removed {
-                            asteroid.split(
-                                (gamestate.number_of_asteroids
-                                    + gamestate.asteroids_children as u32)
-                                    < gamestate.asteroid_limit.into(),
-                                gamestate.asteroids_children,
-                                &mut gamestate.asteroid_changes,
-                            );
-
-                            let score = asteroid
-                                .grant_score(&mut gamestate.score[0], &gamestate.multipliers);
-
-                            gamestate.text_changes.push(Change::Add(LifetimedText::new(
-                                match score {
-                                    100 => 1.0,
-                                    200 => 2.0,
-                                    300 => 2.5,
-                                    _ => 1.0,
-                                },
-                                missile.get_position()
-                                    + vec2(
-                                        rng.gen_range(-50.0..=50.0), // Random X offset
-                                        rng.gen_range(-100.0..=100.0),
-                                    ), // Random Y offset,
-                                0.0,
-                                score.to_string(),
-                                match score {
-                                    100 => 30.0,
-                                    200 => 35.0,
-                                    300 => 45.0,
-                                    _ => 30.0,
-                                },
-                                match score {
-                                    100 => GREEN,
-                                    200 => GOLD,
-                                    300 => MAGENTA,
-                                    _ => WHITE,
-                                },
-                                -30.0,
-                            )));
-                        }
+                None => {
+                    let mut inputs: Vec<key_bindings::TickInput> = gamestate
+                        .players
+                        .iter_mut()
+                        .map(|player| player.input_buffer.next_tick())
+                        .collect();
+                    if let Some(bot) = &mut autopilot
+                        && let Some(input) = inputs.get_mut(bot.get_player())
+                    {
+                        *input = bot.next_tick(&gamestate);
                     }
+                    inputs
                 }
+            };
+            gamestate.tick(&tick_inputs);
+            if let Some(session) = &mut netplay {
+                session.check_state(&gamestate);
+            }
+            if let Some(server) = &mut spectator {
+                server.publish(&gamestate);
             }
-
-            gamestate.discard_texts();
-
-            // End Game gimmick
-            gamestate.discard_asteroids_random(get_time(), &mut end_cooldown, 50);
 
             gamestate.accumulator -= TICKS;
         }
 
+        // A network game lasts one run, or until the peer is gone
+        if let Some(session) = &netplay
+            && session.is_connected()
+        {
+            let finished = if session.is_lost() {
+                eprintln!("[WARN] Lost the netplay peer, playing locally");
+                true
+            } else if gamestate.over || gamestate.win {
+                ast_lib::log!("[INFO] Netplay game finished");
+                true
+            } else {
+                false
+            };
+            if finished && let Some(session) = netplay.take() {
+                session.close(&mut gamestate);
+            }
+        }
+
         gamestate.update_all();
         gamestate.draw_all();
 
-        // Apply keybindings actions
-        key_bindings::handle_input(&mut gamestate, &keybindings);
-
-        // Menu and UI
+        // Menu and UI, the controls editor reads the keys typed before they are cleared
         menus::draw_simulation(&gamestate);
-        let action = menus::menu_draw(&mut gamestate, bounds.x, bounds.y);
+        let action = menus::menu_draw(&mut gamestate, &mut keybindings, bounds.x, bounds.y);
+
+        // Apply the menu and time actions in the layers of the current screen
+        key_bindings::handle_input(&mut gamestate, &keybindings);
+        hot_reload.draw_errors(&gamestate.locale);
+        if let Some(session) = &netplay {
+            session.draw_status(&gamestate.locale);
+        }
+        if !action.is_empty() {
+            gamestate.audio.play(SoundEffect::UiBlip);
+        }
         match action.as_str() {
             "Exit" => break,
             "Clear" => {
@@ -254,15 +463,20 @@ async fn main() {
             _ => (),
         }
         if gamestate.exit {
-            println!("Exiting...");
+            ast_lib::log!("Exiting...");
             break;
         }
 
         next_frame().await;
     }
 
-    // Save keybindings on exit
-    if let Err(e) = keybindings.save("keybindings.json") {
+    // Save keybindings on exit, unless the file has errors: the user's edits would be lost
+    if hot_reload.has_errors(Watched::KeyBindings) {
+        eprintln!(
+            "[WARN] {} has errors, it was not overwritten",
+            key_bindings::KEYBINDINGS_FILE
+        );
+    } else if let Err(e) = keybindings.save(key_bindings::KEYBINDINGS_FILE) {
         eprintln!("Failed to save keybindings: {:?}", e);
     }
 }